@@ -36,27 +36,193 @@ export async function setup(deviceApiKey: string, username: string, password: st
   return res.json();
 }
 
-export async function login(deviceApiKey: string, password: string, totpCode: string) {
+export interface AcceptedInvitation {
+  device_api_key: string
+  totp_secret: string
+  recovery_codes: string[]
+}
+
+/** Create an account from an invitation code. Returns this device's key and the TOTP secret. */
+export async function acceptInvitation(
+  code: string,
+  username: string,
+  password: string
+): Promise<AcceptedInvitation> {
   const passwordHash = await hashPassword(password);
+  const res = await fetch(`${BASE}/api/auth/accept-invitation`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ code, username, password: passwordHash }),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** Log in with a 6-digit TOTP code, or with a one-time recovery code in its place. */
+export async function login(
+  deviceApiKey: string,
+  password: string,
+  code: string
+): Promise<SessionTokens> {
+  const passwordHash = await hashPassword(password);
+  const trimmed = code.trim();
+  const isTotp = /^\d{6}$/.test(trimmed);
   const res = await fetch(`${BASE}/api/auth/login`, {
     method: 'POST',
     headers: { 'Content-Type': 'application/json' },
     body: JSON.stringify({
       device_api_key: deviceApiKey,
       password: passwordHash,
-      totp_code: totpCode,
+      ...(isTotp ? { totp_code: trimmed } : { recovery_code: trimmed }),
     }),
   });
   if (!res.ok) throw new Error(await res.text());
   return res.json();
 }
 
-export async function refreshToken(oldToken: string): Promise<{ token: string }> {
+export interface SessionTokens {
+  token: string
+  /** Works once: each refresh returns the one to use next. */
+  refresh_token: string
+}
+
+export async function refreshToken(refreshToken: string): Promise<SessionTokens> {
   const res = await fetch(`${BASE}/api/auth/refresh`, {
     method: 'POST',
     headers: { 'Content-Type': 'application/json' },
-    body: JSON.stringify({ token: oldToken }),
+    body: JSON.stringify({ refresh_token: refreshToken }),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** Replace the recovery codes with a new set; the old ones stop working. */
+export async function regenerateRecoveryCodes(token: string): Promise<{ recovery_codes: string[] }> {
+  const res = await fetch(`${BASE}/api/auth/recovery-codes`, {
+    method: 'POST',
+    headers: { Authorization: `Bearer ${token}` },
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** End the session of `token`. */
+export async function logout(token: string) {
+  const res = await fetch(`${BASE}/api/auth/logout`, {
+    method: 'POST',
+    headers: { Authorization: `Bearer ${token}` },
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
+
+function toB64url(buf: ArrayBuffer): string {
+  const bytes = new Uint8Array(buf);
+  let bin = '';
+  for (const b of bytes) bin += String.fromCharCode(b);
+  return btoa(bin).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+}
+
+function fromB64url(s: string): Uint8Array {
+  const bin = atob(s.replace(/-/g, '+').replace(/_/g, '/'));
+  return Uint8Array.from(bin, (c) => c.charCodeAt(0));
+}
+
+/** Log in with a passkey and the password; the passkey identifies the device. */
+export async function loginWithPasskey(password: string): Promise<SessionTokens> {
+  const optionsRes = await fetch(`${BASE}/api/auth/webauthn/login/options`, { method: 'POST' });
+  if (!optionsRes.ok) throw new Error(await optionsRes.text());
+  const options = await optionsRes.json();
+  const credential = (await navigator.credentials.get({
+    publicKey: {
+      challenge: fromB64url(options.challenge),
+      rpId: options.rp_id,
+      timeout: options.timeout_ms,
+      userVerification: 'preferred',
+    },
+  })) as PublicKeyCredential | null;
+  if (!credential) throw new Error('Passkey login cancelled');
+  const response = credential.response as AuthenticatorAssertionResponse;
+  const passwordHash = await hashPassword(password);
+  const res = await fetch(`${BASE}/api/auth/login`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({
+      password: passwordHash,
+      passkey: {
+        credential_id: toB64url(credential.rawId),
+        client_data_json: toB64url(response.clientDataJSON),
+        authenticator_data: toB64url(response.authenticatorData),
+        signature: toB64url(response.signature),
+      },
+    }),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export interface Passkey {
+  id: string
+  credential_id: string
+  name: string | null
+  created_at: string
+  last_used_at: string | null
+}
+
+/** Register a passkey on this device so it can log in without a device key or TOTP code. */
+export async function registerPasskey(token: string, name?: string): Promise<Passkey> {
+  const headers = { 'Content-Type': 'application/json', Authorization: `Bearer ${token}` };
+  const optionsRes = await fetch(`${BASE}/api/auth/webauthn/register/options`, {
+    method: 'POST',
+    headers,
+  });
+  if (!optionsRes.ok) throw new Error(await optionsRes.text());
+  const options = await optionsRes.json();
+  const credential = (await navigator.credentials.create({
+    publicKey: {
+      challenge: fromB64url(options.challenge),
+      rp: { id: options.rp_id, name: 'Dev PM Agent' },
+      user: {
+        id: fromB64url(options.user_id),
+        name: options.user_name,
+        displayName: options.user_name,
+      },
+      pubKeyCredParams: options.algorithms.map((alg: number) => ({ type: 'public-key', alg })),
+      excludeCredentials: options.exclude_credentials.map((id: string) => ({
+        type: 'public-key',
+        id: fromB64url(id),
+      })),
+      authenticatorSelection: { residentKey: 'required', userVerification: 'preferred' },
+      attestation: 'none',
+      timeout: options.timeout_ms,
+    },
+  })) as PublicKeyCredential | null;
+  if (!credential) throw new Error('Passkey registration cancelled');
+  const response = credential.response as AuthenticatorAttestationResponse;
+  const res = await fetch(`${BASE}/api/auth/webauthn/register`, {
+    method: 'POST',
+    headers,
+    body: JSON.stringify({
+      client_data_json: toB64url(response.clientDataJSON),
+      attestation_object: toB64url(response.attestationObject),
+      name,
+    }),
   });
   if (!res.ok) throw new Error(await res.text());
   return res.json();
 }
+
+export async function listPasskeys(token: string): Promise<Passkey[]> {
+  const res = await fetch(`${BASE}/api/auth/webauthn/credentials`, {
+    headers: { Authorization: `Bearer ${token}` },
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function deletePasskey(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/auth/webauthn/credentials/${id}`, {
+    method: 'DELETE',
+    headers: { Authorization: `Bearer ${token}` },
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
@@ -0,0 +1,78 @@
+const BASE = import.meta.env.VITE_RELAYER_URL || '';
+
+function authHeaders(token: string) {
+  return {
+    'Content-Type': 'application/json',
+    Authorization: `Bearer ${token}`,
+  };
+}
+
+export interface Chat {
+  id: string
+  title: string
+  repo_path: string | null
+  translator_model: string | null
+  workload_model: string | null
+  cursor_chat_id: string | null
+  archived: boolean
+  last_activity_at: string
+  created_at: string
+  updated_at: string
+}
+
+export async function listChats(token: string, archived = false): Promise<Chat[]> {
+  const res = await fetch(`${BASE}/api/chats${archived ? '?archived=true' : ''}`, {
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function createChat(
+  token: string,
+  data: { title?: string; repo_path?: string; translator_model?: string; workload_model?: string }
+): Promise<Chat> {
+  const res = await fetch(`${BASE}/api/chats`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function updateChat(
+  token: string,
+  id: string,
+  data: {
+    title?: string
+    repo_path?: string
+    translator_model?: string
+    workload_model?: string
+    archived?: boolean
+  }
+): Promise<Chat> {
+  const res = await fetch(`${BASE}/api/chats/${id}`, {
+    method: 'PATCH',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function deleteChat(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/chats/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
+
+export async function listChatCommands(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/chats/${id}/commands`, {
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
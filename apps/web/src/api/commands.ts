@@ -7,6 +7,7 @@ function authHeaders(token: string) {
   };
 }
 
+/** Create a command. Retrying with the same `idempotencyKey` returns the original command. */
 export async function createCommand(
   token: string,
   data: {
@@ -16,23 +17,68 @@ export async function createCommand(
     translator_model?: string
     workload_model?: string
     cursor_chat_id?: string
-  }
+    chat_id?: string
+    max_runtime_secs?: number
+    review_prompt?: boolean
+  },
+  idempotencyKey: string = crypto.randomUUID()
 ) {
   const res = await fetch(`${BASE}/api/commands`, {
     method: 'POST',
-    headers: authHeaders(token),
+    headers: { ...authHeaders(token), 'Idempotency-Key': idempotencyKey },
     body: JSON.stringify(data),
   });
   if (!res.ok) throw new Error(await res.text());
   return res.json();
 }
 
+export interface ListCommandsParams {
+  cursor?: string
+  limit?: number
+  status?: string
+  repo_path?: string
+  context_mode?: string
+  model?: string
+  cursor_chat_id?: string
+  /** Created by this schedule. */
+  schedule_id?: string
+  created_after?: string
+  created_before?: string
+  /** Ran a shell command containing this text, e.g. `cargo test`. */
+  ran?: string
+  /** Wrote a file whose path contains this text. */
+  wrote?: string
+}
+
+/** One page of commands, newest first. Pass `next_cursor` back as `cursor` for older ones. */
+export async function listCommandsPage(token: string, params: ListCommandsParams = {}) {
+  const query = new URLSearchParams()
+  for (const [key, value] of Object.entries(params)) {
+    if (value !== undefined && value !== '') query.set(key, String(value))
+  }
+  const qs = query.toString()
+  const res = await fetch(`${BASE}/api/commands${qs ? `?${qs}` : ''}`, {
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json() as Promise<{ commands: any[]; next_cursor: string | null }>;
+}
+
+/** Most recent page of commands. */
 export async function listCommands(token: string) {
-  const res = await fetch(`${BASE}/api/commands`, {
+  const page = await listCommandsPage(token);
+  return page.commands;
+}
+
+/** Full-text search; snippets wrap matches in <mark> and must be escaped before rendering. */
+export async function searchCommands(token: string, q: string, limit?: number) {
+  const query = new URLSearchParams({ q })
+  if (limit !== undefined) query.set('limit', String(limit))
+  const res = await fetch(`${BASE}/api/commands/search?${query}`, {
     headers: authHeaders(token),
   });
   if (!res.ok) throw new Error(await res.text());
-  return res.json();
+  return res.json() as Promise<{ command: any; snippet: string; rank: number }[]>;
 }
 
 export async function deleteCommand(token: string, id: string) {
@@ -42,3 +88,86 @@ export async function deleteCommand(token: string, id: string) {
   });
   if (!res.ok) throw new Error(await res.text());
 }
+
+/** Approve, edit or reject the proposed prompt of a command in `awaiting_approval`. */
+export async function reviewCommandPrompt(
+  token: string,
+  id: string,
+  review: { action: 'approve' | 'reject' } | { action: 'edit'; prompt: string }
+) {
+  const res = await fetch(`${BASE}/api/commands/${id}/review`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(review),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** Re-run a finished command; unset overrides keep the original's values and chat. */
+export async function rerunCommand(
+  token: string,
+  id: string,
+  overrides: {
+    repo_path?: string
+    context_mode?: string
+    translator_model?: string
+    workload_model?: string
+    max_runtime_secs?: number
+    /** Start a new chat instead of resuming the original's. */
+    fresh_chat?: boolean
+  } = {}
+) {
+  const res = await fetch(`${BASE}/api/commands/${id}/rerun`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(overrides),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export interface OutputEvent {
+  seq: number
+  kind: 'append' | 'replace'
+  content: string
+  created_at?: string
+}
+
+/** Output events after `after` (last seq applied), for resuming a live output stream. */
+export async function listCommandEvents(token: string, id: string, after = 0) {
+  const res = await fetch(`${BASE}/api/commands/${id}/events?after=${after}`, {
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json() as Promise<OutputEvent[]>;
+}
+
+/** Apply a `command_output` event to the output shown so far. */
+export function applyOutputEvent(output: string | null | undefined, event: Pick<OutputEvent, 'kind' | 'content'>) {
+  return event.kind === 'append' ? (output ?? '') + event.content : event.content
+}
+
+export interface ToolCall {
+  call_id: string
+  seq: number
+  tool: string
+  kind: 'shell' | 'read' | 'write' | 'search' | 'list' | 'other'
+  target?: string | null
+  args?: unknown
+  started_at: string
+  completed_at?: string | null
+  duration_ms?: number | null
+  exit_code?: number | null
+  error?: string | null
+  result?: string | null
+}
+
+/** Tool calls the agent made while running a command, in start order. */
+export async function listToolCalls(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/commands/${id}/tool-calls`, {
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json() as Promise<ToolCall[]>;
+}
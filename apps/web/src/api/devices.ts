@@ -16,3 +16,199 @@ export async function reserveCode(token: string, code: string) {
   if (!res.ok) throw new Error(await res.text());
   return res.json();
 }
+
+export interface Device {
+  id: string
+  device_id: string
+  name: string | null
+  role: 'executor' | 'controller'
+  registered_at: string
+  last_seen_at: string
+  /** The device making the request. */
+  current: boolean
+}
+
+export async function listDevices(token: string): Promise<Device[]> {
+  const res = await fetch(`${BASE}/api/devices`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function renameDevice(token: string, id: string, name: string): Promise<Device> {
+  const res = await fetch(`${BASE}/api/devices/${id}`, {
+    method: 'PATCH',
+    headers: authHeaders(token),
+    body: JSON.stringify({ name }),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** Revoke a device; its sessions end immediately. The last controller can't be revoked. */
+export async function revokeDevice(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/devices/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
+
+export interface ExecutorKey {
+  id: string
+  executor_id: string
+  /** First characters of the key, to tell keys apart. */
+  prefix: string
+  label: string | null
+  created_at: string
+  expires_at: string | null
+  revoked_at: string | null
+  last_used_at: string | null
+}
+
+/** `api_key` is only returned once; configure it as the executor's EXECUTOR_API_KEY. */
+export interface IssuedExecutorKey {
+  api_key: string
+  key: ExecutorKey
+}
+
+export async function registerExecutor(token: string, name?: string): Promise<IssuedExecutorKey> {
+  const res = await fetch(`${BASE}/api/executors`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify({ name }),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function listExecutorKeys(token: string, executorId: string): Promise<ExecutorKey[]> {
+  const res = await fetch(`${BASE}/api/executors/${executorId}/keys`, {
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** Issue a new key; the executor's other keys stop working after `grace_secs` (default 24h). */
+export async function issueExecutorKey(
+  token: string,
+  executorId: string,
+  data: { label?: string; grace_secs?: number } = {}
+): Promise<IssuedExecutorKey> {
+  const res = await fetch(`${BASE}/api/executors/${executorId}/keys`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function revokeExecutorKey(token: string, executorId: string, keyId: string) {
+  const res = await fetch(`${BASE}/api/executors/${executorId}/keys/${keyId}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
+
+export interface Session {
+  id: string
+  /** The controller device the session was opened on. */
+  device_id: string
+  device_name: string | null
+  created_at: string
+  last_used_at: string
+  /** When the session ends unless refreshed. */
+  expires_at: string
+  /** The session making the request. */
+  current: boolean
+}
+
+export async function listSessions(token: string): Promise<Session[]> {
+  const res = await fetch(`${BASE}/api/sessions`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** Revoke a session; its JWT and refresh token stop working immediately. */
+export async function revokeSession(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/sessions/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
+
+/** Revoke every session except the current one. */
+export async function revokeOtherSessions(token: string): Promise<{ revoked: number }> {
+  const res = await fetch(`${BASE}/api/sessions`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export type TokenScope =
+  | 'commands:read'
+  | 'commands:create'
+  | 'commands:write'
+  | 'chats:read'
+  | 'chats:write'
+  | 'templates:read'
+  | 'templates:write'
+  | 'workflows:read'
+  | 'workflows:write'
+  | 'workflows:run'
+  | 'schedules:read'
+  | 'schedules:write'
+  | 'repos:read'
+  | 'repos:write'
+  | 'files:read'
+  | 'stats:read'
+
+export interface AccessToken {
+  id: string
+  name: string
+  prefix: string
+  scopes: TokenScope[]
+  created_at: string
+  expires_at: string | null
+  last_used_at: string | null
+  revoked_at: string | null
+}
+
+/** `token` is only returned once; send it as `Authorization: Bearer <token>`. */
+export interface CreatedAccessToken {
+  token: string
+  access_token: AccessToken
+}
+
+/** Create a personal access token. `expires_in_secs` defaults to 90 days; 0 never expires. */
+export async function createAccessToken(
+  token: string,
+  data: { name: string; scopes: TokenScope[]; expires_in_secs?: number }
+): Promise<CreatedAccessToken> {
+  const res = await fetch(`${BASE}/api/tokens`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function listAccessTokens(token: string): Promise<AccessToken[]> {
+  const res = await fetch(`${BASE}/api/tokens`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function revokeAccessToken(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/tokens/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
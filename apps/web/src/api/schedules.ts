@@ -0,0 +1,78 @@
+const BASE = import.meta.env.VITE_RELAYER_URL || '';
+
+function authHeaders(token: string) {
+  return {
+    'Content-Type': 'application/json',
+    Authorization: `Bearer ${token}`,
+  };
+}
+
+export interface Schedule {
+  id: string
+  name: string
+  /** 5-field cron expression, UTC. */
+  cron: string
+  input: string
+  context_mode: string | null
+  repo_path: string | null
+  translator_model: string | null
+  workload_model: string | null
+  max_runtime_secs: number | null
+  enabled: boolean
+  /** Null while disabled. */
+  next_run_at: string | null
+  last_run_at: string | null
+  last_command_id: string | null
+  created_at: string
+  updated_at: string
+}
+
+export interface ScheduleFields {
+  name: string
+  cron: string
+  input: string
+  context_mode?: string
+  repo_path?: string
+  translator_model?: string
+  workload_model?: string
+  max_runtime_secs?: number
+  enabled?: boolean
+}
+
+export async function listSchedules(token: string): Promise<Schedule[]> {
+  const res = await fetch(`${BASE}/api/schedules`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function createSchedule(token: string, data: ScheduleFields): Promise<Schedule> {
+  const res = await fetch(`${BASE}/api/schedules`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function updateSchedule(
+  token: string,
+  id: string,
+  data: Partial<ScheduleFields>
+): Promise<Schedule> {
+  const res = await fetch(`${BASE}/api/schedules/${id}`, {
+    method: 'PATCH',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function deleteSchedule(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/schedules/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
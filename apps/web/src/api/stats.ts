@@ -0,0 +1,37 @@
+const BASE = import.meta.env.VITE_RELAYER_URL || '';
+
+function authHeaders(token: string) {
+  return {
+    'Content-Type': 'application/json',
+    Authorization: `Bearer ${token}`,
+  };
+}
+
+export interface UsageStats {
+  /** Model, repo path or day (YYYY-MM-DD), depending on `group_by`. */
+  key: string | null
+  commands: number
+  duration_ms: number
+  input_tokens: number
+  output_tokens: number
+  cache_read_tokens: number
+  cache_write_tokens: number
+  cost_usd: number
+}
+
+/** Run time, token usage and cost of commands created in [since, until), most expensive first. */
+export async function getUsageStats(
+  token: string,
+  params: { group_by?: 'model' | 'repo' | 'day'; since?: string; until?: string } = {}
+): Promise<UsageStats[]> {
+  const query = new URLSearchParams()
+  for (const [key, value] of Object.entries(params)) {
+    if (value) query.set(key, value)
+  }
+  const qs = query.toString()
+  const res = await fetch(`${BASE}/api/stats${qs ? `?${qs}` : ''}`, {
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
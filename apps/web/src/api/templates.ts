@@ -0,0 +1,60 @@
+const BASE = import.meta.env.VITE_RELAYER_URL || '';
+
+function authHeaders(token: string) {
+  return {
+    'Content-Type': 'application/json',
+    Authorization: `Bearer ${token}`,
+  };
+}
+
+/** Command template stored on the relayer; `id` is what commands send as `context_mode`. */
+export interface CommandTemplate {
+  id: string
+  name: string
+  description: string | null
+  /** Translator instructions; `{repo}` and `{next_sprint_number}` are filled in by the executor. */
+  body: string
+  created_at: string
+  updated_at: string
+}
+
+export async function listTemplates(token: string): Promise<CommandTemplate[]> {
+  const res = await fetch(`${BASE}/api/templates`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function createTemplate(
+  token: string,
+  data: { id: string; name: string; description?: string; body: string }
+): Promise<CommandTemplate> {
+  const res = await fetch(`${BASE}/api/templates`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function updateTemplate(
+  token: string,
+  id: string,
+  data: { name?: string; description?: string; body?: string }
+): Promise<CommandTemplate> {
+  const res = await fetch(`${BASE}/api/templates/${id}`, {
+    method: 'PATCH',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function deleteTemplate(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/templates/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
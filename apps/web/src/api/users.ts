@@ -0,0 +1,97 @@
+const BASE = import.meta.env.VITE_RELAYER_URL || '';
+
+function authHeaders(token: string) {
+  return {
+    'Content-Type': 'application/json',
+    Authorization: `Bearer ${token}`,
+  };
+}
+
+/** Owners manage users; members only see their own work; viewers read everything. */
+export type UserRole = 'owner' | 'member' | 'viewer'
+
+export interface User {
+  id: string
+  username: string
+  role: UserRole
+  created_at: string
+  disabled_at: string | null
+  /** Whether this is the signed-in user. */
+  current: boolean
+}
+
+export interface Invitation {
+  id: string
+  role: UserRole
+  created_at: string
+  expires_at: string
+  accepted_at: string | null
+  accepted_by: string | null
+  revoked_at: string | null
+}
+
+/** `code` is only returned once; the invitee enters it to create their account. */
+export interface CreatedInvitation {
+  code: string
+  invitation: Invitation
+}
+
+/** All users. Owners only. */
+export async function listUsers(token: string): Promise<User[]> {
+  const res = await fetch(`${BASE}/api/users`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function getMe(token: string): Promise<User> {
+  const res = await fetch(`${BASE}/api/users/me`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function updateUserRole(token: string, id: string, role: UserRole): Promise<User> {
+  const res = await fetch(`${BASE}/api/users/${id}`, {
+    method: 'PATCH',
+    headers: authHeaders(token),
+    body: JSON.stringify({ role }),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** Remove a user: their devices are revoked; their commands are kept. */
+export async function removeUser(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/users/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
+
+/** Invite a user. `expires_in_secs` defaults to 7 days (at most 30). */
+export async function createInvitation(
+  token: string,
+  data: { role: UserRole; expires_in_secs?: number }
+): Promise<CreatedInvitation> {
+  const res = await fetch(`${BASE}/api/invitations`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function listInvitations(token: string): Promise<Invitation[]> {
+  const res = await fetch(`${BASE}/api/invitations`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function revokeInvitation(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/invitations/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
@@ -0,0 +1,135 @@
+const BASE = import.meta.env.VITE_RELAYER_URL || '';
+
+function authHeaders(token: string) {
+  return {
+    'Content-Type': 'application/json',
+    Authorization: `Bearer ${token}`,
+  };
+}
+
+export interface WorkflowStep {
+  name: string
+  /** Template id; free-form when null. */
+  context_mode?: string | null
+  /** Command input; `{input}` is replaced with the run's input. */
+  input: string
+  /** Wait for approval before starting this step. */
+  approval?: boolean
+}
+
+export interface Workflow {
+  id: string
+  name: string
+  description: string | null
+  steps: WorkflowStep[]
+  created_at: string
+  updated_at: string
+}
+
+export type WorkflowRunStatus = 'running' | 'awaiting_approval' | 'done' | 'failed' | 'cancelled'
+
+export interface WorkflowRun {
+  id: string
+  workflow_id: string | null
+  name: string
+  status: WorkflowRunStatus
+  current_step: number
+  input: string
+  repo_path: string | null
+  chat_id: string | null
+  steps: (WorkflowStep & { command_id: string | null; status: string | null })[]
+  created_at: string
+  updated_at: string
+}
+
+export async function listWorkflows(token: string): Promise<Workflow[]> {
+  const res = await fetch(`${BASE}/api/workflows`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function createWorkflow(
+  token: string,
+  data: { id: string; name: string; description?: string; steps: WorkflowStep[] }
+): Promise<Workflow> {
+  const res = await fetch(`${BASE}/api/workflows`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function updateWorkflow(
+  token: string,
+  id: string,
+  data: { name?: string; description?: string; steps?: WorkflowStep[] }
+): Promise<Workflow> {
+  const res = await fetch(`${BASE}/api/workflows/${id}`, {
+    method: 'PATCH',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function deleteWorkflow(token: string, id: string) {
+  const res = await fetch(`${BASE}/api/workflows/${id}`, {
+    method: 'DELETE',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+}
+
+export async function startWorkflowRun(
+  token: string,
+  id: string,
+  data: {
+    input: string
+    repo_path?: string
+    translator_model?: string
+    workload_model?: string
+    chat_id?: string
+  }
+): Promise<WorkflowRun> {
+  const res = await fetch(`${BASE}/api/workflows/${id}/runs`, {
+    method: 'POST',
+    headers: authHeaders(token),
+    body: JSON.stringify(data),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function listWorkflowRuns(token: string): Promise<WorkflowRun[]> {
+  const res = await fetch(`${BASE}/api/workflow-runs`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function getWorkflowRun(token: string, id: string): Promise<WorkflowRun> {
+  const res = await fetch(`${BASE}/api/workflow-runs/${id}`, { headers: authHeaders(token) });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+/** Start the step a run is waiting on. */
+export async function approveWorkflowRun(token: string, id: string): Promise<WorkflowRun> {
+  const res = await fetch(`${BASE}/api/workflow-runs/${id}/approve`, {
+    method: 'POST',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
+
+export async function cancelWorkflowRun(token: string, id: string): Promise<WorkflowRun> {
+  const res = await fetch(`${BASE}/api/workflow-runs/${id}/cancel`, {
+    method: 'POST',
+    headers: authHeaders(token),
+  });
+  if (!res.ok) throw new Error(await res.text());
+  return res.json();
+}
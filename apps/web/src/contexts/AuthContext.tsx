@@ -8,7 +8,13 @@ import {
   type ReactNode,
 } from 'react'
 import { refreshToken } from '../api/auth'
-import { getToken, setToken as storeSetToken, clearToken } from '../stores/auth'
+import {
+  getToken,
+  setToken as storeSetToken,
+  clearToken,
+  getRefreshToken,
+  setRefreshToken,
+} from '../stores/auth'
 
 /** Parse JWT payload to get exp (seconds since epoch). Returns null if invalid. */
 function getJwtExp(token: string): number | null {
@@ -68,8 +74,14 @@ export function AuthProvider({ children }: { children: ReactNode }) {
       const delayMs = Math.max(0, (refreshAt - nowSecs) * 1000)
       timeoutRef.current = setTimeout(async () => {
         timeoutRef.current = null
+        const refresh = getRefreshToken()
+        if (!refresh) {
+          clearAuth()
+          return
+        }
         try {
-          const { token: newToken } = await refreshToken(t)
+          const { token: newToken, refresh_token } = await refreshToken(refresh)
+          setRefreshToken(refresh_token)
           setToken(newToken)
           /* useEffect will re-run and schedule next refresh */
         } catch {
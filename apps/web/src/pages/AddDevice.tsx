@@ -1,6 +1,7 @@
 import { useState } from 'react'
 import { useNavigate } from 'react-router-dom'
 import { reserveCode } from '../api/devices'
+import { registerPasskey } from '../api/auth'
 import { generateWordCode } from '../utils/wordCode'
 import { useAuth } from '../contexts/AuthContext'
 
@@ -9,8 +10,26 @@ export default function AddDevice() {
   const [code, setCode] = useState('')
   const [loading, setLoading] = useState(false)
   const [error, setError] = useState('')
+  const [passkeyAdded, setPasskeyAdded] = useState(false)
   const navigate = useNavigate()
 
+  async function handleAddPasskey() {
+    if (!token) {
+      setError('Login required')
+      return
+    }
+    setError('')
+    setLoading(true)
+    try {
+      await registerPasskey(token)
+      setPasskeyAdded(true)
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Failed to add passkey')
+    } finally {
+      setLoading(false)
+    }
+  }
+
   async function handleKeygen() {
     if (!token) {
       setError('Login required')
@@ -48,6 +67,16 @@ export default function AddDevice() {
             <p className="mt-2 font-mono text-lg tracking-wider">{code}</p>
           </div>
         )}
+        <button
+          onClick={handleAddPasskey}
+          disabled={loading}
+          className="btn btn-secondary w-full"
+        >
+          Add passkey to this device
+        </button>
+        {passkeyAdded && (
+          <p className="text-sm text-muted">Passkey added. Next time, log in with passkey + password.</p>
+        )}
         {error && <p className="error-text">{error}</p>}
         <button
           onClick={() => navigate('/chat')}
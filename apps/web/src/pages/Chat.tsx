@@ -1,9 +1,10 @@
 import { useState, useEffect, useCallback } from 'react'
 import { useNavigate } from 'react-router-dom'
-import { createCommand, listCommands, deleteCommand } from '../api/commands'
+import { applyOutputEvent, createCommand, listCommands, deleteCommand } from '../api/commands'
 import { useWebSocket } from '../hooks/useWebSocket'
 import { listModels } from '../api/models'
 import { listRepos } from '../api/repos'
+import { logout } from '../api/auth'
 import { useAuth } from '../contexts/AuthContext'
 import { TaskConfigSelector, type Repo } from '../components/TaskConfigSelector'
 import { TEMPLATES, getTemplateById, getDefaultTemplate } from '../templates'
@@ -174,6 +175,17 @@ export default function Chat() {
     (event: MessageEvent) => {
       try {
         const msg = JSON.parse(event.data as string)
+        if (msg.type === 'command_output' && msg.payload) {
+          const { id, kind, content } = msg.payload
+          setCommands((prev) => {
+            const idx = prev.findIndex((c) => c.id === id)
+            if (idx < 0) return prev
+            const next = [...prev]
+            next[idx] = { ...next[idx], output: applyOutputEvent(next[idx].output, { kind, content }) }
+            return next
+          })
+          return
+        }
         if (msg.type === 'command_update' && msg.payload) {
           const { id, status, output, summary, cursor_chat_id } = msg.payload
           setCommands((prev) => {
@@ -227,6 +239,7 @@ export default function Chat() {
   }
 
   function handleLogout() {
+    if (token) logout(token).catch(() => {})
     clearAuth()
     navigate('/login')
   }
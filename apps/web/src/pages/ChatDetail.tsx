@@ -1,6 +1,6 @@
 import { useState, useEffect, useCallback, useRef } from 'react'
 import { useNavigate, useParams } from 'react-router-dom'
-import { createCommand, listCommands } from '../api/commands'
+import { applyOutputEvent, createCommand, listCommands } from '../api/commands'
 import { useWebSocket } from '../hooks/useWebSocket'
 import { listModels } from '../api/models'
 import { useAuth } from '../contexts/AuthContext'
@@ -153,6 +153,17 @@ export default function ChatDetail() {
     (event: MessageEvent) => {
       try {
         const msg = JSON.parse(event.data as string)
+        if (msg.type === 'command_output' && msg.payload) {
+          const { id, kind, content } = msg.payload
+          setCommands((prev) => {
+            const idx = prev.findIndex((c) => c.id === id)
+            if (idx < 0) return prev
+            const next = [...prev]
+            next[idx] = { ...next[idx], output: applyOutputEvent(next[idx].output, { kind, content }) }
+            return next
+          })
+          return
+        }
         if (msg.type === 'command_update' && msg.payload) {
           const { id, status, output, summary, cursor_chat_id } = msg.payload
           setCommands((prev) => {
@@ -1,7 +1,7 @@
 import { useState } from 'react'
 import { Link, useNavigate } from 'react-router-dom'
-import { login } from '../api/auth'
-import { getDeviceKey, setDeviceKey, clearDeviceKey } from '../stores/auth'
+import { login, loginWithPasskey } from '../api/auth'
+import { getDeviceKey, setDeviceKey, clearDeviceKey, setRefreshToken } from '../stores/auth'
 import { useAuth } from '../contexts/AuthContext'
 
 export default function Login() {
@@ -24,7 +24,8 @@ export default function Login() {
     setError('')
     setLoading(true)
     try {
-      const { token } = await login(keyToUse, password, totpCode)
+      const { token, refresh_token } = await login(keyToUse, password, totpCode)
+      setRefreshToken(refresh_token)
       setToken(token)
       setDeviceKey(keyToUse)
       navigate('/chat')
@@ -40,6 +41,21 @@ export default function Login() {
     }
   }
 
+  async function handlePasskeyLogin() {
+    setError('')
+    setLoading(true)
+    try {
+      const { token, refresh_token } = await loginWithPasskey(password)
+      setRefreshToken(refresh_token)
+      setToken(token)
+      navigate('/chat')
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Passkey login failed')
+    } finally {
+      setLoading(false)
+    }
+  }
+
   return (
     <div className="mobile-frame flex min-h-screen flex-col gap-2 py-3">
       <h1 className="title-main">Dev PM Agent</h1>
@@ -97,8 +113,7 @@ export default function Login() {
             type="text"
             value={totpCode}
             onChange={(e) => setTotpCode(e.target.value)}
-            placeholder="6 digits"
-            maxLength={6}
+            placeholder="6 digits, or a recovery code"
             className="input-control"
             required
           />
@@ -111,6 +126,14 @@ export default function Login() {
         >
           {loading ? 'Logging in…' : 'Login'}
         </button>
+        <button
+          type="button"
+          onClick={handlePasskeyLogin}
+          disabled={loading || !password}
+          className="btn btn-ghost w-full"
+        >
+          Login with passkey + password
+        </button>
         <p className="text-center text-sm text-muted">
           First time? <Link to="/setup">Setup</Link>
         </p>
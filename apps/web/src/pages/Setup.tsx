@@ -9,6 +9,7 @@ export default function Setup() {
   const [username, setUsername] = useState('')
   const [password, setPassword] = useState('')
   const [totpSecret, setTotpSecret] = useState('')
+  const [recoveryCodes, setRecoveryCodes] = useState<string[]>([])
   const [error, setError] = useState('')
   const [loading, setLoading] = useState(false)
   const navigate = useNavigate()
@@ -33,8 +34,9 @@ export default function Setup() {
     setError('')
     setLoading(true)
     try {
-      const { totp_secret } = await setup(deviceApiKey.trim(), username, password)
+      const { totp_secret, recovery_codes } = await setup(deviceApiKey.trim(), username, password)
       setTotpSecret(totp_secret)
+      setRecoveryCodes(recovery_codes)
       setStep('totp')
     } catch (err) {
       setError(err instanceof Error ? err.message : 'Setup failed')
@@ -148,6 +150,21 @@ export default function Setup() {
             Copy
           </button>
         </div>
+        <div>
+          <label className="field-label">
+            Recovery codes — each logs in once if you lose the authenticator
+          </label>
+          <code className="code-block mt-1 block whitespace-pre warn-text">
+            {recoveryCodes.join('\n')}
+          </code>
+          <button
+            type="button"
+            onClick={() => navigator.clipboard?.writeText(recoveryCodes.join('\n'))}
+            className="btn btn-ghost mt-1"
+          >
+            Copy
+          </button>
+        </div>
         <p className="text-sm text-muted">
           For login you need: device key (from step 1), password, and TOTP code.
         </p>
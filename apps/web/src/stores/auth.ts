@@ -1,4 +1,5 @@
 const TOKEN_KEY = 'jwt';
+const REFRESH_KEY = 'refresh_token';
 const DEVICE_KEY = 'device_api_key';
 
 export function getToken(): string | null {
@@ -11,10 +12,20 @@ export function setToken(token: string) {
 
 export function clearToken() {
   localStorage.removeItem(TOKEN_KEY);
+  localStorage.removeItem(REFRESH_KEY);
+}
+
+export function getRefreshToken(): string | null {
+  return localStorage.getItem(REFRESH_KEY);
+}
+
+export function setRefreshToken(token: string) {
+  localStorage.setItem(REFRESH_KEY, token);
 }
 
 export function clearAuth() {
   localStorage.removeItem(TOKEN_KEY);
+  localStorage.removeItem(REFRESH_KEY);
   localStorage.removeItem(DEVICE_KEY);
 }
 
@@ -3,18 +3,28 @@
 use anyhow::{Context, Result};
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::AsyncBufReadExt;
-use tokio::process::Command;
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+
+mod tool_calls;
+
+use shared::{phase_outcomes, phases, tool_kinds, CommandPhase, ToolCallRecord};
+use tool_calls::ToolCallTracker;
 
 const REPOS_PREFIX: &str = "repos";
 
+/// How long a cancelled agent gets to exit after SIGTERM before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
 /// Extract JSON object from text that may be wrapped in markdown or extra prose.
 fn extract_json(s: &str) -> Option<&str> {
     let s = s.trim();
     if let Some(start) = s.find('{') {
         if let Some(end) = s.rfind('}') {
             if end >= start {
-                return Some(s.get(start..=end)?);
+                return s.get(start..=end);
             }
         }
     }
@@ -33,13 +43,231 @@ pub fn validate_repo_path(path: &str) -> Result<()> {
 /// Callback for streaming output. Receives current accumulated output.
 pub type OnOutput = Arc<dyn Fn(&str) + Send + Sync>;
 
+/// Callback for tool calls. Receives each call when it starts and again when it completes.
+pub type OnToolCall = Arc<dyn Fn(ToolCallRecord) + Send + Sync>;
+
+/// Why a run was stopped through its `CancelToken`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Cancelled by the user.
+    Cancelled,
+    /// Ran longer than its `max_runtime`.
+    Timeout,
+    /// Produced no output for the idle timeout.
+    NoOutput,
+}
+
+/// Cancellation signal for a running command. Clones share state; `cancel()` or `stop()`
+/// wakes every phase (translate, create-chat, run, summarize) waiting on it.
+#[derive(Clone)]
+pub struct CancelToken {
+    tx: Arc<watch::Sender<Option<StopReason>>>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        let (tx, _) = watch::channel(None);
+        Self { tx: Arc::new(tx) }
+    }
+
+    pub fn cancel(&self) {
+        self.stop(StopReason::Cancelled);
+    }
+
+    /// Stop the run for `reason`. The first reason wins.
+    pub fn stop(&self, reason: StopReason) {
+        self.tx.send_if_modified(|r| {
+            if r.is_some() {
+                return false;
+            }
+            *r = Some(reason);
+            true
+        });
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.tx.borrow().is_some()
+    }
+
+    pub fn reason(&self) -> Option<StopReason> {
+        *self.tx.borrow()
+    }
+
+    /// Resolves once the token has been stopped.
+    pub async fn cancelled(&self) {
+        let mut rx = self.tx.subscribe();
+        let _ = rx.wait_for(|r| r.is_some()).await;
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Time limits for one run. A zero `idle_timeout` disables the no-output check.
+#[derive(Debug, Clone, Copy)]
+pub struct RunLimits {
+    pub max_runtime: Duration,
+    pub idle_timeout: Duration,
+}
+
+/// When a run last produced output, for the no-output watchdog. Phases that don't stream
+/// (translation, chat creation, summarization) pause it, so only `max_runtime` bounds them.
+#[derive(Debug, Clone)]
+pub struct Activity(Arc<std::sync::Mutex<Option<tokio::time::Instant>>>);
+
+impl Activity {
+    pub fn new() -> Self {
+        Self(Arc::new(std::sync::Mutex::new(Some(
+            tokio::time::Instant::now(),
+        ))))
+    }
+
+    /// Record output now.
+    pub fn touch(&self) {
+        *self.0.lock().unwrap() = Some(tokio::time::Instant::now());
+    }
+
+    /// Pause the no-output check until the returned guard is dropped, which counts as output.
+    pub fn pause(&self) -> ActivityPause<'_> {
+        *self.0.lock().unwrap() = None;
+        ActivityPause(self)
+    }
+
+    /// Last output, or now while paused.
+    fn last(&self) -> tokio::time::Instant {
+        self.0
+            .lock()
+            .unwrap()
+            .unwrap_or_else(tokio::time::Instant::now)
+    }
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resumes an `Activity` when dropped; see `Activity::pause`.
+pub struct ActivityPause<'a>(&'a Activity);
+
+impl Drop for ActivityPause<'_> {
+    fn drop(&mut self) {
+        self.0.touch();
+    }
+}
+
+/// Stop `cancel` with `Timeout` once `limits.max_runtime` has passed, or with `NoOutput` once
+/// `activity` has been silent for `limits.idle_timeout`. Returns when the token is stopped for
+/// any reason; abort it when the run ends.
+pub async fn watchdog(cancel: CancelToken, limits: RunLimits, activity: Activity) {
+    let deadline = tokio::time::Instant::now() + limits.max_runtime;
+    loop {
+        let idle_deadline =
+            (!limits.idle_timeout.is_zero()).then(|| activity.last() + limits.idle_timeout);
+        let wake = idle_deadline.map_or(deadline, |d| d.min(deadline));
+        tokio::select! {
+            _ = tokio::time::sleep_until(wake) => {}
+            _ = cancel.cancelled() => return,
+        }
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            tracing::warn!(max_runtime = ?limits.max_runtime, "run exceeded max runtime");
+            cancel.stop(StopReason::Timeout);
+            return;
+        }
+        if !limits.idle_timeout.is_zero() && now >= activity.last() + limits.idle_timeout {
+            tracing::warn!(idle_timeout = ?limits.idle_timeout, "agent produced no output");
+            cancel.stop(StopReason::NoOutput);
+            return;
+        }
+    }
+}
+
+/// Returned (via anyhow) when a run is stopped by its `CancelToken`; see
+/// `CancelToken::reason` for why.
+#[derive(Debug, thiserror::Error)]
+#[error("command cancelled")]
+pub struct Cancelled;
+
+/// Base `agent` command. Runs in its own process group so cancellation can signal every
+/// process the agent spawned (shells, test runners), and is killed if its handle is dropped.
+fn agent_command() -> Command {
+    let mut cmd = Command::new("agent");
+    cmd.kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+    cmd
+}
+
+/// Send `signal` to the process group led by `pid` (see `agent_command`).
+#[cfg(unix)]
+fn signal_process_group(pid: Option<u32>, signal: i32) {
+    if let Some(pid) = pid {
+        // SAFETY: kill(2) with a negative pid only signals that process group.
+        unsafe {
+            libc::kill(-(pid as i32), signal);
+        }
+    }
+}
+
+/// Stop an agent and its process tree: SIGTERM the group, give it `KILL_GRACE` to exit,
+/// then SIGKILL whatever is left.
+async fn terminate(child: &mut Child) {
+    let pid = child.id();
+    #[cfg(unix)]
+    signal_process_group(pid, libc::SIGTERM);
+    if tokio::time::timeout(KILL_GRACE, child.wait())
+        .await
+        .is_err()
+    {
+        let _ = child.kill().await;
+    }
+    #[cfg(unix)]
+    signal_process_group(pid, libc::SIGKILL);
+    tracing::info!(pid = ?pid, "agent process tree terminated");
+}
+
+/// Read a child pipe to EOF (empty if the pipe was not captured).
+async fn read_pipe(pipe: Option<impl tokio::io::AsyncRead + Unpin>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if let Some(mut p) = pipe {
+        let _ = tokio::io::AsyncReadExt::read_to_end(&mut p, &mut buf).await;
+    }
+    buf
+}
+
+/// Run `cmd` to completion, or kill its process tree and fail with `Cancelled`.
+async fn output_or_cancel(mut cmd: Command, cancel: &CancelToken) -> Result<std::process::Output> {
+    let mut child = cmd.spawn().context("spawn agent")?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let waited = tokio::select! {
+        res = async {
+            let (out, err, status) = tokio::join!(read_pipe(stdout), read_pipe(stderr), child.wait());
+            status.map(|status| std::process::Output { status, stdout: out, stderr: err })
+        } => Some(res),
+        _ = cancel.cancelled() => None,
+    };
+    match waited {
+        Some(res) => Ok(res?),
+        None => {
+            terminate(&mut child).await;
+            Err(Cancelled.into())
+        }
+    }
+}
+
 /// Create a new Cursor CLI chat and return its ID for use with --resume.
-async fn create_cursor_chat() -> Result<String> {
-    let output = Command::new("agent")
-        .args(["create-chat"])
+async fn create_cursor_chat(cancel: &CancelToken) -> Result<String> {
+    let mut cmd = agent_command();
+    cmd.args(["create-chat"])
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
+        .stderr(Stdio::piped());
+    let output = output_or_cancel(cmd, cancel)
         .await
         .context("run agent create-chat")?;
     let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -49,62 +277,46 @@ async fn create_cursor_chat() -> Result<String> {
     Ok(id)
 }
 
-const FOLDER_PLACEMENT: &str = r#"
-Template output folder placement: Put documents in plans/, security_reviews/, or sprints/ as appropriate. Use repo root (e.g. sprints/SPRINT_001.md) for repo-wide scope; if the work only touches one package in a monorepo, use that package's subdirectory (e.g. packages/foo/sprints/SPRINT_001.md).
-"#;
-
-/// Build template-specific context instructions for the translation prompt.
-/// When context_mode is set, these guide the model on the expected workflow.
-fn context_mode_instructions(context_mode: Option<&str>, repo_path: &str) -> String {
-    let escaped_repo = repo_path.replace('"', "\\\"");
-    match context_mode {
-        Some("sprint") => format!(
-            r#"Context: User selected SPRINT template. You are in workspace: "{}".
-{}
-Sprint workflow: Create a sprint document in sprints/ (or packages/{{name}}/sprints/ if only touching one package). Use SPRINT_001.md or next available number. Base it on the user's request and any prior work (security audit, gap analysis, etc.).
-Write the sprint doc to the repo. Output its full contents for user review.
-IMPORTANT: Do NOT implement the changes yet. The user will review the sprint doc, then send a follow-up message (e.g. "implement it" or "approved") to execute the sprint.
-Exception: If the user explicitly asks to "implement" or "execute" an existing sprint, do that instead of creating a new doc.
-"#,
-            escaped_repo, FOLDER_PLACEMENT
-        ),
-        Some("security_review") => format!(
-            r#"Context: User selected SECURITY REVIEW template. Workspace: "{}".
-{}
-Produce a security review document. Place it in security_reviews/ at root, or packages/{{name}}/security_reviews/ if only touching one package. Include findings summary, severity levels, and recommended actions.
-"#,
-            escaped_repo, FOLDER_PLACEMENT
-        ),
-        Some("monorepo_init") => format!(
-            r#"Context: User selected MONOREPO INIT template. Workspace: "{}".
-{}
-Create plans/ folder and PLAN_INITIAL.md (at root or packages/{{name}}/plans/ if package-scoped). Output for interactive review.
-"#,
-            escaped_repo, FOLDER_PLACEMENT
-        ),
-        Some("gap_analysis") => format!(
-            r#"Context: User selected GAP ANALYSIS template. Workspace: "{}".
-{}
-Write PLAN_GAP_{{x}}.md in plans/ (or packages/{{name}}/plans/ if package-scoped). Output for interactive review.
-"#,
-            escaped_repo, FOLDER_PLACEMENT
-        ),
-        Some("feature_plan") => format!(
-            r#"Context: User selected FEATURE PLAN template. Workspace: "{}".
-{}
-Write PLAN_FEAT_{{x}}.md in plans/ (or packages/{{name}}/plans/ if package-scoped). Output for interactive review.
-"#,
-            escaped_repo, FOLDER_PLACEMENT
-        ),
-        Some("commit") => format!(
-            r#"Context: User selected COMMIT template. Workspace: "{}".
-Commit the changes made in this conversation (stage and commit with an appropriate message). Pre-commit hooks (lint, test, format) often run and can take a long time. In your output, clearly describe what happened: whether pre-commit passed or failed, what ran, and any errors if it failed. The user needs to know the outcome either way. NEVER use --no-verify.
-Output format for pre-commit report: Keep it compact. Put section numbers and headers on the same line (e.g. "1. Format checks" not "1.\nFormat checks"). Use a proper Markdown unordered list (-) for each check item so they render as separate list items (e.g. "- operator (Rust fmt) – ✓" on its own line), not as continuation of the section header.
-"#,
-            escaped_repo
-        ),
-        _ => String::new(),
+/// Next free sprint number in the repo, zero-padded (`SPRINT_007.md` exists -> "008"). Looks
+/// at `SPRINT_NNN*.md` files in any `sprints/` folder near the top of the repo.
+fn next_sprint_number(repo: &std::path::Path) -> String {
+    let last = walkdir::WalkDir::new(repo)
+        .max_depth(4)
+        .into_iter()
+        .filter_entry(|e| {
+            let name = e.file_name().to_string_lossy();
+            !(e.depth() > 0
+                && (name.starts_with('.') || name == "node_modules" || name == "target"))
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path()
+                .parent()
+                .and_then(|p| p.file_name())
+                .is_some_and(|n| n == "sprints")
+        })
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            let digits: String = name
+                .strip_prefix("SPRINT_")?
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            digits.parse::<u32>().ok()
+        })
+        .max()
+        .unwrap_or(0);
+    format!("{:03}", last + 1)
+}
+
+/// Fill in a template body for the translation prompt: `{repo}` is the workspace path and
+/// `{next_sprint_number}` the next free sprint number. Other braces are left as-is.
+fn render_template(body: &str, repo_path: &str, expanded_repo: &std::path::Path) -> String {
+    let mut out = body.replace("{repo}", &repo_path.replace('"', "\\\""));
+    if out.contains("{next_sprint_number}") {
+        out = out.replace("{next_sprint_number}", &next_sprint_number(expanded_repo));
     }
+    out
 }
 
 /// Format chat history for the translation prompt.
@@ -123,26 +335,72 @@ fn format_chat_history(history: &[(String, Option<String>)]) -> String {
     out
 }
 
+/// Inputs for one command run.
+pub struct RunParams<'a> {
+    pub input: &'a str,
+    pub repo_path: &'a str,
+    pub translator_model: &'a str,
+    pub workload_model: &'a str,
+    /// Resume this Cursor chat instead of creating a new one.
+    pub resume_chat_id: Option<&'a str>,
+    pub context_mode: Option<&'a str>,
+    /// Body of the `context_mode` template; see `render_template`.
+    pub template: Option<&'a str>,
+    /// Prior (input, output) turns in the chat, for translator context.
+    pub chat_history: Option<&'a [(String, Option<String>)]>,
+    /// Stop after translation and return the prompt for review.
+    pub review_prompt: bool,
+    /// Reviewed prompt to run; translation is skipped.
+    pub approved_prompt: Option<&'a str>,
+}
+
+/// How a `run_command` pipeline ended.
+#[derive(Debug)]
+pub enum RunOutcome {
+    Finished {
+        output: String,
+        summary: String,
+        cursor_chat_id: String,
+    },
+    /// Stopped after translation (`RunParams::review_prompt`) with the prompt to review.
+    AwaitingApproval { prompt: String },
+}
+
 /// Run the full command pipeline: translate -> execute -> summarize.
 /// Uses `agent create-chat` to get a session ID (or `resume_chat_id` when continuing),
 /// then runs workload with `--resume [chatId]`.
-/// Returns (output, summary, cursor_chat_id).
+/// Fails with `Cancelled` as soon as `cancel` fires, killing whichever agent is running.
+/// Each phase is appended to `phase_log` with its outcome as it ends, so the log is complete
+/// even when a phase fails or the run is stopped.
+/// `activity` is paused while phases that don't stream output run.
 pub async fn run_command(
-    input: &str,
-    repo_path: &str,
-    translator_model: &str,
-    workload_model: &str,
-    resume_chat_id: Option<&str>,
+    params: RunParams<'_>,
     on_output: Option<OnOutput>,
-    context_mode: Option<&str>,
-    chat_history: Option<&[(String, Option<String>)]>,
-) -> Result<(String, String, String)> {
+    on_tool_call: Option<OnToolCall>,
+    phase_log: &mut Vec<CommandPhase>,
+    cancel: &CancelToken,
+    activity: &Activity,
+) -> Result<RunOutcome> {
+    let RunParams {
+        input,
+        repo_path,
+        translator_model,
+        workload_model,
+        resume_chat_id,
+        context_mode,
+        template,
+        chat_history,
+        review_prompt,
+        approved_prompt,
+    } = params;
     validate_repo_path(repo_path)?;
 
     let expanded = shellexpand::tilde(repo_path).to_string();
 
-    // 1. Translation (skip for freeform — send user input directly)
-    let cursor_prompt = if context_mode.is_none() {
+    // 1. Translation (skip for freeform — send user input directly — and for reviewed prompts)
+    let cursor_prompt = if let Some(prompt) = approved_prompt {
+        prompt.to_string()
+    } else if context_mode.is_none() {
         if let Some(ref cb) = on_output {
             cb("[Creating chat session...]");
         }
@@ -151,7 +409,9 @@ pub async fn run_command(
         if let Some(ref cb) = on_output {
             cb("Translating task...");
         }
-        let context_prefix = context_mode_instructions(context_mode, repo_path);
+        let context_prefix = template
+            .map(|body| render_template(body, repo_path, std::path::Path::new(&expanded)))
+            .unwrap_or_default();
         let (history_block, input_label) = match chat_history.filter(|h| !h.is_empty()) {
             Some(h) => {
                 let formatted = format_chat_history(h);
@@ -179,14 +439,29 @@ Output format: {{"cursor_prompt": "refined or expanded task for the coding agent
             input.replace('"', "\\\"")
         );
 
-        let translation_out = run_agent(translator_model, None, &translation_prompt).await?;
+        let started = Instant::now();
+        let translated = {
+            let _paused = activity.pause();
+            run_agent(translator_model, None, &translation_prompt, cancel).await
+        };
+        phase_log.push(timed_phase(
+            phases::TRANSLATE,
+            Some(translator_model),
+            started,
+            translated.as_ref().err(),
+        ));
+        let translation_out = translated?;
         let json_str = extract_json(&translation_out).context("no JSON in translator output")?;
         let parsed: serde_json::Value =
             serde_json::from_str(json_str).context("parse translation JSON")?;
-        parsed["cursor_prompt"]
+        let prompt = parsed["cursor_prompt"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("cursor_prompt missing in translator output"))?
-            .to_string()
+            .to_string();
+        if review_prompt {
+            return Ok(RunOutcome::AwaitingApproval { prompt });
+        }
+        prompt
     };
 
     let chat_id = match resume_chat_id {
@@ -201,7 +476,18 @@ Output format: {{"cursor_prompt": "refined or expanded task for the coding agent
                     cursor_prompt
                 ));
             }
-            create_cursor_chat().await?
+            let started = Instant::now();
+            let created = {
+                let _paused = activity.pause();
+                create_cursor_chat(cancel).await
+            };
+            phase_log.push(timed_phase(
+                phases::CREATE_CHAT,
+                None,
+                started,
+                created.as_ref().err(),
+            ));
+            created?
         }
     };
 
@@ -210,7 +496,8 @@ Output format: {{"cursor_prompt": "refined or expanded task for the coding agent
     }
 
     // 2. Execution (streaming) with --resume for Cursor CLI chat session
-    let exec_out = run_agent_in_repo_streaming(
+    let started = Instant::now();
+    let ran = run_agent_in_repo_streaming(
         workload_model,
         &expanded,
         &cursor_prompt,
@@ -222,8 +509,30 @@ Output format: {{"cursor_prompt": "refined or expanded task for the coding agent
                 cb(&format!("{}\n\n{}", trans_display, out));
             }) as OnOutput
         }),
+        on_tool_call,
+        cancel,
     )
-    .await?;
+    .await;
+    let (exec_out, usage) = match ran {
+        Ok(ran) => ran,
+        Err(e) => {
+            phase_log.push(timed_phase(
+                phases::RUN,
+                Some(workload_model),
+                started,
+                Some(&e),
+            ));
+            return Err(e);
+        }
+    };
+    // Usage comes from the agent's final `result` event; timing and model are ours.
+    phase_log.push(CommandPhase {
+        phase: phases::RUN.to_string(),
+        model: Some(workload_model.to_string()),
+        duration_ms: started.elapsed().as_millis() as u64,
+        outcome: Some(phase_outcomes::OK.to_string()),
+        ..usage
+    });
 
     // 3. Summarization
     if let Some(ref cb) = on_output {
@@ -246,15 +555,92 @@ Output to summarize:\n{}",
             .take(2000)
             .collect::<String>()
     );
-    let summary = run_agent(workload_model, None, &summary_prompt)
-        .await
-        .unwrap_or_else(|_| "Summary unavailable".to_string());
+    let started = Instant::now();
+    let summarized = {
+        let _paused = activity.pause();
+        run_agent(workload_model, None, &summary_prompt, cancel).await
+    };
+    phase_log.push(timed_phase(
+        phases::SUMMARIZE,
+        Some(workload_model),
+        started,
+        summarized.as_ref().err(),
+    ));
+    let summary = match summarized {
+        Ok(s) => s,
+        Err(e) if e.is::<Cancelled>() => return Err(e),
+        Err(_) => "Summary unavailable".to_string(),
+    };
 
-    Ok((exec_out, summary, chat_id))
+    Ok(RunOutcome::Finished {
+        output: exec_out,
+        summary,
+        cursor_chat_id: chat_id,
+    })
 }
 
-async fn run_agent(model: &str, repo: Option<&str>, prompt: &str) -> Result<String> {
-    let mut cmd = Command::new("agent");
+/// Phase record with the time elapsed since `started`, no usage, and the outcome of a phase
+/// that ended with `error`, if any.
+fn timed_phase(
+    phase: &str,
+    model: Option<&str>,
+    started: Instant,
+    error: Option<&anyhow::Error>,
+) -> CommandPhase {
+    let outcome = match error {
+        None => phase_outcomes::OK,
+        Some(e) if e.is::<Cancelled>() => phase_outcomes::CANCELLED,
+        Some(_) => phase_outcomes::FAILED,
+    };
+    CommandPhase {
+        phase: phase.to_string(),
+        model: model.map(String::from),
+        duration_ms: started.elapsed().as_millis() as u64,
+        outcome: Some(outcome.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Token usage and cost from the agent's final `result` stream event. Field names vary
+/// between agent versions, so both camelCase and snake_case spellings are accepted.
+fn usage_from_result(event: &serde_json::Value) -> CommandPhase {
+    let usage = event.get("usage").unwrap_or(event);
+    let count = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| usage.get(k).and_then(|v| v.as_u64()))
+    };
+    CommandPhase {
+        input_tokens: count(&["inputTokens", "input_tokens"]),
+        output_tokens: count(&["outputTokens", "output_tokens"]),
+        cache_read_tokens: count(&[
+            "cacheReadTokens",
+            "cache_read_tokens",
+            "cache_read_input_tokens",
+        ]),
+        cache_write_tokens: count(&[
+            "cacheWriteTokens",
+            "cache_write_tokens",
+            "cache_creation_input_tokens",
+        ]),
+        cost_usd: ["total_cost_usd", "cost_usd", "costUsd"]
+            .iter()
+            .find_map(|k| {
+                event
+                    .get(k)
+                    .or_else(|| usage.get(k))
+                    .and_then(|v| v.as_f64())
+            }),
+        ..Default::default()
+    }
+}
+
+async fn run_agent(
+    model: &str,
+    repo: Option<&str>,
+    prompt: &str,
+    cancel: &CancelToken,
+) -> Result<String> {
+    let mut cmd = agent_command();
     cmd.args(["-p", "--model", model, "--output-format", "text", "--force"])
         .arg(prompt)
         .stdout(Stdio::piped())
@@ -264,7 +650,7 @@ async fn run_agent(model: &str, repo: Option<&str>, prompt: &str) -> Result<Stri
         cmd.args(["--workspace", r]);
     }
 
-    let output = cmd.output().await.context("run agent")?;
+    let output = output_or_cancel(cmd, cancel).await.context("run agent")?;
     let stdout = String::from_utf8_lossy(&output.stdout);
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -285,35 +671,22 @@ fn format_tool_call(
     let Some((tool_key, inner)) = tc.iter().next() else {
         return;
     };
-    let tool_name = tool_key
-        .trim_end_matches("ToolCall")
-        .trim_end_matches("Tool");
+    let tool_name = tool_calls::tool_name(tool_key);
 
     if subtype == "started" {
         // Extract a human-readable description of what the tool is doing
-        let args = inner.get("args");
-        let desc = match tool_name {
-            "bash" | "runCommand" | "terminal" => args
-                .and_then(|a| a.get("command").or_else(|| a.get("cmd")))
-                .and_then(|v| v.as_str())
-                .map(|s| format!("$ {}", s)),
-            "ls" | "listDir" => args
-                .and_then(|a| a.get("path"))
-                .and_then(|v| v.as_str())
-                .map(|s| format!("ls {}", s)),
-            "read" | "readFile" => args
-                .and_then(|a| a.get("path").or_else(|| a.get("filePath")))
-                .and_then(|v| v.as_str())
-                .map(|s| format!("cat {}", s)),
-            "write" | "writeFile" | "editFile" | "edit" => args
-                .and_then(|a| a.get("path").or_else(|| a.get("filePath")))
-                .and_then(|v| v.as_str())
-                .map(|s| format!("write {}", s)),
-            "grep" | "search" => args
-                .and_then(|a| a.get("pattern").or_else(|| a.get("query")))
-                .and_then(|v| v.as_str())
-                .map(|s| format!("grep {}", s)),
-            _ => Some(format!("[{}]", tool_name)),
+        let desc = match tool_calls::classify(tool_name, inner.get("args")) {
+            (tool_kinds::OTHER, _) => Some(format!("[{}]", tool_name)),
+            (kind, target) => target.map(|t| {
+                let verb = match kind {
+                    tool_kinds::SHELL => "$",
+                    tool_kinds::LIST => "ls",
+                    tool_kinds::READ => "cat",
+                    tool_kinds::WRITE => "write",
+                    _ => "grep",
+                };
+                format!("{} {}", verb, t)
+            }),
         };
         if let Some(d) = desc {
             if !console.is_empty() {
@@ -362,7 +735,9 @@ async fn run_agent_in_repo_streaming(
     prompt: &str,
     chat_id: Option<&str>,
     on_output: Option<OnOutput>,
-) -> Result<String> {
+    on_tool_call: Option<OnToolCall>,
+    cancel: &CancelToken,
+) -> Result<(String, CommandPhase)> {
     tracing::info!(
         model = model,
         repo = repo,
@@ -371,7 +746,7 @@ async fn run_agent_in_repo_streaming(
         "spawning agent"
     );
 
-    let mut cmd = Command::new("agent");
+    let mut cmd = agent_command();
     cmd.args([
         "-p",
         "--model",
@@ -396,7 +771,7 @@ async fn run_agent_in_repo_streaming(
         .ok_or_else(|| anyhow::anyhow!("no stdout"))?;
     let stderr = child.stderr.take();
 
-    let (tx, rx) = tokio::sync::oneshot::channel::<Result<String>>();
+    let (tx, rx) = tokio::sync::oneshot::channel::<Result<(String, CommandPhase)>>();
     let mut reader = tokio::io::BufReader::new(stdout);
     let mut buf = String::new();
     let stream_cb = on_output.clone();
@@ -420,6 +795,8 @@ async fn run_agent_in_repo_streaming(
         let mut response = String::new();
         let mut full_result = String::new();
         let mut console = String::new();
+        let mut tool_calls = ToolCallTracker::default();
+        let mut usage = CommandPhase::default();
         let mut event_count = 0u32;
         loop {
             buf.clear();
@@ -469,8 +846,14 @@ async fn run_agent_in_repo_streaming(
                                 if let Some(tc) = val.get("tool_call").and_then(|v| v.as_object()) {
                                     format_tool_call(tc, sub, &mut console);
                                 }
+                                if let Some(record) = tool_calls.on_event(&val, sub) {
+                                    if let Some(ref cb) = on_tool_call {
+                                        cb(record);
+                                    }
+                                }
                             }
                             ("result", _) => {
+                                usage = usage_from_result(&val);
                                 if let Some(r) = val.get("result").and_then(|v| v.as_str()) {
                                     full_result = r.to_string();
                                     tracing::info!(
@@ -508,8 +891,8 @@ async fn run_agent_in_repo_streaming(
                             display.push_str("[Response]\n");
                             display.push_str(response_content);
                         }
-                        if !display.is_empty() && stream_cb.is_some() {
-                            stream_cb.as_ref().unwrap()(&display);
+                        if let Some(cb) = stream_cb.as_ref().filter(|_| !display.is_empty()) {
+                            cb(&display);
                         }
                     }
                 }
@@ -529,11 +912,20 @@ async fn run_agent_in_repo_streaming(
             full_result
         };
         tracing::info!(events = event_count, "agent stream complete");
-        let _ = tx.send(Ok(result));
+        let _ = tx.send(Ok((result, usage)));
     });
 
     tracing::info!("waiting for agent process to exit");
-    let status = child.wait().await.context("wait agent")?;
+    let waited = tokio::select! {
+        status = child.wait() => Some(status),
+        _ = cancel.cancelled() => None,
+    };
+    let Some(status) = waited else {
+        tracing::info!("cancel requested, killing agent");
+        terminate(&mut child).await;
+        return Err(Cancelled.into());
+    };
+    let status = status.context("wait agent")?;
     if !status.success() {
         let mut stderr = String::new();
         if let Some(mut s) = child.stderr {
@@ -567,4 +959,169 @@ mod tests {
         assert!(validate_repo_path("/tmp/foo").is_err());
         assert!(validate_repo_path("~/documents").is_err());
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn output_or_cancel_kills_child_on_cancel() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 30"])
+            .kill_on_drop(true)
+            .process_group(0)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let cancel = CancelToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+        let started = std::time::Instant::now();
+        let err = output_or_cancel(cmd, &cancel).await.unwrap_err();
+        assert!(err.is::<Cancelled>());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn watchdog_stops_silent_run_with_no_output() {
+        let cancel = CancelToken::new();
+        let limits = RunLimits {
+            max_runtime: Duration::from_secs(30),
+            idle_timeout: Duration::from_millis(100),
+        };
+        watchdog(cancel.clone(), limits, Activity::new()).await;
+        assert_eq!(cancel.reason(), Some(StopReason::NoOutput));
+    }
+
+    #[tokio::test]
+    async fn watchdog_waits_out_paused_phases_until_max_runtime() {
+        let cancel = CancelToken::new();
+        let activity = Activity::new();
+        let limits = RunLimits {
+            max_runtime: Duration::from_millis(400),
+            idle_timeout: Duration::from_millis(100),
+        };
+        let _paused = activity.pause();
+        watchdog(cancel.clone(), limits, activity.clone()).await;
+        assert_eq!(cancel.reason(), Some(StopReason::Timeout));
+    }
+
+    #[tokio::test]
+    async fn watchdog_times_out_run_that_keeps_producing_output() {
+        let cancel = CancelToken::new();
+        let activity = Activity::new();
+        let limits = RunLimits {
+            max_runtime: Duration::from_millis(400),
+            idle_timeout: Duration::from_millis(200),
+        };
+        let touch = activity.clone();
+        let ticker = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                touch.touch();
+            }
+        });
+        watchdog(cancel.clone(), limits, activity).await;
+        ticker.abort();
+        assert_eq!(cancel.reason(), Some(StopReason::Timeout));
+    }
+
+    #[tokio::test]
+    async fn failed_phase_is_logged_with_its_outcome() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let mut phase_log = Vec::new();
+        let result = run_command(
+            RunParams {
+                input: "x",
+                repo_path: "~/repos/x",
+                translator_model: "fast",
+                workload_model: "big",
+                resume_chat_id: None,
+                context_mode: Some("plan"),
+                template: None,
+                chat_history: None,
+                review_prompt: false,
+                approved_prompt: None,
+            },
+            None,
+            None,
+            &mut phase_log,
+            &cancel,
+            &Activity::new(),
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(phase_log.len(), 1);
+        assert_eq!(phase_log[0].phase, phases::TRANSLATE);
+        // Cancelled, or failed to spawn where no agent is installed.
+        assert_ne!(phase_log[0].outcome.as_deref(), Some(phase_outcomes::OK));
+
+        let started = Instant::now();
+        let outcome = |error: Option<anyhow::Error>| {
+            timed_phase(phases::RUN, None, started, error.as_ref()).outcome
+        };
+        assert_eq!(outcome(None).as_deref(), Some(phase_outcomes::OK));
+        assert_eq!(
+            outcome(Some(Cancelled.into())).as_deref(),
+            Some(phase_outcomes::CANCELLED)
+        );
+        assert_eq!(
+            outcome(Some(anyhow::anyhow!("agent failed"))).as_deref(),
+            Some(phase_outcomes::FAILED)
+        );
+    }
+
+    #[test]
+    fn first_stop_reason_wins() {
+        let cancel = CancelToken::new();
+        cancel.stop(StopReason::Timeout);
+        cancel.cancel();
+        assert_eq!(cancel.reason(), Some(StopReason::Timeout));
+    }
+
+    #[test]
+    fn render_template_fills_repo_and_next_sprint_number() {
+        let repo = std::env::temp_dir().join(format!("render-template-{}", std::process::id()));
+        std::fs::create_dir_all(repo.join("sprints")).unwrap();
+        std::fs::create_dir_all(repo.join("packages/foo/sprints")).unwrap();
+        std::fs::write(repo.join("sprints/SPRINT_002.md"), "").unwrap();
+        std::fs::write(repo.join("packages/foo/sprints/SPRINT_007_auth.md"), "").unwrap();
+        std::fs::write(repo.join("SPRINT_099.md"), "").unwrap();
+
+        let out = render_template(
+            "Workspace: \"{repo}\". Write SPRINT_{next_sprint_number}.md in packages/{name}/",
+            "~/repos/my\"app",
+            &repo,
+        );
+        assert_eq!(
+            out,
+            "Workspace: \"~/repos/my\\\"app\". Write SPRINT_008.md in packages/{name}/"
+        );
+        std::fs::remove_dir_all(&repo).unwrap();
+        assert_eq!(next_sprint_number(&repo), "001");
+    }
+
+    #[test]
+    fn usage_from_result_reads_both_spellings() {
+        let camel = serde_json::json!({
+            "type": "result",
+            "result": "done",
+            "usage": { "inputTokens": 1200, "outputTokens": 300, "cacheReadTokens": 50 }
+        });
+        let usage = usage_from_result(&camel);
+        assert_eq!(usage.input_tokens, Some(1200));
+        assert_eq!(usage.output_tokens, Some(300));
+        assert_eq!(usage.cache_read_tokens, Some(50));
+        assert_eq!(usage.cost_usd, None);
+
+        let snake = serde_json::json!({
+            "type": "result",
+            "total_cost_usd": 0.25,
+            "usage": { "input_tokens": 10, "cache_creation_input_tokens": 7 }
+        });
+        let usage = usage_from_result(&snake);
+        assert_eq!(usage.input_tokens, Some(10));
+        assert_eq!(usage.cache_write_tokens, Some(7));
+        assert_eq!(usage.cost_usd, Some(0.25));
+    }
 }
@@ -0,0 +1,212 @@
+//! Structured records of the agent's `tool_call` stream events.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde_json::Value;
+use shared::{tool_kinds, ToolCallRecord};
+
+/// Longest tool result kept on a record, in chars.
+const RESULT_MAX_CHARS: usize = 4000;
+
+/// Tool name from its `tool_call` key, e.g. `bashToolCall` -> `bash`.
+pub(super) fn tool_name(tool_key: &str) -> &str {
+    tool_key
+        .trim_end_matches("ToolCall")
+        .trim_end_matches("Tool")
+}
+
+/// Kind (see `tool_kinds`) and target of a tool call. Known tools without the expected
+/// argument have no target.
+pub(super) fn classify(tool: &str, args: Option<&Value>) -> (&'static str, Option<String>) {
+    let arg = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| args.and_then(|a| a.get(k)).and_then(|v| v.as_str()))
+            .map(String::from)
+    };
+    match tool {
+        "bash" | "runCommand" | "terminal" | "shell" => {
+            (tool_kinds::SHELL, arg(&["command", "cmd"]))
+        }
+        "ls" | "listDir" => (tool_kinds::LIST, arg(&["path"])),
+        "read" | "readFile" => (tool_kinds::READ, arg(&["path", "filePath"])),
+        "write" | "writeFile" | "editFile" | "edit" => {
+            (tool_kinds::WRITE, arg(&["path", "filePath"]))
+        }
+        "grep" | "search" => (tool_kinds::SEARCH, arg(&["pattern", "query"])),
+        _ => (tool_kinds::OTHER, None),
+    }
+}
+
+/// Exit code, error and (truncated) result text from a tool call's `result`. Results are
+/// either the payload itself or wrapped in `success` / `failure`.
+fn parse_result(result: &Value) -> (Option<i64>, Option<String>, Option<String>) {
+    let body = result
+        .get("success")
+        .or_else(|| result.get("failure"))
+        .unwrap_or(result);
+    let exit_code = body
+        .get("exitCode")
+        .or_else(|| body.get("exit_code"))
+        .and_then(|v| v.as_i64());
+    let error = result
+        .get("error")
+        .or_else(|| result.get("failure"))
+        .and_then(|e| {
+            e.as_str()
+                .or_else(|| e.get("message").and_then(|v| v.as_str()))
+                .or_else(|| e.get("stderr").and_then(|v| v.as_str()))
+        })
+        .map(String::from);
+    let text = ["stdout", "output", "content"]
+        .iter()
+        .find_map(|k| body.get(k).and_then(|v| v.as_str()))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(truncate);
+    (exit_code, error, text)
+}
+
+fn truncate(s: &str) -> String {
+    if s.chars().count() <= RESULT_MAX_CHARS {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(RESULT_MAX_CHARS).collect();
+    out.push_str("\n... (truncated)");
+    out
+}
+
+fn now_iso8601() -> String {
+    chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Pairs `started` and `completed` events into [`ToolCallRecord`]s. Events are matched by
+/// `call_id`; without one, a completion closes the oldest open call of the same tool.
+#[derive(Default)]
+pub struct ToolCallTracker {
+    open: HashMap<String, (ToolCallRecord, Instant)>,
+    started: u32,
+}
+
+impl ToolCallTracker {
+    /// Apply a `tool_call` stream event. Returns the record to report, if the event was
+    /// understood.
+    pub fn on_event(&mut self, event: &Value, subtype: &str) -> Option<ToolCallRecord> {
+        let (tool_key, inner) = event.get("tool_call")?.as_object()?.iter().next()?;
+        let tool = tool_name(tool_key);
+        let call_id = event
+            .get("call_id")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+        match subtype {
+            "started" => {
+                let record = self.start(call_id, tool, inner);
+                self.open
+                    .insert(record.call_id.clone(), (record.clone(), Instant::now()));
+                Some(record)
+            }
+            "completed" => {
+                let open_id = call_id
+                    .clone()
+                    .filter(|id| self.open.contains_key(id))
+                    .or_else(|| {
+                        self.open
+                            .values()
+                            .filter(|(r, _)| r.tool == tool)
+                            .min_by_key(|(r, _)| r.seq)
+                            .map(|(r, _)| r.call_id.clone())
+                    });
+                let (mut record, started) = match open_id.and_then(|id| self.open.remove(&id)) {
+                    Some(open) => open,
+                    None => (self.start(call_id, tool, inner), Instant::now()),
+                };
+                record.completed_at = Some(now_iso8601());
+                record.duration_ms = Some(started.elapsed().as_millis() as u64);
+                if let Some(result) = inner.get("result") {
+                    let (exit_code, error, text) = parse_result(result);
+                    record.exit_code = exit_code;
+                    record.error = error;
+                    record.result = text;
+                }
+                Some(record)
+            }
+            _ => None,
+        }
+    }
+
+    fn start(&mut self, call_id: Option<String>, tool: &str, inner: &Value) -> ToolCallRecord {
+        self.started += 1;
+        let args = inner.get("args").cloned();
+        let (kind, target) = classify(tool, args.as_ref());
+        ToolCallRecord {
+            call_id: call_id.unwrap_or_else(|| format!("{}-{}", tool, self.started)),
+            seq: self.started,
+            tool: tool.to_string(),
+            kind: kind.to_string(),
+            target,
+            args,
+            started_at: now_iso8601(),
+            completed_at: None,
+            duration_ms: None,
+            exit_code: None,
+            error: None,
+            result: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracker_pairs_started_and_completed() {
+        let mut tracker = ToolCallTracker::default();
+        let started = serde_json::json!({
+            "type": "tool_call",
+            "subtype": "started",
+            "call_id": "c1",
+            "tool_call": { "bashToolCall": { "args": { "command": "cargo test" } } }
+        });
+        let record = tracker.on_event(&started, "started").unwrap();
+        assert_eq!(record.seq, 1);
+        assert_eq!(record.kind, tool_kinds::SHELL);
+        assert_eq!(record.target.as_deref(), Some("cargo test"));
+        assert!(record.completed_at.is_none());
+
+        let completed = serde_json::json!({
+            "type": "tool_call",
+            "subtype": "completed",
+            "call_id": "c1",
+            "tool_call": { "bashToolCall": {
+                "args": { "command": "cargo test" },
+                "result": { "success": { "exitCode": 101, "stdout": "  1 failed  " } }
+            } }
+        });
+        let record = tracker.on_event(&completed, "completed").unwrap();
+        assert_eq!(record.call_id, "c1");
+        assert_eq!(record.seq, 1);
+        assert_eq!(record.exit_code, Some(101));
+        assert_eq!(record.result.as_deref(), Some("1 failed"));
+        assert!(record.completed_at.is_some());
+        assert!(record.duration_ms.is_some());
+
+        // Without call ids, a completion closes the open call of the same tool.
+        let write = |subtype: &str| {
+            serde_json::json!({
+                "type": "tool_call",
+                "subtype": subtype,
+                "tool_call": { "editFileToolCall": {
+                    "args": { "path": "src/lib.rs" },
+                    "result": { "error": "permission denied" }
+                } }
+            })
+        };
+        let started = tracker.on_event(&write("started"), "started").unwrap();
+        let completed = tracker.on_event(&write("completed"), "completed").unwrap();
+        assert_eq!(started.call_id, completed.call_id);
+        assert_eq!(completed.seq, 2);
+        assert_eq!(completed.kind, tool_kinds::WRITE);
+        assert_eq!(completed.error.as_deref(), Some("permission denied"));
+    }
+}
@@ -3,3 +3,4 @@
 pub mod cli;
 pub mod cursor;
 pub mod relay_client;
+pub mod scheduler;
@@ -1,6 +1,7 @@
 //! Dev PM Agent Executor — desktop daemon.
 
 use std::env;
+use std::time::Duration;
 
 use clap::Parser;
 use executor::{cli, relay_client};
@@ -18,6 +19,14 @@ fn hash_password(password: &str, client_salt: &str) -> String {
         .collect()
 }
 
+/// Read a number of seconds from env, falling back to `default` when unset or invalid.
+fn env_secs(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
@@ -57,6 +66,14 @@ async fn main() -> anyhow::Result<()> {
                 env::var("TRANSLATOR_MODEL").unwrap_or_else(|_| "composer-1.5".to_string());
             let workload_model =
                 env::var("WORKLOAD_MODEL").unwrap_or_else(|_| "composer-1.5".to_string());
+            let max_concurrent = env::var("MAX_CONCURRENT_COMMANDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2);
+            let limits = executor::cursor::RunLimits {
+                max_runtime: Duration::from_secs(env_secs("MAX_RUNTIME_SECS", 3600)),
+                idle_timeout: Duration::from_secs(env_secs("IDLE_TIMEOUT_SECS", 600)),
+            };
 
             relay_client::run_ws_client(
                 &ws_url,
@@ -64,6 +81,8 @@ async fn main() -> anyhow::Result<()> {
                 &default_repo,
                 &translator_model,
                 &workload_model,
+                max_concurrent,
+                limits,
             )
             .await?;
         }
@@ -100,6 +119,17 @@ async fn main() -> anyhow::Result<()> {
             println!();
             println!("Add this TOTP secret to your authenticator app:");
             println!("{}", totp_secret);
+            let recovery_codes = body["recovery_codes"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            if !recovery_codes.is_empty() {
+                println!();
+                println!("Recovery codes (each logs in once without TOTP):");
+                for code in recovery_codes.iter().filter_map(|c| c.as_str()) {
+                    println!("{}", code);
+                }
+            }
         }
     }
 
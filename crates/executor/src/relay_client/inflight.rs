@@ -0,0 +1,60 @@
+//! Registry of commands currently running on this executor.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use uuid::Uuid;
+
+use crate::cursor::CancelToken;
+
+/// In-flight commands keyed by command id, so a `command_cancel` from the relayer can reach
+/// the run it targets. Outlives individual WebSocket connections: runs keep going across
+/// reconnects and stay cancellable.
+#[derive(Clone, Default)]
+pub struct InFlight(Arc<Mutex<HashMap<Uuid, CancelToken>>>);
+
+impl InFlight {
+    /// Register a command and return its cancel token.
+    pub fn register(&self, id: Uuid) -> CancelToken {
+        let token = CancelToken::new();
+        self.0.lock().unwrap().insert(id, token.clone());
+        token
+    }
+
+    /// True if the command is already running (or queued) here.
+    pub fn contains(&self, id: Uuid) -> bool {
+        self.0.lock().unwrap().contains_key(&id)
+    }
+
+    pub fn remove(&self, id: Uuid) {
+        self.0.lock().unwrap().remove(&id);
+    }
+
+    /// Cancel a registered command. Returns false if it is not running here.
+    pub fn cancel(&self, id: Uuid) -> bool {
+        match self.0.lock().unwrap().get(&id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_reaches_registered_token() {
+        let inflight = InFlight::default();
+        let id = Uuid::new_v4();
+        let token = inflight.register(id);
+        assert!(!token.is_cancelled());
+        assert!(inflight.cancel(id));
+        assert!(token.is_cancelled());
+        inflight.remove(id);
+        assert!(!inflight.cancel(id));
+    }
+}
@@ -1,5 +1,8 @@
 //! WebSocket and HTTP client for relayer.
 
+mod inflight;
+mod output;
+mod tool_calls;
 mod ws;
 
 pub use ws::run_ws_client;
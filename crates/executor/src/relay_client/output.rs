@@ -0,0 +1,136 @@
+//! Streams a running command's output to the relayer as sequenced deltas.
+
+use std::time::Duration;
+
+use shared::{AppendOutputEventsRequest, OutputEvent, OutputEventKind};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+#[derive(Default)]
+struct Snapshot {
+    output: String,
+    finished: bool,
+}
+
+/// Background sender for one command's output events. The agent callback pushes the full
+/// output seen so far; the sender posts at most one delta per throttle interval, in order,
+/// so events reach the relayer with strictly increasing `seq`.
+pub struct OutputStream {
+    tx: watch::Sender<Snapshot>,
+    task: JoinHandle<()>,
+}
+
+impl OutputStream {
+    /// Start streaming to `events_url` (`POST /api/commands/{id}/events`), numbering events
+    /// after `last_seq`, the last seq the relayer already has for the command.
+    pub fn spawn(events_url: String, api_key: String, throttle: Duration, last_seq: u64) -> Self {
+        let (tx, rx) = watch::channel(Snapshot::default());
+        let task = tokio::spawn(send_loop(events_url, api_key, throttle, last_seq, rx));
+        Self { tx, task }
+    }
+
+    /// Handle for the agent's output callback.
+    pub fn sender(&self) -> OutputSender {
+        OutputSender(self.tx.clone())
+    }
+
+    /// Send whatever is still pending and stop. Call before reporting the final status so
+    /// no delta arrives after it.
+    pub async fn finish(self) {
+        self.tx.send_modify(|s| s.finished = true);
+        let _ = self.task.await;
+    }
+}
+
+/// Cloneable handle that records output for an [`OutputStream`].
+#[derive(Clone)]
+pub struct OutputSender(watch::Sender<Snapshot>);
+
+impl OutputSender {
+    /// Record the full output so far. Cheap; sending happens in the background.
+    pub fn push(&self, output: &str) {
+        self.0.send_modify(|s| {
+            if s.output != output {
+                s.output = output.to_string();
+            }
+        });
+    }
+}
+
+async fn send_loop(
+    url: String,
+    api_key: String,
+    throttle: Duration,
+    mut seq: u64,
+    mut rx: watch::Receiver<Snapshot>,
+) {
+    let client = reqwest::Client::new();
+    // Output the relayer is known to have; `None` after a failed send so the next event is
+    // a full `replace` rather than a delta against something it may have missed.
+    let mut sent: Option<String> = Some(String::new());
+    loop {
+        if rx.changed().await.is_err() {
+            return;
+        }
+        let (current, finished) = {
+            let s = rx.borrow_and_update();
+            (s.output.clone(), s.finished)
+        };
+        if let Some((kind, content)) = delta(sent.as_deref(), &current) {
+            seq += 1;
+            let ok = client
+                .post(&url)
+                .bearer_auth(&api_key)
+                .json(&AppendOutputEventsRequest {
+                    events: vec![OutputEvent { seq, kind, content }],
+                })
+                .send()
+                .await
+                .map(|r| r.status().is_success())
+                .unwrap_or(false);
+            if !ok {
+                tracing::warn!(seq = seq, "failed to send output event");
+            }
+            sent = ok.then_some(current);
+        }
+        if finished {
+            return;
+        }
+        tokio::time::sleep(throttle).await;
+    }
+}
+
+/// Event that turns `sent` into `current`: an append when `current` extends it, otherwise
+/// a replace. `None` when nothing changed.
+fn delta(sent: Option<&str>, current: &str) -> Option<(OutputEventKind, String)> {
+    match sent {
+        Some(s) if s == current => None,
+        Some(s) if current.starts_with(s) => {
+            Some((OutputEventKind::Append, current[s.len()..].to_string()))
+        }
+        _ => Some((OutputEventKind::Replace, current.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_appends_suffix_or_replaces() {
+        assert_eq!(delta(Some("ab"), "ab"), None);
+        assert_eq!(
+            delta(Some("ab"), "abcd"),
+            Some((OutputEventKind::Append, "cd".to_string()))
+        );
+        assert_eq!(
+            delta(Some("abc"), "xyz"),
+            Some((OutputEventKind::Replace, "xyz".to_string()))
+        );
+        // Unknown relayer state always resyncs with the full output.
+        assert_eq!(
+            delta(None, "abc"),
+            Some((OutputEventKind::Replace, "abc".to_string()))
+        );
+    }
+}
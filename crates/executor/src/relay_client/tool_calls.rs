@@ -0,0 +1,55 @@
+//! Reports a running command's tool calls to the relayer.
+
+use std::sync::Arc;
+
+use shared::ToolCallRecord;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::cursor::OnToolCall;
+
+/// Background sender for one command's tool-call records, posted in the order the agent
+/// reported them so a completion never lands before its start.
+pub struct ToolCallReporter {
+    /// `None` tells the sender to stop.
+    tx: mpsc::UnboundedSender<Option<ToolCallRecord>>,
+    task: JoinHandle<()>,
+}
+
+impl ToolCallReporter {
+    /// Start reporting to `url` (`POST /api/commands/{id}/tool-calls`).
+    pub fn spawn(url: String, api_key: String) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Option<ToolCallRecord>>();
+        let task = tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            while let Some(Some(record)) = rx.recv().await {
+                let sent = client
+                    .post(&url)
+                    .bearer_auth(&api_key)
+                    .json(&record)
+                    .send()
+                    .await
+                    .map(|r| r.status().is_success())
+                    .unwrap_or(false);
+                if !sent {
+                    tracing::warn!(call_id = %record.call_id, "failed to report tool call");
+                }
+            }
+        });
+        Self { tx, task }
+    }
+
+    /// Callback for the agent run.
+    pub fn callback(&self) -> OnToolCall {
+        let tx = self.tx.clone();
+        Arc::new(move |record| {
+            let _ = tx.send(Some(record));
+        })
+    }
+
+    /// Send the records reported so far and stop; later records are dropped.
+    pub async fn finish(self) {
+        let _ = self.tx.send(None);
+        let _ = self.task.await;
+    }
+}
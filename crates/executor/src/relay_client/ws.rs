@@ -3,17 +3,32 @@
 use anyhow::Result;
 use futures_util::{SinkExt, StreamExt};
 use shared::{
-    FileReadResponseRequest, FileSearchMatch, FileSearchResponseRequest, WsCommandNewPayload,
-    WsEnvelope, WsFileReadRequestPayload, WsFileSearchRequestPayload,
+    failure_reasons, FileReadResponseRequest, FileSearchMatch, FileSearchResponseRequest,
+    WsCommandCancelPayload, WsCommandNewPayload, WsEnvelope, WsFileReadRequestPayload,
+    WsFileSearchRequestPayload,
 };
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
 use walkdir::WalkDir;
 
+use super::inflight::InFlight;
+use super::output::OutputStream;
+use super::tool_calls::ToolCallReporter;
 use crate::cursor;
+use crate::scheduler::{Place, Scheduler, Ticket};
+
+/// Fallbacks for fields a command leaves unset.
+struct CommandDefaults {
+    repo: String,
+    translator_model: String,
+    workload_model: String,
+    limits: cursor::RunLimits,
+}
 
 /// Normalize file_path: strip leading slash and ./ so repo.join() works correctly.
 fn normalize_file_path(file_path: &str) -> &str {
@@ -147,7 +162,7 @@ async fn handle_file_search(
         }
     }
 
-    matches.sort_by(|a, b| b.1.cmp(&a.1)); // newest first
+    matches.sort_by_key(|m| std::cmp::Reverse(m.1)); // newest first
 
     let limit = if name == "*.md" { 200 } else { 50 };
     let file_matches: Vec<FileSearchMatch> = matches
@@ -335,6 +350,8 @@ pub async fn run_ws_client(
     default_repo: &str,
     default_translator_model: &str,
     default_workload_model: &str,
+    max_concurrent: usize,
+    limits: cursor::RunLimits,
 ) -> Result<()> {
     let http_url = ws_url
         .replace("wss://", "https://")
@@ -351,18 +368,27 @@ pub async fn run_ws_client(
         .map(String::as_str)
         .unwrap_or(default_repo);
 
+    let defaults = Arc::new(CommandDefaults {
+        repo: fallback_repo.to_string(),
+        translator_model: default_translator_model.to_string(),
+        workload_model: default_workload_model.to_string(),
+        limits,
+    });
+
     let url = ws_url.to_string();
+    let inflight = InFlight::default();
+    let scheduler = Scheduler::new(max_concurrent);
     loop {
         match connect_async(&url).await {
             Ok((ws, _)) => {
                 tracing::info!("Connected to relayer");
                 if let Err(e) = handle_connection(
                     ws,
-                    fallback_repo,
-                    default_translator_model,
-                    default_workload_model,
+                    &defaults,
                     executor_api_key,
                     ws_url,
+                    &inflight,
+                    &scheduler,
                 )
                 .await
                 {
@@ -381,11 +407,11 @@ async fn handle_connection(
     ws: tokio_tungstenite::WebSocketStream<
         tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
     >,
-    default_repo: &str,
-    default_translator_model: &str,
-    default_workload_model: &str,
+    defaults: &Arc<CommandDefaults>,
     executor_api_key: &str,
     base_url: &str,
+    inflight: &InFlight,
+    scheduler: &Scheduler,
 ) -> Result<()> {
     let (mut ws_tx, mut ws_rx) = ws.split();
 
@@ -431,31 +457,55 @@ async fn handle_connection(
             if let Ok(cmd) =
                 serde_json::from_value::<shared::WsCommandNewPayload>(envelope.payload.clone())
             {
+                // Ack so the relayer does not replay it; the relayer may still deliver a
+                // command twice around a reconnect (broadcast + replay), so dedupe here.
+                ws_tx
+                    .send(Message::Text(
+                        serde_json::json!({
+                            "type": shared::ws_types::COMMAND_ACK,
+                            "payload": shared::WsCommandAckPayload { id: cmd.id }
+                        })
+                        .to_string(),
+                    ))
+                    .await?;
+                if inflight.contains(cmd.id) {
+                    tracing::debug!(cmd_id = %cmd.id, "command already running, ignoring duplicate");
+                    continue;
+                }
+                let cancel = inflight.register(cmd.id);
+                let repo = cmd.repo_path.as_deref().unwrap_or(&defaults.repo);
+                let mut ticket = scheduler.enqueue(cmd.id, repo, cmd.cursor_chat_id.as_deref());
                 tokio::spawn({
                     let base_url = base_url.to_string();
                     let api_key = executor_api_key.to_string();
-                    let repo = cmd
-                        .repo_path
-                        .clone()
-                        .unwrap_or_else(|| default_repo.to_string());
-                    let trans = cmd
-                        .translator_model
-                        .clone()
-                        .unwrap_or_else(|| default_translator_model.to_string());
-                    let work = cmd
-                        .workload_model
-                        .clone()
-                        .unwrap_or_else(|| default_workload_model.to_string());
+                    let inflight = inflight.clone();
+                    let defaults = defaults.clone();
                     async move {
-                        if let Err(e) =
-                            run_command(&base_url, &api_key, cmd, &repo, &trans, &work).await
-                        {
-                            tracing::error!("Command failed: {}", e);
+                        let id = cmd.id;
+                        if wait_for_turn(&base_url, &api_key, id, &mut ticket, &cancel).await {
+                            if let Err(e) =
+                                run_command(&base_url, &api_key, cmd, &defaults, cancel).await
+                            {
+                                tracing::error!("Command failed: {}", e);
+                            }
                         }
+                        drop(ticket);
+                        inflight.remove(id);
                     }
                 });
             }
         }
+        if envelope.r#type == shared::ws_types::COMMAND_CANCEL {
+            if let Ok(req) =
+                serde_json::from_value::<WsCommandCancelPayload>(envelope.payload.clone())
+            {
+                if inflight.cancel(req.id) {
+                    tracing::info!(cmd_id = %req.id, "cancel requested");
+                } else {
+                    tracing::debug!(cmd_id = %req.id, "cancel for command not running here");
+                }
+            }
+        }
         if envelope.r#type == shared::ws_types::FILE_READ_REQUEST {
             if let Ok(req) =
                 serde_json::from_value::<WsFileReadRequestPayload>(envelope.payload.clone())
@@ -523,65 +573,162 @@ async fn handle_connection(
     Ok(())
 }
 
+/// PATCH URL for a command, from the relayer WebSocket URL.
+fn command_url(base_url: &str, id: Uuid) -> String {
+    let http_url = base_url
+        .replace("wss://", "https://")
+        .replace("ws://", "http://");
+    format!("{}/api/commands/{}", http_url.trim_end_matches("/ws"), id)
+}
+
+/// Revision for the next status PATCH: microseconds since the epoch, bumped past the last one
+/// handed out so it keeps increasing across commands, approval legs and executor restarts. The
+/// relayer drops updates that are not newer than the last one it applied.
+fn next_revision() -> u64 {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let now = u64::try_from(chrono::Utc::now().timestamp_micros()).unwrap_or(0);
+    let prev = LAST
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap_or(0);
+    now.max(prev + 1)
+}
+
+/// PATCH a status update with the next revision. A rejected update (stale revision or a
+/// transition the command has already moved past) is logged and otherwise ignored.
+async fn patch_status(
+    client: &reqwest::Client,
+    patch_url: &str,
+    api_key: &str,
+    mut body: serde_json::Value,
+) {
+    body["revision"] = serde_json::json!(next_revision());
+    match client
+        .patch(patch_url)
+        .bearer_auth(api_key)
+        .json(&body)
+        .send()
+        .await
+    {
+        Ok(res) if !res.status().is_success() => {
+            let code = res.status();
+            let text = res.text().await.unwrap_or_default();
+            tracing::warn!(url = %patch_url, status = %code, body = %text, "status update rejected");
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!(url = %patch_url, err = %e, "status update failed"),
+    }
+}
+
+/// Wait for the scheduler to start this command, PATCHing `queued` with its position each
+/// time it changes. Returns false if the command was cancelled while still queued (after
+/// reporting `cancelled`).
+async fn wait_for_turn(
+    base_url: &str,
+    api_key: &str,
+    id: Uuid,
+    ticket: &mut Ticket,
+    cancel: &cursor::CancelToken,
+) -> bool {
+    let patch_url = command_url(base_url, id);
+    let client = reqwest::Client::new();
+    let mut place = ticket.place();
+    loop {
+        let Place::Queued { ahead } = place else {
+            return true;
+        };
+        tracing::info!(cmd_id = %id, ahead = ahead, "command queued");
+        patch_status(
+            &client,
+            &patch_url,
+            api_key,
+            serde_json::json!({
+                "status": "queued",
+                "queue_position": ahead
+            }),
+        )
+        .await;
+        tokio::select! {
+            next = ticket.changed() => place = next,
+            _ = cancel.cancelled() => {
+                tracing::info!(cmd_id = %id, "cancelled while queued");
+                patch_status(
+                    &client,
+                    &patch_url,
+                    api_key,
+                    serde_json::json!({
+                        "status": "cancelled",
+                        "output": "[Cancelled while queued]"
+                    }),
+                )
+                .await;
+                return false;
+            }
+        }
+    }
+}
+
 async fn run_command(
     base_url: &str,
     api_key: &str,
     cmd: WsCommandNewPayload,
-    default_repo: &str,
-    translator_model: &str,
-    workload_model: &str,
+    defaults: &CommandDefaults,
+    cancel: cursor::CancelToken,
 ) -> Result<()> {
-    let http_url = base_url
-        .replace("wss://", "https://")
-        .replace("ws://", "http://");
-    let cmd_url = http_url.trim_end_matches("/ws");
-    let patch_url = format!("{}/api/commands/{}", cmd_url, cmd.id);
+    let patch_url = command_url(base_url, cmd.id);
 
     let client = reqwest::Client::new();
 
     // PATCH status = running
-    let _ = client
-        .patch(&patch_url)
-        .bearer_auth(api_key)
-        .json(&serde_json::json!({
-            "status": "running"
-        }))
-        .send()
-        .await;
+    patch_status(
+        &client,
+        &patch_url,
+        api_key,
+        serde_json::json!({ "status": "running" }),
+    )
+    .await;
 
-    let repo = cmd.repo_path.as_deref().unwrap_or(default_repo);
-    let trans = cmd.translator_model.as_deref().unwrap_or(translator_model);
-    let work = cmd.workload_model.as_deref().unwrap_or(workload_model);
+    let repo = cmd.repo_path.as_deref().unwrap_or(&defaults.repo);
+    let trans = cmd
+        .translator_model
+        .as_deref()
+        .unwrap_or(&defaults.translator_model);
+    let work = cmd
+        .workload_model
+        .as_deref()
+        .unwrap_or(&defaults.workload_model);
+    let limits = cursor::RunLimits {
+        max_runtime: cmd
+            .max_runtime_secs
+            .map(std::time::Duration::from_secs)
+            .unwrap_or(defaults.limits.max_runtime),
+        ..defaults.limits
+    };
 
-    let patch_url_for_cb = patch_url.to_string();
-    let api_key_for_cb = api_key.to_string();
-    let last_send = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
-    let throttle_ms = 300u64;
+    let stream = OutputStream::spawn(
+        format!("{}/events", patch_url),
+        api_key.to_string(),
+        std::time::Duration::from_millis(300),
+        cmd.output_seq.unwrap_or(0),
+    );
+    let stream_tx = stream.sender();
+    let tool_calls =
+        ToolCallReporter::spawn(format!("{}/tool-calls", patch_url), api_key.to_string());
+    // Latest output, unthrottled, so a cancelled run can report everything it produced.
+    let last_output = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    let last_output_for_cb = last_output.clone();
+    // Last time the agent produced output, for the no-output watchdog.
+    let activity = cursor::Activity::new();
+    let activity_for_cb = activity.clone();
+    let cancel_for_cb = cancel.clone();
     let on_output = std::sync::Arc::new(move |output: &str| {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
-        let last = last_send.load(std::sync::atomic::Ordering::Relaxed);
-        if last > 0 && now.saturating_sub(last) < throttle_ms {
+        *last_output_for_cb.lock().unwrap() = output.to_string();
+        activity_for_cb.touch();
+        if cancel_for_cb.is_cancelled() {
             return;
         }
-        last_send.store(now, std::sync::atomic::Ordering::Relaxed);
-        let url = patch_url_for_cb.clone();
-        let key = api_key_for_cb.clone();
-        let out = output.to_string();
-        tokio::spawn(async move {
-            let client = reqwest::Client::new();
-            let _ = client
-                .patch(&url)
-                .bearer_auth(&key)
-                .json(&serde_json::json!({
-                    "status": "running",
-                    "output": out
-                }))
-                .send()
-                .await;
-        });
+        stream_tx.push(output);
     });
 
     let chat_history: Option<Vec<(String, Option<String>)>> = cmd.chat_history.as_ref().map(|v| {
@@ -591,28 +738,80 @@ async fn run_command(
     });
     let chat_history_ref = chat_history.as_deref();
 
-    tracing::info!(cmd_id = %cmd.id, "running command");
+    tracing::info!(cmd_id = %cmd.id, max_runtime = ?limits.max_runtime, "running command");
+    let watchdog = tokio::spawn(cursor::watchdog(cancel.clone(), limits, activity.clone()));
+    let mut phases = Vec::new();
     let result = cursor::run_command(
-        &cmd.input,
-        repo,
-        trans,
-        work,
-        cmd.cursor_chat_id.as_deref(),
+        cursor::RunParams {
+            input: &cmd.input,
+            repo_path: repo,
+            translator_model: trans,
+            workload_model: work,
+            resume_chat_id: cmd.cursor_chat_id.as_deref(),
+            context_mode: cmd.context_mode.as_deref(),
+            template: cmd.template.as_deref(),
+            chat_history: chat_history_ref,
+            review_prompt: cmd.review_prompt,
+            approved_prompt: cmd.approved_prompt.as_deref(),
+        },
         Some(on_output),
-        cmd.context_mode.as_deref(),
-        chat_history_ref,
+        Some(tool_calls.callback()),
+        &mut phases,
+        &cancel,
+        &activity,
     )
     .await;
+    watchdog.abort();
+    stream.finish().await;
+    tool_calls.finish().await;
 
+    let mut failure_reason = None;
+    let mut proposed_prompt = None;
     let (status, output, summary, cursor_chat_id) = match result {
-        Ok((out, sum, chat_id)) => ("done", out, sum, Some(chat_id)),
+        Ok(cursor::RunOutcome::Finished {
+            output,
+            summary,
+            cursor_chat_id,
+        }) => ("done", output, summary, Some(cursor_chat_id)),
+        Ok(cursor::RunOutcome::AwaitingApproval { prompt }) => {
+            let output = format!("T: {}\n\n[Awaiting approval]", prompt);
+            proposed_prompt = Some(prompt);
+            ("awaiting_approval", output, String::new(), None)
+        }
+        Err(e) if e.is::<cursor::Cancelled>() => {
+            let (status, note) = match cancel.reason() {
+                Some(cursor::StopReason::Timeout) => {
+                    failure_reason = Some(failure_reasons::TIMEOUT);
+                    (
+                        "failed",
+                        format!("[Timed out after {}s]", limits.max_runtime.as_secs()),
+                    )
+                }
+                Some(cursor::StopReason::NoOutput) => {
+                    failure_reason = Some(failure_reasons::NO_OUTPUT);
+                    (
+                        "failed",
+                        format!("[No output for {}s]", limits.idle_timeout.as_secs()),
+                    )
+                }
+                _ => ("cancelled", "[Cancelled]".to_string()),
+            };
+            let partial = last_output.lock().unwrap().clone();
+            let output = if partial.is_empty() {
+                note
+            } else {
+                format!("{}\n\n{}", partial, note)
+            };
+            (status, output, String::new(), cmd.cursor_chat_id.clone())
+        }
         Err(e) => {
             tracing::error!(err = %e, "command failed");
+            failure_reason = Some(failure_reasons::AGENT_ERROR);
             ("failed", format!("Error: {}", e), String::new(), None)
         }
     };
 
-    tracing::info!(cmd_id = %cmd.id, status = status, "command finished");
+    tracing::info!(cmd_id = %cmd.id, status = status, failure_reason = failure_reason, "command finished");
     let mut patch_body = serde_json::json!({
         "status": status,
         "output": output,
@@ -621,12 +820,16 @@ async fn run_command(
     if let Some(ref chat_id) = cursor_chat_id {
         patch_body["cursor_chat_id"] = serde_json::json!(chat_id);
     }
-    let _ = client
-        .patch(&patch_url)
-        .bearer_auth(api_key)
-        .json(&patch_body)
-        .send()
-        .await;
+    if let Some(reason) = failure_reason {
+        patch_body["failure_reason"] = serde_json::json!(reason);
+    }
+    if let Some(prompt) = proposed_prompt {
+        patch_body["proposed_prompt"] = serde_json::json!(prompt);
+    }
+    if !phases.is_empty() {
+        patch_body["phases"] = serde_json::json!(phases);
+    }
+    patch_status(&client, &patch_url, api_key, patch_body).await;
 
     Ok(())
 }
@@ -0,0 +1,211 @@
+//! Command scheduler: bounds how many agents run at once and serializes runs that share a
+//! repo or a Cursor chat, so concurrent agents never edit the same checkout.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// Where a command stands in the scheduler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Place {
+    /// Waiting. `ahead` counts queued commands before it plus running commands on the same
+    /// repo or chat.
+    Queued { ahead: usize },
+    /// Holding a slot; the command may run until its `Ticket` is dropped.
+    Started,
+}
+
+struct Job {
+    id: Uuid,
+    repo: String,
+    chat: Option<String>,
+}
+
+impl Job {
+    fn conflicts(&self, other: &Job) -> bool {
+        self.repo == other.repo || (self.chat.is_some() && self.chat == other.chat)
+    }
+}
+
+struct Waiting {
+    job: Job,
+    tx: watch::Sender<Place>,
+}
+
+struct Inner {
+    max_concurrent: usize,
+    running: Vec<Job>,
+    waiting: VecDeque<Waiting>,
+}
+
+impl Inner {
+    /// Start every waiting job that can run, in FIFO order, then refresh queue positions.
+    /// A job that cannot start blocks later jobs on its repo/chat, so order is strict per key.
+    fn dispatch(&mut self) {
+        let mut blocked: Vec<Job> = Vec::new();
+        let mut i = 0;
+        while i < self.waiting.len() {
+            let job = &self.waiting[i].job;
+            let can_start = self.running.len() < self.max_concurrent
+                && !self.running.iter().any(|r| r.conflicts(job))
+                && !blocked.iter().any(|b| b.conflicts(job));
+            if can_start {
+                let w = self.waiting.remove(i).expect("index in range");
+                w.tx.send_replace(Place::Started);
+                self.running.push(w.job);
+            } else {
+                blocked.push(Job {
+                    id: job.id,
+                    repo: job.repo.clone(),
+                    chat: job.chat.clone(),
+                });
+                i += 1;
+            }
+        }
+        for (idx, w) in self.waiting.iter().enumerate() {
+            let ahead = idx + self.running.iter().filter(|r| r.conflicts(&w.job)).count();
+            w.tx.send_if_modified(|place| {
+                let next = Place::Queued { ahead };
+                let changed = *place != next;
+                *place = next;
+                changed
+            });
+        }
+    }
+
+    fn remove(&mut self, id: Uuid) {
+        self.running.retain(|j| j.id != id);
+        self.waiting.retain(|w| w.job.id != id);
+    }
+}
+
+/// Executor-wide scheduler. Clones share state.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Scheduler {
+    /// `max_concurrent` is clamped to at least 1.
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                max_concurrent: max_concurrent.max(1),
+                running: Vec::new(),
+                waiting: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Queue a command for `repo` (and `chat`, when resuming one). The returned ticket
+    /// reports its place; dropping it leaves the queue or frees the slot.
+    pub fn enqueue(&self, id: Uuid, repo: &str, chat: Option<&str>) -> Ticket {
+        let (tx, rx) = watch::channel(Place::Queued { ahead: 0 });
+        let job = Job {
+            id,
+            repo: repo_key(repo),
+            chat: chat.map(String::from),
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.waiting.push_back(Waiting { job, tx });
+        inner.dispatch();
+        Ticket {
+            id,
+            scheduler: self.clone(),
+            rx,
+        }
+    }
+}
+
+/// A command's place in the scheduler. Hold it for the whole run.
+pub struct Ticket {
+    id: Uuid,
+    scheduler: Scheduler,
+    rx: watch::Receiver<Place>,
+}
+
+impl Ticket {
+    pub fn place(&self) -> Place {
+        *self.rx.borrow()
+    }
+
+    /// Wait for the place to change and return it.
+    pub async fn changed(&mut self) -> Place {
+        // The sender lives in the scheduler until this ticket is dropped, so this only
+        // fails after `Started` (when the job has left the waiting queue).
+        let _ = self.rx.changed().await;
+        *self.rx.borrow_and_update()
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let mut inner = self.scheduler.inner.lock().unwrap();
+        inner.remove(self.id);
+        inner.dispatch();
+    }
+}
+
+/// Normalize a repo path so `~/repos/x`, `/home/u/repos/x` and `~/repos/x/` share a key.
+fn repo_key(repo: &str) -> String {
+    let expanded = shellexpand::tilde(repo.trim()).to_string();
+    expanded.trim_end_matches('/').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_same_repo() {
+        let s = Scheduler::new(4);
+        let a = s.enqueue(Uuid::new_v4(), "~/repos/a", None);
+        let b = s.enqueue(Uuid::new_v4(), "~/repos/a/", None);
+        let c = s.enqueue(Uuid::new_v4(), "~/repos/c", None);
+        assert_eq!(a.place(), Place::Started);
+        assert_eq!(b.place(), Place::Queued { ahead: 1 });
+        assert_eq!(c.place(), Place::Started);
+        drop(a);
+        assert_eq!(b.place(), Place::Started);
+    }
+
+    #[test]
+    fn serializes_same_chat_across_repos() {
+        let s = Scheduler::new(4);
+        let a = s.enqueue(Uuid::new_v4(), "~/repos/a", Some("chat-1"));
+        let b = s.enqueue(Uuid::new_v4(), "~/repos/b", Some("chat-1"));
+        assert_eq!(a.place(), Place::Started);
+        assert_eq!(b.place(), Place::Queued { ahead: 1 });
+    }
+
+    #[test]
+    fn global_cap_and_fifo_positions() {
+        let s = Scheduler::new(1);
+        let a = s.enqueue(Uuid::new_v4(), "~/repos/a", None);
+        let b = s.enqueue(Uuid::new_v4(), "~/repos/b", None);
+        let c = s.enqueue(Uuid::new_v4(), "~/repos/c", None);
+        assert_eq!(a.place(), Place::Started);
+        assert_eq!(b.place(), Place::Queued { ahead: 0 });
+        assert_eq!(c.place(), Place::Queued { ahead: 1 });
+        drop(b);
+        assert_eq!(c.place(), Place::Queued { ahead: 0 });
+        drop(a);
+        assert_eq!(c.place(), Place::Started);
+    }
+
+    #[test]
+    fn later_job_does_not_overtake_blocked_job_on_same_repo() {
+        let s = Scheduler::new(4);
+        let chat_holder = s.enqueue(Uuid::new_v4(), "~/repos/x", Some("chat-1"));
+        // Blocked by chat-1, not by its repo.
+        let first = s.enqueue(Uuid::new_v4(), "~/repos/a", Some("chat-1"));
+        let second = s.enqueue(Uuid::new_v4(), "~/repos/a", None);
+        assert_eq!(first.place(), Place::Queued { ahead: 1 });
+        assert_eq!(second.place(), Place::Queued { ahead: 1 });
+        drop(chat_holder);
+        assert_eq!(first.place(), Place::Started);
+        assert_eq!(second.place(), Place::Queued { ahead: 1 });
+    }
+}
@@ -2,6 +2,8 @@
 
 mod routes;
 
+pub(crate) use routes::{command_new_payload, create_and_send_command, CommandOrigin};
+
 use axum::{
     http::{header, HeaderValue, Method},
     routing::get,
@@ -52,7 +54,11 @@ pub fn router(state: AppState) -> Router {
             Method::DELETE,
             Method::OPTIONS,
         ])
-        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::HeaderName::from_static("idempotency-key"),
+        ]);
 
     Router::new()
         .route("/health", get(health))
@@ -1,5 +1,7 @@
 //! Authentication and authorization.
 
+pub mod webauthn;
+
 use anyhow::Result;
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
@@ -13,13 +15,27 @@ pub struct Claims {
     pub role: String,
     pub exp: i64,
     pub iat: i64,
+    /// Session id (see `db::create_session`).
+    #[serde(default)]
+    pub jti: String,
 }
 
-/// Create JWT for device.
+/// A validated JWT.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwtIdentity {
+    pub device_id: Uuid,
+    pub admin_id: Uuid,
+    pub role: String,
+    /// None for tokens issued before sessions existed; those are rejected.
+    pub session_id: Option<Uuid>,
+}
+
+/// Create JWT for a device's session.
 pub fn create_jwt(
     device_id: Uuid,
     admin_id: Uuid,
     role: &str,
+    session_id: Uuid,
     secret: &str,
     ttl_secs: u64,
 ) -> Result<String> {
@@ -32,6 +48,7 @@ pub fn create_jwt(
         role: role.to_string(),
         exp: now + ttl_secs as i64,
         iat: now,
+        jti: session_id.to_string(),
     };
     let token = encode(
         &Header::default(),
@@ -41,8 +58,8 @@ pub fn create_jwt(
     Ok(token)
 }
 
-/// Validate JWT and return (device_id, admin_id, role).
-pub fn validate_jwt(token: &str, secret: &str) -> Result<Option<(Uuid, Uuid, String)>> {
+/// Validate a JWT's signature and expiry. Whether its session is live is up to the caller.
+pub fn validate_jwt(token: &str, secret: &str) -> Result<Option<JwtIdentity>> {
     let mut validation = Validation::new(Algorithm::HS256);
     validation.validate_exp = true;
     let data = decode::<Claims>(
@@ -54,32 +71,32 @@ pub fn validate_jwt(token: &str, secret: &str) -> Result<Option<(Uuid, Uuid, Str
         Ok(data) => {
             let device_id = Uuid::parse_str(&data.claims.sub)?;
             let admin_id = Uuid::parse_str(&data.claims.admin_id)?;
-            Ok(Some((device_id, admin_id, data.claims.role)))
+            Ok(Some(JwtIdentity {
+                device_id,
+                admin_id,
+                role: data.claims.role,
+                session_id: Uuid::parse_str(&data.claims.jti).ok(),
+            }))
         }
         Err(_) => Ok(None),
     }
 }
 
-/// Decode JWT ignoring expiration (for refresh flow). Returns claims if signature is valid.
-pub fn decode_jwt_ignore_exp(token: &str, secret: &str) -> Result<Option<Claims>> {
-    let mut validation = Validation::new(Algorithm::HS256);
-    validation.validate_exp = false;
-    let data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &validation,
-    );
-    match data {
-        Ok(data) => Ok(Some(data.claims)),
-        Err(_) => Ok(None),
-    }
-}
-
 /// Hash API key for storage.
 pub fn hash_api_key(key: &str) -> Result<String> {
     Ok(bcrypt::hash(key, bcrypt::DEFAULT_COST)?)
 }
 
+/// Hash an executor key or refresh token for storage and lookup (SHA-256, hex). Both are random
+/// 256-bit values checked on every use, so they don't need bcrypt.
+pub fn hash_token(key: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
 /// Generate a random API key (hex).
 pub fn generate_api_key() -> String {
     use std::fmt::Write;
@@ -98,20 +115,60 @@ pub fn generate_totp_secret() -> Result<String> {
     Ok(base32::encode(Alphabet::RFC4648 { padding: false }, &bytes))
 }
 
-/// Verify TOTP code.
-pub fn verify_totp(secret: &str, code: &str) -> bool {
+/// TOTP time step length in seconds.
+const TOTP_STEP_SECS: u64 = 30;
+
+/// Verify a TOTP code, allowing one step of clock skew either way. Returns the time step the
+/// code belongs to, so a caller can refuse a step that was already used.
+pub fn verify_totp(secret: &str, code: &str) -> Option<u64> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    verify_totp_at(secret, code, now)
+}
+
+fn verify_totp_at(secret: &str, code: &str, now: u64) -> Option<u64> {
     use totp_rs::{Algorithm, TOTP};
     let secret_decoded = base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret);
     let bytes = match secret_decoded {
         Some(b) if b.len() >= 16 => b,
-        _ => return false,
+        _ => return None,
     };
-    let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, bytes);
-    let totp = match totp {
-        Ok(t) => t,
-        Err(_) => return false,
-    };
-    totp.check_current(code).unwrap_or(false)
+    // Skew 0: each step is checked on its own so the matching one is known.
+    let totp = TOTP::new(Algorithm::SHA1, 6, 0, TOTP_STEP_SECS, bytes).ok()?;
+    let current = now / TOTP_STEP_SECS;
+    (current.saturating_sub(1)..=current + 1).find(|step| totp.check(code, step * TOTP_STEP_SECS))
+}
+
+/// Number of recovery codes in a set.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Generate a set of one-time recovery codes: 80 random bits each, as four dash-separated
+/// groups of lowercase base32.
+pub fn generate_recovery_codes() -> Vec<String> {
+    use base32::Alphabet;
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let bytes: [u8; 10] = rand::random();
+            let code = base32::encode(Alphabet::RFC4648 { padding: false }, &bytes).to_lowercase();
+            code.as_bytes()
+                .chunks(4)
+                .map(|c| std::str::from_utf8(c).unwrap())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect()
+}
+
+/// Hash a recovery code for storage and lookup, ignoring case, dashes and whitespace.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    hash_token(&normalized)
 }
 
 #[cfg(test)]
@@ -133,13 +190,19 @@ mod tests {
         let admin_id = Uuid::new_v4();
         let role = "controller";
 
-        let token = create_jwt(device_id, admin_id, role, &secret, 3600).unwrap();
+        let session_id = Uuid::new_v4();
+
+        let token = create_jwt(device_id, admin_id, role, session_id, &secret, 3600).unwrap();
         let parsed = validate_jwt(&token, &secret).unwrap();
-        assert!(parsed.is_some());
-        let (d, a, r) = parsed.unwrap();
-        assert_eq!(d, device_id);
-        assert_eq!(a, admin_id);
-        assert_eq!(r, role);
+        assert_eq!(
+            parsed,
+            Some(JwtIdentity {
+                device_id,
+                admin_id,
+                role: role.to_string(),
+                session_id: Some(session_id),
+            })
+        );
     }
 
     #[test]
@@ -149,7 +212,15 @@ mod tests {
         let device_id = Uuid::new_v4();
         let admin_id = Uuid::new_v4();
 
-        let token = create_jwt(device_id, admin_id, "controller", &secret, 3600).unwrap();
+        let token = create_jwt(
+            device_id,
+            admin_id,
+            "controller",
+            Uuid::new_v4(),
+            &secret,
+            3600,
+        )
+        .unwrap();
         let parsed = validate_jwt(&token, &wrong_secret).unwrap();
         assert!(parsed.is_none());
     }
@@ -178,14 +249,32 @@ mod tests {
         assert!(bytes.len() >= 16);
 
         let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, bytes).unwrap();
-        let code = totp.generate_current().unwrap();
+        let now = 1_700_000_000;
 
-        assert!(verify_totp(&secret, &code));
+        // Codes from the neighbouring steps are accepted, each reporting its own step.
+        for step in [now / 30 - 1, now / 30, now / 30 + 1] {
+            let code = totp.generate(step * 30);
+            assert_eq!(verify_totp_at(&secret, &code, now), Some(step));
+        }
+        let stale = totp.generate(now - 90);
+        assert_eq!(verify_totp_at(&secret, &stale, now), None);
     }
 
     #[test]
     fn totp_rejects_wrong_code() {
         let secret = generate_totp_secret().unwrap();
-        assert!(!verify_totp(&secret, "000000"));
+        assert!(verify_totp(&secret, "000000").is_none());
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_hash_normalized() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(codes[0].len(), 19);
+        let unique: std::collections::HashSet<_> = codes.iter().collect();
+        assert_eq!(unique.len(), codes.len());
+        let typed = format!(" {} ", codes[0].replace('-', "").to_uppercase());
+        assert_eq!(hash_recovery_code(&typed), hash_recovery_code(&codes[0]));
+        assert_ne!(hash_recovery_code(&codes[1]), hash_recovery_code(&codes[0]));
     }
 }
@@ -0,0 +1,361 @@
+//! WebAuthn (passkey) registration and assertion checks.
+//!
+//! Only ES256 (P-256) credentials are accepted, and attestation statements are not checked: the
+//! relayer asks for `none` attestation, so a passkey proves possession of its key, not its make.
+
+use anyhow::{anyhow, bail, ensure, Result};
+use base64::Engine;
+use ciborium::Value;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// COSE algorithm id for ES256.
+pub const COSE_ALG_ES256: i64 = -7;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// The relying party credentials are scoped to: `id` is the web app's domain, `origin` its URL.
+#[derive(Debug, Clone, Copy)]
+pub struct RelyingParty<'a> {
+    pub id: &'a str,
+    pub origin: &'a str,
+}
+
+/// A credential created by a verified registration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewCredential {
+    /// The challenge the registration answered; the caller checks it was issued and unused.
+    pub challenge: String,
+    pub credential_id: Vec<u8>,
+    /// Uncompressed SEC1 P-256 point (0x04 || x || y).
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+/// A verified assertion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assertion {
+    /// The challenge the assertion signed; the caller checks it was issued and unused.
+    pub challenge: String,
+    pub sign_count: u32,
+}
+
+/// Generate a random challenge (base64url).
+pub fn generate_challenge() -> String {
+    let bytes: [u8; 32] = rand::random();
+    b64url(&bytes)
+}
+
+pub fn b64url(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn from_b64url(s: &str) -> Result<Vec<u8>> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(s.trim_end_matches('='))
+        .map_err(|_| anyhow!("invalid base64url"))
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ty: String,
+    challenge: String,
+    origin: String,
+}
+
+/// Check clientDataJSON's type and origin; returns its challenge.
+fn check_client_data(rp: RelyingParty, client_data_json: &[u8], ty: &str) -> Result<String> {
+    let data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| anyhow!("invalid client data"))?;
+    ensure!(data.ty == ty, "client data type is not {}", ty);
+    ensure!(data.origin == rp.origin, "origin mismatch");
+    Ok(data.challenge)
+}
+
+struct AuthenticatorData<'a> {
+    flags: u8,
+    sign_count: u32,
+    /// Attested credential data and extensions.
+    rest: &'a [u8],
+}
+
+/// Parse authenticator data and check it is for `rp` with the user present.
+fn parse_authenticator_data<'a>(rp: RelyingParty, data: &'a [u8]) -> Result<AuthenticatorData<'a>> {
+    ensure!(data.len() >= 37, "authenticator data too short");
+    ensure!(
+        data[..32] == Sha256::digest(rp.id.as_bytes())[..],
+        "rp id mismatch"
+    );
+    let flags = data[32];
+    ensure!(flags & FLAG_USER_PRESENT != 0, "user not present");
+    Ok(AuthenticatorData {
+        flags,
+        sign_count: u32::from_be_bytes([data[33], data[34], data[35], data[36]]),
+        rest: &data[37..],
+    })
+}
+
+fn cose_get(map: &[(Value, Value)], key: i64) -> Option<&Value> {
+    map.iter()
+        .find(|(k, _)| k.as_integer().is_some_and(|i| i128::from(i) == key as i128))
+        .map(|(_, v)| v)
+}
+
+/// Decode an ES256 COSE key to an uncompressed SEC1 point.
+fn es256_public_key(cose: &Value) -> Result<Vec<u8>> {
+    let map = cose.as_map().ok_or_else(|| anyhow!("invalid public key"))?;
+    let int = |key| {
+        cose_get(map, key)
+            .and_then(Value::as_integer)
+            .map(i128::from)
+    };
+    ensure!(
+        int(1) == Some(2) && int(3) == Some(COSE_ALG_ES256 as i128) && int(-1) == Some(1),
+        "only ES256 passkeys are supported"
+    );
+    let coord = |key| {
+        cose_get(map, key)
+            .and_then(Value::as_bytes)
+            .filter(|b| b.len() == 32)
+            .ok_or_else(|| anyhow!("invalid public key"))
+    };
+    let mut point = vec![0x04];
+    point.extend_from_slice(coord(-2)?);
+    point.extend_from_slice(coord(-3)?);
+    Ok(point)
+}
+
+/// Verify a `navigator.credentials.create()` response.
+pub fn verify_registration(
+    rp: RelyingParty,
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+) -> Result<NewCredential> {
+    let challenge = check_client_data(rp, client_data_json, "webauthn.create")?;
+    let attestation: Value = ciborium::de::from_reader(attestation_object)
+        .map_err(|_| anyhow!("invalid attestation object"))?;
+    let auth_data = attestation
+        .as_map()
+        .and_then(|m| {
+            m.iter()
+                .find(|(k, _)| k.as_text() == Some("authData"))
+                .and_then(|(_, v)| v.as_bytes())
+        })
+        .ok_or_else(|| anyhow!("attestation object has no authData"))?;
+    let data = parse_authenticator_data(rp, auth_data)?;
+    ensure!(
+        data.flags & FLAG_ATTESTED_CREDENTIAL != 0,
+        "no attested credential"
+    );
+    let rest = data.rest;
+    ensure!(rest.len() >= 18, "attested credential data too short");
+    let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+    ensure!(
+        rest.len() >= 18 + id_len,
+        "attested credential data too short"
+    );
+    let credential_id = rest[18..18 + id_len].to_vec();
+    let mut key_bytes = &rest[18 + id_len..];
+    let cose: Value =
+        ciborium::de::from_reader(&mut key_bytes).map_err(|_| anyhow!("invalid public key"))?;
+    Ok(NewCredential {
+        challenge,
+        credential_id,
+        public_key: es256_public_key(&cose)?,
+        sign_count: data.sign_count,
+    })
+}
+
+/// Verify a `navigator.credentials.get()` response against a stored credential. A signature
+/// counter that doesn't move forward means the authenticator was cloned, so it is refused.
+pub fn verify_assertion(
+    rp: RelyingParty,
+    public_key: &[u8],
+    stored_sign_count: u32,
+    client_data_json: &[u8],
+    authenticator_data: &[u8],
+    signature: &[u8],
+) -> Result<Assertion> {
+    let challenge = check_client_data(rp, client_data_json, "webauthn.get")?;
+    let data = parse_authenticator_data(rp, authenticator_data)?;
+    let mut signed = authenticator_data.to_vec();
+    signed.extend_from_slice(&Sha256::digest(client_data_json));
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ECDSA_P256_SHA256_ASN1, public_key)
+        .verify(&signed, signature)
+        .map_err(|_| anyhow!("invalid signature"))?;
+    if (data.sign_count != 0 || stored_sign_count != 0) && data.sign_count <= stored_sign_count {
+        bail!("signature counter did not increase");
+    }
+    Ok(Assertion {
+        challenge,
+        sign_count: data.sign_count,
+    })
+}
+
+/// A software authenticator for tests: one ES256 credential, `none` attestation.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+    pub struct SoftAuthenticator {
+        key: EcdsaKeyPair,
+        rp_id: String,
+        origin: String,
+        pub credential_id: Vec<u8>,
+        pub sign_count: u32,
+    }
+
+    /// An authenticator response, base64url-encoded as the web app sends it.
+    pub struct Response {
+        pub client_data_json: String,
+        /// Attestation object for registration, authenticator data for assertion.
+        pub data: String,
+        pub signature: String,
+    }
+
+    impl SoftAuthenticator {
+        pub fn new(rp_id: &str, origin: &str) -> Self {
+            let rng = SystemRandom::new();
+            let pkcs8 =
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+            let key =
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                    .unwrap();
+            Self {
+                key,
+                rp_id: rp_id.to_string(),
+                origin: origin.to_string(),
+                credential_id: rand::random::<[u8; 16]>().to_vec(),
+                sign_count: 0,
+            }
+        }
+
+        fn client_data(&self, ty: &str, challenge: &str) -> Vec<u8> {
+            serde_json::to_vec(&serde_json::json!({
+                "type": ty,
+                "challenge": challenge,
+                "origin": self.origin,
+                "crossOrigin": false,
+            }))
+            .unwrap()
+        }
+
+        fn auth_data(&self, flags: u8, attested: &[u8]) -> Vec<u8> {
+            let mut data = Sha256::digest(self.rp_id.as_bytes()).to_vec();
+            data.push(flags);
+            data.extend_from_slice(&self.sign_count.to_be_bytes());
+            data.extend_from_slice(attested);
+            data
+        }
+
+        pub fn register(&self, challenge: &str) -> Response {
+            let point = self.key.public_key().as_ref();
+            let cose = Value::Map(vec![
+                (Value::from(1), Value::from(2)),
+                (Value::from(3), Value::from(COSE_ALG_ES256)),
+                (Value::from(-1), Value::from(1)),
+                (Value::from(-2), Value::Bytes(point[1..33].to_vec())),
+                (Value::from(-3), Value::Bytes(point[33..].to_vec())),
+            ]);
+            let mut attested = vec![0u8; 16];
+            attested.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+            attested.extend_from_slice(&self.credential_id);
+            ciborium::ser::into_writer(&cose, &mut attested).unwrap();
+            let attestation = Value::Map(vec![
+                (Value::from("fmt"), Value::from("none")),
+                (Value::from("attStmt"), Value::Map(vec![])),
+                (
+                    Value::from("authData"),
+                    Value::Bytes(
+                        self.auth_data(FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL, &attested),
+                    ),
+                ),
+            ]);
+            let mut attestation_object = Vec::new();
+            ciborium::ser::into_writer(&attestation, &mut attestation_object).unwrap();
+            Response {
+                client_data_json: b64url(&self.client_data("webauthn.create", challenge)),
+                data: b64url(&attestation_object),
+                signature: String::new(),
+            }
+        }
+
+        pub fn assert(&mut self, challenge: &str) -> Response {
+            self.sign_count += 1;
+            let client_data = self.client_data("webauthn.get", challenge);
+            let auth_data = self.auth_data(FLAG_USER_PRESENT, &[]);
+            let mut signed = auth_data.clone();
+            signed.extend_from_slice(&Sha256::digest(&client_data));
+            let signature = self.key.sign(&SystemRandom::new(), &signed).unwrap();
+            Response {
+                client_data_json: b64url(&client_data),
+                data: b64url(&auth_data),
+                signature: b64url(signature.as_ref()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::SoftAuthenticator;
+    use super::*;
+
+    const RP: RelyingParty = RelyingParty {
+        id: "localhost",
+        origin: "http://localhost:5173",
+    };
+
+    fn register(auth: &SoftAuthenticator, challenge: &str) -> Result<NewCredential> {
+        let r = auth.register(challenge);
+        verify_registration(
+            RP,
+            &from_b64url(&r.client_data_json).unwrap(),
+            &from_b64url(&r.data).unwrap(),
+        )
+    }
+
+    fn assert(auth: &mut SoftAuthenticator, cred: &NewCredential, count: u32) -> Result<Assertion> {
+        let r = auth.assert("login-challenge");
+        verify_assertion(
+            RP,
+            &cred.public_key,
+            count,
+            &from_b64url(&r.client_data_json).unwrap(),
+            &from_b64url(&r.data).unwrap(),
+            &from_b64url(&r.signature).unwrap(),
+        )
+    }
+
+    #[test]
+    fn registration_then_assertion() {
+        let mut auth = SoftAuthenticator::new(RP.id, RP.origin);
+        let cred = register(&auth, "reg-challenge").unwrap();
+        assert_eq!(cred.challenge, "reg-challenge");
+        assert_eq!(cred.credential_id, auth.credential_id);
+        assert_eq!(cred.public_key.len(), 65);
+
+        let assertion = assert(&mut auth, &cred, 0).unwrap();
+        assert_eq!(assertion.challenge, "login-challenge");
+        assert_eq!(assertion.sign_count, 1);
+        // A counter that doesn't move forward looks like a cloned authenticator.
+        auth.sign_count = 0;
+        assert!(assert(&mut auth, &cred, 5).is_err());
+    }
+
+    #[test]
+    fn rejects_other_origins_rps_and_keys() {
+        let other_origin = SoftAuthenticator::new(RP.id, "https://evil.example");
+        assert!(register(&other_origin, "c").is_err());
+        let other_rp = SoftAuthenticator::new("evil.example", RP.origin);
+        assert!(register(&other_rp, "c").is_err());
+
+        let mut auth = SoftAuthenticator::new(RP.id, RP.origin);
+        let other = register(&SoftAuthenticator::new(RP.id, RP.origin), "c").unwrap();
+        assert!(assert(&mut auth, &other, 0).is_err());
+    }
+}
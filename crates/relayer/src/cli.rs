@@ -0,0 +1,79 @@
+//! CLI argument parsing.
+
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(name = "relayer")]
+#[command(about = "Dev PM Agent relayer — auth, device registration, command relay")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Serve the HTTP + WebSocket API [default]
+    Serve,
+
+    /// Manual recovery against the SQLite database (see docs/RECOVERY_CLI.md)
+    Recover(RecoverArgs),
+}
+
+#[derive(Args)]
+pub struct RecoverArgs {
+    /// Relayer SQLite database
+    #[arg(
+        long,
+        global = true,
+        env = "DATABASE_PATH",
+        default_value = "./data/relayer.db"
+    )]
+    pub db_path: PathBuf,
+
+    /// Allow recovery commands to run (or set RELAYER_RECOVERY_ENABLED=1)
+    #[arg(long, global = true)]
+    pub enable: bool,
+
+    #[command(subcommand)]
+    pub command: RecoverCommand,
+}
+
+#[derive(Subcommand)]
+pub enum RecoverCommand {
+    /// Set a new password for an admin; TOTP is unchanged
+    ResetPassword {
+        #[arg(long)]
+        username: String,
+
+        #[arg(long)]
+        new_password: String,
+
+        /// Server salt the relayer runs with
+        #[arg(long, env = "PASSWORD_SALT", hide_env_values = true)]
+        password_salt: String,
+
+        /// Client salt the webapp hashes passwords with (VITE_CLIENT_SALT)
+        #[arg(long, env = "CLIENT_SALT", hide_env_values = true)]
+        client_salt: String,
+    },
+
+    /// Replace an admin's TOTP secret and print the new one
+    ResetTotp {
+        #[arg(long)]
+        username: String,
+    },
+
+    /// Revoke a device, looked up by `device_id` or `id`
+    RevokeDevice {
+        #[arg(long)]
+        device_id: String,
+    },
+
+    /// List executor and controller devices
+    ListDevices,
+
+    /// Delete used and expired device registration codes
+    ClearExpiredCodes,
+}
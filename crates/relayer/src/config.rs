@@ -10,12 +10,20 @@ pub struct Config {
     pub database_path: PathBuf,
     pub jwt_secret: String,
     pub jwt_ttl_secs: u64,
+    /// How long a session stays refreshable after its JWT expires. Each refresh extends it.
     pub jwt_refresh_grace_secs: u64,
     pub executor_api_key: String,
     pub device_registration_code_ttl_secs: u64,
     pub password_salt: String,
     /// Allowed CORS origins (e.g. frontend URL). Comma-separated in env.
     pub cors_allowed_origins: Vec<String>,
+    /// Fail `running` commands with no update for this long (executor crashed mid-run).
+    /// Keep above the executor's IDLE_TIMEOUT_SECS.
+    pub stale_command_secs: u64,
+    /// WebAuthn relying party id: the web app's domain, which passkeys are bound to.
+    pub webauthn_rp_id: String,
+    /// Origin passkey ceremonies must come from (the web app's URL).
+    pub webauthn_origin: String,
 }
 
 impl Config {
@@ -59,6 +67,16 @@ impl Config {
                     "http://127.0.0.1:5173".to_string(),
                 ]
             });
+        let stale_command_secs = std::env::var("STALE_COMMAND_SECS")
+            .unwrap_or_else(|_| "900".to_string())
+            .parse()
+            .unwrap_or(900);
+        let webauthn_origin = std::env::var("WEBAUTHN_ORIGIN")
+            .ok()
+            .or_else(|| cors_allowed_origins.first().cloned())
+            .unwrap_or_default();
+        let webauthn_rp_id = std::env::var("WEBAUTHN_RP_ID")
+            .unwrap_or_else(|_| origin_host(&webauthn_origin).to_string());
 
         Ok(Self {
             host,
@@ -71,6 +89,9 @@ impl Config {
             device_registration_code_ttl_secs,
             password_salt,
             cors_allowed_origins,
+            stale_command_secs,
+            webauthn_rp_id,
+            webauthn_origin,
         })
     }
 
@@ -93,6 +114,16 @@ impl Config {
             device_registration_code_ttl_secs: 600,
             password_salt: password_salt.into(),
             cors_allowed_origins: vec!["http://localhost:5173".to_string()],
+            stale_command_secs: 900,
+            webauthn_rp_id: "localhost".to_string(),
+            webauthn_origin: "http://localhost:5173".to_string(),
         }
     }
 }
+
+/// Host of an origin URL: `https://app.example.com:8443` -> `app.example.com`.
+fn origin_host(origin: &str) -> &str {
+    let rest = origin.split_once("://").map_or(origin, |(_, r)| r);
+    let host = rest.split('/').next().unwrap_or(rest);
+    host.rsplit_once(':').map_or(host, |(h, _)| h)
+}
@@ -22,7 +22,7 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
     let mut entries: Vec<_> = fs::read_dir(migrations_dir)?
         .filter_map(|e| e.ok())
         .map(|e| e.path())
-        .filter(|p| p.extension().map_or(false, |e| e == "sql"))
+        .filter(|p| p.extension().is_some_and(|e| e == "sql"))
         .collect();
     entries.sort();
 
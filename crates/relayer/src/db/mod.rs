@@ -8,6 +8,10 @@ use anyhow::{anyhow, Result};
 /// Reduces timing side channel: always perform at least one bcrypt verify.
 const DUMMY_BCRYPT_HASH: &str = "$2b$12$LQv3c1yqBWVHxkd0LHAkCOYz6TtxMQJqhN8/X4.VTtTfBd3c9zJWi";
 use rusqlite::{params, Connection};
+use shared::{
+    status_actors, CommandPhase, CommandStatus, CommandStatusChange, TokenScope, ToolCallRecord,
+    UsageStats, UserRole, WorkflowStep,
+};
 use std::path::Path;
 use std::sync::Mutex;
 use uuid::Uuid;
@@ -91,26 +95,47 @@ pub fn admin_exists(conn: &Connection) -> Result<bool> {
     Ok(count > 0)
 }
 
-/// Create admin and first controller device (setup).
+/// Create admin and first controller device (setup). The admin is the relayer's owner.
+/// Returns the admin id.
 pub fn setup_admin(
     conn: &Connection,
     username: &str,
     password_hash: &str,
     totp_secret: &str,
     device_api_key_hash: &str,
-) -> Result<()> {
+) -> Result<Uuid> {
+    create_user(
+        conn,
+        username,
+        password_hash,
+        totp_secret,
+        device_api_key_hash,
+        UserRole::Owner,
+    )
+}
+
+/// Create a user with `role` and their first controller device. Returns the user's id.
+pub fn create_user(
+    conn: &Connection,
+    username: &str,
+    password_hash: &str,
+    totp_secret: &str,
+    device_api_key_hash: &str,
+    role: UserRole,
+) -> Result<Uuid> {
     let admin_id = Uuid::new_v4();
     let device_id = Uuid::new_v4();
     let now = chrono_iso8601();
 
     conn.execute(
-        "INSERT INTO admin (id, username, password_hash, totp_secret, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        "INSERT INTO admin (id, username, password_hash, totp_secret, role, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
         params![
             admin_id.to_string(),
             username,
             password_hash,
             totp_secret,
+            role.as_str(),
             now,
         ],
     )?;
@@ -121,7 +146,7 @@ pub fn setup_admin(
         params![device_id.to_string(), admin_id.to_string(), device_api_key_hash, now],
     )?;
 
-    Ok(())
+    Ok(admin_id)
 }
 
 /// Get admin by username.
@@ -142,6 +167,214 @@ pub fn get_admin(conn: &Connection, username: &str) -> Result<Option<(String, St
     }
 }
 
+/// A user account, as listed to owners.
+#[derive(Debug, Clone)]
+pub struct UserRow {
+    pub id: Uuid,
+    pub username: String,
+    pub role: UserRole,
+    pub created_at: String,
+    pub disabled_at: Option<String>,
+}
+
+const USER_COLUMNS: &str = "id, username, role, created_at, disabled_at";
+
+fn user_from_row(row: &rusqlite::Row) -> rusqlite::Result<UserRow> {
+    Ok(UserRow {
+        id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
+        username: row.get(1)?,
+        role: UserRole::parse(&row.get::<_, String>(2)?).unwrap_or(UserRole::Viewer),
+        created_at: row.get(3)?,
+        disabled_at: row.get(4)?,
+    })
+}
+
+/// Get a user by id, disabled ones included.
+pub fn get_user(conn: &Connection, id: Uuid) -> Result<Option<UserRow>> {
+    match conn.query_row(
+        &format!("SELECT {} FROM admin WHERE id = ?1", USER_COLUMNS),
+        [id.to_string()],
+        user_from_row,
+    ) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// All users, disabled ones included, oldest first.
+pub fn list_users(conn: &Connection) -> Result<Vec<UserRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM admin ORDER BY created_at, id",
+        USER_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], user_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Change a user's role. Returns false if not found or disabled.
+pub fn set_user_role(conn: &Connection, id: Uuid, role: UserRole) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE admin SET role = ?1, updated_at = ?2 WHERE id = ?3 AND disabled_at IS NULL",
+        params![role.as_str(), chrono_iso8601(), id.to_string()],
+    )?;
+    Ok(n > 0)
+}
+
+/// Disable a user: revoke their controller devices (ending their sessions and access tokens)
+/// and turn off their schedules. Their commands, and the executor if they registered it, are
+/// kept. Returns the revoked device ids, or None if not found or already disabled.
+pub fn disable_user(conn: &Connection, id: Uuid) -> Result<Option<Vec<Uuid>>> {
+    let now = chrono_iso8601();
+    let tx = conn.unchecked_transaction()?;
+    let n = tx.execute(
+        "UPDATE admin SET disabled_at = ?1, updated_at = ?1 WHERE id = ?2 AND disabled_at IS NULL",
+        params![now, id.to_string()],
+    )?;
+    if n == 0 {
+        return Ok(None);
+    }
+    tx.execute(
+        "UPDATE schedules SET enabled = 0, next_run_at = NULL, updated_at = ?1 WHERE admin_id = ?2",
+        params![now, id.to_string()],
+    )?;
+    let mut revoked = Vec::new();
+    for d in list_admin_devices(&tx, id)? {
+        let device_id = Uuid::parse_str(&d.id)?;
+        if d.role == "controller" && revoke_device(&tx, device_id, id)? {
+            revoked.push(device_id);
+        }
+    }
+    tx.commit()?;
+    Ok(Some(revoked))
+}
+
+/// Invitation metadata; the code itself is only returned when created.
+#[derive(Debug, Clone)]
+pub struct InvitationRow {
+    pub id: Uuid,
+    pub role: UserRole,
+    pub created_at: String,
+    pub expires_at: String,
+    pub accepted_at: Option<String>,
+    pub accepted_by: Option<Uuid>,
+    pub revoked_at: Option<String>,
+}
+
+const INVITATION_COLUMNS: &str =
+    "id, role, created_at, expires_at, accepted_at, accepted_by, revoked_at";
+
+fn invitation_from_row(row: &rusqlite::Row) -> rusqlite::Result<InvitationRow> {
+    Ok(InvitationRow {
+        id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
+        role: UserRole::parse(&row.get::<_, String>(1)?).unwrap_or(UserRole::Viewer),
+        created_at: row.get(2)?,
+        expires_at: row.get(3)?,
+        accepted_at: row.get(4)?,
+        accepted_by: row
+            .get::<_, Option<String>>(5)?
+            .map(|id| Uuid::parse_str(&id).unwrap()),
+        revoked_at: row.get(6)?,
+    })
+}
+
+/// Store an invitation for a new user with `role`. Returns it as listed.
+pub fn create_invitation(
+    conn: &Connection,
+    code_hash: &str,
+    role: UserRole,
+    created_by: Uuid,
+    expires_at: &str,
+) -> Result<InvitationRow> {
+    let id = Uuid::new_v4();
+    conn.execute(
+        "INSERT INTO invitations (id, code_hash, role, created_by, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            id.to_string(),
+            code_hash,
+            role.as_str(),
+            created_by.to_string(),
+            chrono_iso8601(),
+            expires_at
+        ],
+    )?;
+    Ok(conn.query_row(
+        &format!(
+            "SELECT {} FROM invitations WHERE id = ?1",
+            INVITATION_COLUMNS
+        ),
+        [id.to_string()],
+        invitation_from_row,
+    )?)
+}
+
+/// All invitations, newest first.
+pub fn list_invitations(conn: &Connection) -> Result<Vec<InvitationRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM invitations ORDER BY created_at DESC, id",
+        INVITATION_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], invitation_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Revoke an invitation that was not accepted yet. Returns false if not found, accepted or
+/// already revoked.
+pub fn revoke_invitation(conn: &Connection, id: Uuid) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE invitations SET revoked_at = ?1
+         WHERE id = ?2 AND accepted_at IS NULL AND revoked_at IS NULL",
+        params![chrono_iso8601(), id.to_string()],
+    )?;
+    Ok(n > 0)
+}
+
+/// Use up a live invitation, creating its user and their first controller device. Returns the
+/// new user's id, or None if the code is unknown, used, revoked or expired.
+pub fn accept_invitation(
+    conn: &Connection,
+    code_hash: &str,
+    username: &str,
+    password_hash: &str,
+    totp_secret: &str,
+    device_api_key_hash: &str,
+) -> Result<Option<Uuid>> {
+    let now = chrono_iso8601();
+    let tx = conn.unchecked_transaction()?;
+    let row = tx.query_row(
+        "SELECT id, role FROM invitations
+         WHERE code_hash = ?1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > ?2",
+        params![code_hash, now],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    );
+    let (id, role) = match row {
+        Ok(r) => r,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let role = UserRole::parse(&role).ok_or_else(|| anyhow!("invalid role {}", role))?;
+    let user_id = create_user(
+        &tx,
+        username,
+        password_hash,
+        totp_secret,
+        device_api_key_hash,
+        role,
+    )?;
+    let n = tx.execute(
+        "UPDATE invitations SET accepted_at = ?1, accepted_by = ?2
+         WHERE id = ?3 AND accepted_at IS NULL",
+        params![now, user_id.to_string(), id],
+    )?;
+    if n != 1 {
+        // Accepted by someone else since the SELECT; the user created above is rolled back.
+        return Ok(None);
+    }
+    tx.commit()?;
+    Ok(Some(user_id))
+}
+
 /// Validate device by API key (verify plaintext against stored bcrypt hashes).
 /// Returns (device_id, admin_id, role).
 /// Performs constant-time bcrypt when no match to avoid leaking key existence via timing.
@@ -193,14 +426,14 @@ pub fn reserve_code(
 
 /// Consume a registration code and create new controller device.
 /// password is the client-hashed value; password_salt is prepended for server-side verification.
-/// Returns totp_secret on success.
+/// Returns (admin id, totp_secret) on success.
 pub fn register_device(
     conn: &Connection,
     code: &str,
     password: &str,
     device_api_key_hash: &str,
     password_salt: &str,
-) -> Result<Option<String>> {
+) -> Result<Option<(Uuid, String)>> {
     let now = chrono_iso8601();
 
     // Find code and validate
@@ -258,240 +491,2728 @@ pub fn register_device(
         [code],
     )?;
 
-    Ok(Some(totp_secret))
+    Ok(Some((Uuid::parse_str(&admin_id)?, totp_secret)))
 }
 
-/// Create a new command.
-pub fn create_command(
+/// Record TOTP time step `step` as used by admin. Returns false if that step or a later one was
+/// already used: the code is a replay.
+pub fn accept_totp_step(conn: &Connection, admin_id: Uuid, step: u64) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE admin SET totp_last_step = ?1
+         WHERE id = ?2 AND (totp_last_step IS NULL OR totp_last_step < ?1)",
+        params![step as i64, admin_id.to_string()],
+    )?;
+    Ok(n > 0)
+}
+
+/// Replace admin's recovery codes with a new set of hashes.
+pub fn replace_recovery_codes(
+    conn: &Connection,
+    admin_id: Uuid,
+    code_hashes: &[String],
+) -> Result<()> {
+    let now = chrono_iso8601();
+    conn.execute(
+        "DELETE FROM recovery_codes WHERE admin_id = ?1",
+        [admin_id.to_string()],
+    )?;
+    for hash in code_hashes {
+        conn.execute(
+            "INSERT INTO recovery_codes (id, admin_id, code_hash, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![Uuid::new_v4().to_string(), admin_id.to_string(), hash, now],
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether admin has a recovery code left to use.
+pub fn has_unused_recovery_codes(conn: &Connection, admin_id: Uuid) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM recovery_codes WHERE admin_id = ?1 AND used_at IS NULL)",
+        [admin_id.to_string()],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+/// Use up one of admin's recovery codes. Returns false if there is no such unused code.
+pub fn use_recovery_code(conn: &Connection, admin_id: Uuid, code_hash: &str) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE recovery_codes SET used_at = ?1
+         WHERE admin_id = ?2 AND code_hash = ?3 AND used_at IS NULL",
+        params![chrono_iso8601(), admin_id.to_string(), code_hash],
+    )?;
+    Ok(n > 0)
+}
+
+/// Device row with its admin's username.
+#[derive(Debug, Clone)]
+pub struct DeviceRow {
+    pub id: String,
+    pub admin_id: String,
+    pub device_id: String,
+    pub username: String,
+    pub name: Option<String>,
+    pub role: String,
+    pub registered_at: String,
+    pub last_seen_at: String,
+    pub revoked_at: Option<String>,
+}
+
+const DEVICE_COLUMNS: &str = "d.id, d.admin_id, d.device_id, a.username, d.name, d.role, d.registered_at, d.last_seen_at, d.revoked_at";
+
+fn device_from_row(row: &rusqlite::Row) -> rusqlite::Result<DeviceRow> {
+    Ok(DeviceRow {
+        id: row.get(0)?,
+        admin_id: row.get(1)?,
+        device_id: row.get(2)?,
+        username: row.get(3)?,
+        name: row.get(4)?,
+        role: row.get(5)?,
+        registered_at: row.get(6)?,
+        last_seen_at: row.get(7)?,
+        revoked_at: row.get(8)?,
+    })
+}
+
+/// All devices, revoked ones included, executors first, then by registration time.
+pub fn list_devices(conn: &Connection) -> Result<Vec<DeviceRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM devices d JOIN admin a ON a.id = d.admin_id
+         ORDER BY d.role = 'controller', d.registered_at, d.id",
+        DEVICE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], device_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Admin's devices that are not revoked, by registration time.
+pub fn list_admin_devices(conn: &Connection, admin_id: Uuid) -> Result<Vec<DeviceRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM devices d JOIN admin a ON a.id = d.admin_id
+         WHERE d.admin_id = ?1 AND d.revoked_at IS NULL
+         ORDER BY d.registered_at, d.id",
+        DEVICE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([admin_id.to_string()], device_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Get a device by id if it belongs to admin and is not revoked.
+pub fn get_device(conn: &Connection, id: Uuid, admin_id: Uuid) -> Result<Option<DeviceRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM devices d JOIN admin a ON a.id = d.admin_id
+         WHERE d.id = ?1 AND d.admin_id = ?2 AND d.revoked_at IS NULL",
+        DEVICE_COLUMNS
+    ))?;
+    match stmt.query_row([id.to_string(), admin_id.to_string()], device_from_row) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Devices whose `id` or `device_id` is `key`.
+pub fn find_devices(conn: &Connection, key: &str) -> Result<Vec<DeviceRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM devices d JOIN admin a ON a.id = d.admin_id
+         WHERE d.id = ?1 OR d.device_id = ?1 ORDER BY d.id",
+        DEVICE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([key], device_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// How old `devices.last_seen_at` gets before `touch_device` writes it again, so requests don't
+/// each write the device row.
+const LAST_SEEN_RESOLUTION_SECS: i64 = 60;
+
+/// Record that an authenticated device was seen now, to within `LAST_SEEN_RESOLUTION_SECS`.
+/// Returns false if it does not exist, is revoked or belongs to another admin, i.e. its
+/// credentials must be rejected.
+pub fn touch_device(conn: &Connection, id: Uuid, admin_id: Uuid) -> Result<bool> {
+    let live: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM devices
+                       WHERE id = ?1 AND admin_id = ?2 AND revoked_at IS NULL)",
+        params![id.to_string(), admin_id.to_string()],
+        |row| row.get(0),
+    )?;
+    if live {
+        let stale_before = (chrono::Utc::now()
+            - chrono::Duration::seconds(LAST_SEEN_RESOLUTION_SECS))
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string();
+        conn.execute(
+            "UPDATE devices SET last_seen_at = ?1 WHERE id = ?2 AND last_seen_at < ?3",
+            params![chrono_iso8601(), id.to_string(), stale_before],
+        )?;
+    }
+    Ok(live)
+}
+
+/// Rename a device. Returns false if not found or revoked.
+pub fn rename_device(conn: &Connection, id: Uuid, admin_id: Uuid, name: &str) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE devices SET name = ?1 WHERE id = ?2 AND admin_id = ?3 AND revoked_at IS NULL",
+        params![name, id.to_string(), admin_id.to_string()],
+    )?;
+    Ok(n > 0)
+}
+
+/// Revoke a device: drop its key hash so it can't log in and mark it and its sessions revoked so
+/// its JWTs are rejected. Returns false if not found or already revoked.
+pub fn revoke_device(conn: &Connection, id: Uuid, admin_id: Uuid) -> Result<bool> {
+    let now = chrono_iso8601();
+    let n = conn.execute(
+        "UPDATE devices SET revoked_at = ?1, token_hash = NULL
+         WHERE id = ?2 AND admin_id = ?3 AND revoked_at IS NULL",
+        params![now, id.to_string(), admin_id.to_string()],
+    )?;
+    if n > 0 {
+        conn.execute(
+            "UPDATE sessions SET revoked_at = ?1, revoked_reason = 'device'
+             WHERE device_id = ?2 AND revoked_at IS NULL",
+            params![now, id.to_string()],
+        )?;
+    }
+    Ok(n > 0)
+}
+
+/// Number of admin's controller devices that are not revoked.
+pub fn count_active_controllers(conn: &Connection, admin_id: Uuid) -> Result<i64> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) FROM devices
+         WHERE admin_id = ?1 AND role = 'controller' AND revoked_at IS NULL",
+        [admin_id.to_string()],
+        |row| row.get(0),
+    )?)
+}
+
+/// Executor key metadata; the key itself is only returned when issued.
+#[derive(Debug, Clone)]
+pub struct ExecutorKeyRow {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub prefix: String,
+    pub label: Option<String>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+const EXECUTOR_KEY_COLUMNS: &str =
+    "id, device_id, prefix, label, created_at, expires_at, revoked_at, last_used_at";
+
+fn executor_key_from_row(row: &rusqlite::Row) -> rusqlite::Result<ExecutorKeyRow> {
+    Ok(ExecutorKeyRow {
+        id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
+        device_id: Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
+        prefix: row.get(2)?,
+        label: row.get(3)?,
+        created_at: row.get(4)?,
+        expires_at: row.get(5)?,
+        revoked_at: row.get(6)?,
+        last_used_at: row.get(7)?,
+    })
+}
+
+/// The relayer's executor device, unless revoked. All users share it.
+pub fn get_executor(conn: &Connection) -> Result<Option<Uuid>> {
+    match conn.query_row(
+        "SELECT id FROM devices WHERE role = 'executor' AND revoked_at IS NULL
+         ORDER BY registered_at LIMIT 1",
+        [],
+        |row| row.get::<_, String>(0),
+    ) {
+        Ok(id) => Ok(Some(Uuid::parse_str(&id)?)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Create the relayer's executor device, owned by `admin_id`. Fails if that admin already has
+/// one that is not revoked.
+pub fn create_executor(conn: &Connection, admin_id: Uuid, name: &str) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    let now = chrono_iso8601();
+    conn.execute(
+        "INSERT INTO devices (id, admin_id, device_id, name, role, token_hash, registered_at, last_seen_at)
+         VALUES (?1, ?2, ?1, ?3, 'executor', NULL, ?4, ?4)",
+        params![id.to_string(), admin_id.to_string(), name, now],
+    )?;
+    Ok(id)
+}
+
+/// Store a new key for an executor. With `others_expire_at`, the device's other live keys
+/// expire then at the latest (rotation with a grace period).
+pub fn insert_executor_key(
     conn: &Connection,
     device_id: Uuid,
-    input: &str,
-    repo_path: Option<&str>,
-    context_mode: Option<&str>,
-    translator_model: Option<&str>,
-    workload_model: Option<&str>,
-    cursor_chat_id: Option<&str>,
+    key_hash: &str,
+    prefix: &str,
+    label: Option<&str>,
+    others_expire_at: Option<&str>,
 ) -> Result<Uuid> {
     let id = Uuid::new_v4();
     let now = chrono_iso8601();
+    if let Some(at) = others_expire_at {
+        conn.execute(
+            "UPDATE executor_keys SET expires_at = ?1
+             WHERE device_id = ?2 AND revoked_at IS NULL
+               AND (expires_at IS NULL OR expires_at > ?1)",
+            params![at, device_id.to_string()],
+        )?;
+    }
     conn.execute(
-        "INSERT INTO commands (id, device_id, input, status, output, summary, repo_path, context_mode, translator_model, workload_model, cursor_chat_id, created_at, updated_at)
-         VALUES (?1, ?2, ?3, 'pending', NULL, NULL, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+        "INSERT INTO executor_keys (id, device_id, key_hash, prefix, label, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         params![
             id.to_string(),
             device_id.to_string(),
-            input,
-            repo_path,
-            context_mode,
-            translator_model,
-            workload_model,
-            cursor_chat_id,
-            now,
+            key_hash,
+            prefix,
+            label,
+            now
         ],
     )?;
     Ok(id)
 }
 
-/// Get command by id.
-pub fn get_command(
+/// An authenticated executor key.
+#[derive(Debug, Clone, Copy)]
+pub struct ExecutorAuth {
+    pub key_id: Uuid,
+    pub device_id: Uuid,
+    pub admin_id: Uuid,
+}
+
+/// Resolve an executor key by hash if the key is live and its device is not revoked,
+/// recording the use on both.
+pub fn authenticate_executor_key(
     conn: &Connection,
-    id: Uuid,
-) -> Result<
-    Option<(
-        Uuid,
-        Uuid,
-        String,
-        String,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        String,
-        String,
-    )>,
-> {
-    let mut stmt = conn.prepare(
-        "SELECT id, device_id, input, status, output, summary, repo_path, context_mode, translator_model, workload_model, cursor_chat_id, created_at, updated_at FROM commands WHERE id = ?1",
+    key_hash: &str,
+) -> Result<Option<ExecutorAuth>> {
+    let now = chrono_iso8601();
+    let row = conn.query_row(
+        "SELECT k.id, d.id, d.admin_id FROM executor_keys k JOIN devices d ON d.id = k.device_id
+         WHERE k.key_hash = ?1 AND k.revoked_at IS NULL
+           AND (k.expires_at IS NULL OR k.expires_at > ?2)
+           AND d.role = 'executor' AND d.revoked_at IS NULL",
+        params![key_hash, now],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        },
+    );
+    let (key_id, device_id, admin_id) = match row {
+        Ok(r) => r,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    conn.execute(
+        "UPDATE executor_keys SET last_used_at = ?1 WHERE id = ?2",
+        params![now, key_id],
     )?;
-    let row = stmt.query_row([id.to_string()], |row| {
-        Ok((
-            Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
-            Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
-            row.get(2)?,
-            row.get(3)?,
-            row.get(4)?,
-            row.get(5)?,
-            row.get(6)?,
-            row.get(7)?,
-            row.get(8)?,
-            row.get(9)?,
-            row.get(10)?,
-            row.get(11)?,
-            row.get(12)?,
-        ))
-    });
-    match row {
-        Ok(r) => Ok(Some(r)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.into()),
+    conn.execute(
+        "UPDATE devices SET last_seen_at = ?1 WHERE id = ?2",
+        params![now, device_id],
+    )?;
+    Ok(Some(ExecutorAuth {
+        key_id: Uuid::parse_str(&key_id)?,
+        device_id: Uuid::parse_str(&device_id)?,
+        admin_id: Uuid::parse_str(&admin_id)?,
+    }))
+}
+
+/// Whether a key with this hash was ever stored, including expired and revoked ones.
+pub fn executor_key_exists(conn: &Connection, key_hash: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM executor_keys WHERE key_hash = ?1)",
+        [key_hash],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+/// Label of the key imported from the relayer's `EXECUTOR_API_KEY` env var. Migration 022's
+/// header calls it 'env'; the stored label has always been this one.
+pub const ENV_EXECUTOR_KEY_LABEL: &str = "EXECUTOR_API_KEY";
+
+/// Import the relayer's env executor key as a key of the executor, labelled
+/// [`ENV_EXECUTOR_KEY_LABEL`], creating the executor device for the first owner if needed. No-op
+/// (false) when there is no owner yet or a key with this hash was stored before, even if it has
+/// since expired or been revoked.
+pub fn import_env_executor_key(conn: &Connection, key_hash: &str, prefix: &str) -> Result<bool> {
+    if executor_key_exists(conn, key_hash)? {
+        return Ok(false);
     }
+    let device_id = match get_executor(conn)? {
+        Some(id) => id,
+        None => {
+            let owner = conn.query_row(
+                "SELECT id FROM admin WHERE role = 'owner' AND disabled_at IS NULL
+                 ORDER BY created_at LIMIT 1",
+                [],
+                |row| row.get::<_, String>(0),
+            );
+            let admin_id = match owner {
+                Ok(id) => Uuid::parse_str(&id)?,
+                Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+                Err(e) => return Err(e.into()),
+            };
+            create_executor(conn, admin_id, "executor")?
+        }
+    };
+    insert_executor_key(
+        conn,
+        device_id,
+        key_hash,
+        prefix,
+        Some(ENV_EXECUTOR_KEY_LABEL),
+        None,
+    )?;
+    Ok(true)
 }
 
-/// List commands for admin.
-pub fn list_commands(
+/// Keys of an executor, newest first.
+pub fn list_executor_keys(conn: &Connection, device_id: Uuid) -> Result<Vec<ExecutorKeyRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM executor_keys WHERE device_id = ?1 ORDER BY created_at DESC, id",
+        EXECUTOR_KEY_COLUMNS
+    ))?;
+    let rows = stmt.query_map([device_id.to_string()], executor_key_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Revoke an executor key now. Returns false if not found or already revoked.
+pub fn revoke_executor_key(conn: &Connection, id: Uuid, device_id: Uuid) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE executor_keys SET revoked_at = ?1
+         WHERE id = ?2 AND device_id = ?3 AND revoked_at IS NULL",
+        params![chrono_iso8601(), id.to_string(), device_id.to_string()],
+    )?;
+    Ok(n > 0)
+}
+
+/// A session as listed to its admin; the refresh hash is never returned.
+#[derive(Debug, Clone)]
+pub struct SessionRow {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub device_name: Option<String>,
+    pub created_at: String,
+    pub last_used_at: String,
+    pub expires_at: String,
+}
+
+/// Open session `id` for a controller device, live until `expires_at` unless refreshed.
+pub fn create_session(
     conn: &Connection,
+    id: Uuid,
     admin_id: Uuid,
-    limit: i64,
-) -> Result<
-    Vec<(
-        Uuid,
-        Uuid,
-        String,
-        String,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        String,
-        String,
-    )>,
-> {
+    device_id: Uuid,
+    refresh_hash: &str,
+    expires_at: &str,
+) -> Result<()> {
+    let now = chrono_iso8601();
+    conn.execute(
+        "INSERT INTO sessions (id, admin_id, device_id, refresh_hash, created_at, last_used_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6)",
+        params![
+            id.to_string(),
+            admin_id.to_string(),
+            device_id.to_string(),
+            refresh_hash,
+            now,
+            expires_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Record a request made with a session's JWT on the session and its device. Returns false if
+/// the session is revoked or expired, or is not the device's, or the device is revoked: the JWT
+/// must be rejected.
+pub fn touch_session(conn: &Connection, id: Uuid, device_id: Uuid, admin_id: Uuid) -> Result<bool> {
+    let now = chrono_iso8601();
+    let n = conn.execute(
+        "UPDATE sessions SET last_used_at = ?1
+         WHERE id = ?2 AND device_id = ?3 AND admin_id = ?4
+           AND revoked_at IS NULL AND expires_at > ?1",
+        params![
+            now,
+            id.to_string(),
+            device_id.to_string(),
+            admin_id.to_string()
+        ],
+    )?;
+    Ok(n > 0 && touch_device(conn, device_id, admin_id)?)
+}
+
+/// Outcome of presenting a refresh token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionRefresh {
+    /// The token was current (or replaced within the grace period); it has been replaced by
+    /// the new one.
+    Rotated { device_id: Uuid, admin_id: Uuid },
+    /// The token was replaced before the grace period, so it leaked: the session is now revoked.
+    Reused,
+    /// No such session or token, or the session is revoked or expired.
+    Invalid,
+}
+
+/// Rotate a session's refresh token from `refresh_hash` to `new_hash` and slide its expiry to
+/// `expires_at`. The token replaced last still rotates if it was replaced at or after
+/// `grace_since` (concurrent refreshes); presented later it revokes the session.
+pub fn refresh_session(
+    conn: &Connection,
+    id: Uuid,
+    refresh_hash: &str,
+    new_hash: &str,
+    expires_at: &str,
+    grace_since: &str,
+) -> Result<SessionRefresh> {
+    let now = chrono_iso8601();
+    let row = conn.query_row(
+        "SELECT s.refresh_hash, s.previous_hash, s.rotated_at, s.device_id, s.admin_id
+         FROM sessions s
+         JOIN devices d ON d.id = s.device_id
+         WHERE s.id = ?1 AND s.revoked_at IS NULL AND s.expires_at > ?2
+           AND d.revoked_at IS NULL",
+        params![id.to_string(), now],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        },
+    );
+    let (current, previous, rotated_at, device_id, admin_id) = match row {
+        Ok(r) => r,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(SessionRefresh::Invalid),
+        Err(e) => return Err(e.into()),
+    };
+    if current == refresh_hash {
+        conn.execute(
+            "UPDATE sessions SET refresh_hash = ?1, previous_hash = refresh_hash, rotated_at = ?3,
+                                 expires_at = ?2, last_used_at = ?3
+             WHERE id = ?4",
+            params![new_hash, expires_at, now, id.to_string()],
+        )?;
+    } else if previous.as_deref() != Some(refresh_hash) {
+        return Ok(SessionRefresh::Invalid);
+    } else if rotated_at.is_some_and(|at| at.as_str() >= grace_since) {
+        // Lost a race with the refresh that replaced it; the replaced token stays previous.
+        conn.execute(
+            "UPDATE sessions SET refresh_hash = ?1, expires_at = ?2, last_used_at = ?3 WHERE id = ?4",
+            params![new_hash, expires_at, now, id.to_string()],
+        )?;
+    } else {
+        conn.execute(
+            "UPDATE sessions SET revoked_at = ?1, revoked_reason = 'reuse' WHERE id = ?2",
+            params![now, id.to_string()],
+        )?;
+        return Ok(SessionRefresh::Reused);
+    }
+    Ok(SessionRefresh::Rotated {
+        device_id: Uuid::parse_str(&device_id)?,
+        admin_id: Uuid::parse_str(&admin_id)?,
+    })
+}
+
+/// Admin's live sessions, most recently used first.
+pub fn list_sessions(conn: &Connection, admin_id: Uuid) -> Result<Vec<SessionRow>> {
     let mut stmt = conn.prepare(
-        "SELECT c.id, c.device_id, c.input, c.status, c.output, c.summary, c.repo_path, c.context_mode, c.translator_model, c.workload_model, c.cursor_chat_id, c.created_at, c.updated_at
+        "SELECT s.id, s.device_id, d.name, s.created_at, s.last_used_at, s.expires_at
+         FROM sessions s JOIN devices d ON d.id = s.device_id
+         WHERE s.admin_id = ?1 AND s.revoked_at IS NULL AND s.expires_at > ?2
+         ORDER BY s.last_used_at DESC, s.id",
+    )?;
+    let rows = stmt.query_map(params![admin_id.to_string(), chrono_iso8601()], |row| {
+        Ok(SessionRow {
+            id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
+            device_id: Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
+            device_name: row.get(2)?,
+            created_at: row.get(3)?,
+            last_used_at: row.get(4)?,
+            expires_at: row.get(5)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Revoke one of admin's sessions. Returns false if not found or already revoked.
+pub fn revoke_session(conn: &Connection, id: Uuid, admin_id: Uuid, reason: &str) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE sessions SET revoked_at = ?1, revoked_reason = ?2
+         WHERE id = ?3 AND admin_id = ?4 AND revoked_at IS NULL",
+        params![
+            chrono_iso8601(),
+            reason,
+            id.to_string(),
+            admin_id.to_string()
+        ],
+    )?;
+    Ok(n > 0)
+}
+
+/// Revoke all of admin's live sessions except `keep`. Returns the revoked session ids.
+pub fn revoke_other_sessions(
+    conn: &Connection,
+    admin_id: Uuid,
+    keep: Option<Uuid>,
+) -> Result<Vec<Uuid>> {
+    let keep = keep.map(|id| id.to_string()).unwrap_or_default();
+    let mut stmt = conn.prepare(
+        "UPDATE sessions SET revoked_at = ?1, revoked_reason = 'revoke_all'
+         WHERE admin_id = ?2 AND id != ?3 AND revoked_at IS NULL
+         RETURNING id",
+    )?;
+    let rows = stmt.query_map(
+        params![chrono_iso8601(), admin_id.to_string(), keep],
+        |row| row.get::<_, String>(0),
+    )?;
+    rows.map(|id| Ok(Uuid::parse_str(&id?)?)).collect()
+}
+
+/// Personal access token metadata; the token itself is only returned when created.
+#[derive(Debug, Clone)]
+pub struct AccessTokenRow {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+const ACCESS_TOKEN_COLUMNS: &str =
+    "id, device_id, name, prefix, scopes, created_at, expires_at, last_used_at, revoked_at";
+
+fn access_token_from_row(row: &rusqlite::Row) -> rusqlite::Result<AccessTokenRow> {
+    Ok(AccessTokenRow {
+        id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
+        device_id: Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
+        name: row.get(2)?,
+        prefix: row.get(3)?,
+        scopes: parse_scopes(&row.get::<_, String>(4)?),
+        created_at: row.get(5)?,
+        expires_at: row.get(6)?,
+        last_used_at: row.get(7)?,
+        revoked_at: row.get(8)?,
+    })
+}
+
+/// Stored scopes are space-separated; unknown ones (from a newer relayer) are dropped.
+fn parse_scopes(s: &str) -> Vec<TokenScope> {
+    s.split_whitespace().filter_map(TokenScope::parse).collect()
+}
+
+/// New personal access token for `insert_access_token`.
+pub struct NewAccessToken<'a> {
+    pub admin_id: Uuid,
+    pub device_id: Uuid,
+    pub name: &'a str,
+    pub token_hash: &'a str,
+    pub prefix: &'a str,
+    pub scopes: &'a [TokenScope],
+    pub expires_at: Option<&'a str>,
+}
+
+/// Store a personal access token. Returns it as listed.
+pub fn insert_access_token(conn: &Connection, t: &NewAccessToken) -> Result<AccessTokenRow> {
+    let id = Uuid::new_v4();
+    let scopes: Vec<&str> = t.scopes.iter().map(TokenScope::as_str).collect();
+    conn.execute(
+        "INSERT INTO access_tokens (id, admin_id, device_id, name, token_hash, prefix, scopes, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            id.to_string(),
+            t.admin_id.to_string(),
+            t.device_id.to_string(),
+            t.name,
+            t.token_hash,
+            t.prefix,
+            scopes.join(" "),
+            chrono_iso8601(),
+            t.expires_at
+        ],
+    )?;
+    Ok(conn.query_row(
+        &format!(
+            "SELECT {} FROM access_tokens WHERE id = ?1",
+            ACCESS_TOKEN_COLUMNS
+        ),
+        [id.to_string()],
+        access_token_from_row,
+    )?)
+}
+
+/// An authenticated personal access token.
+#[derive(Debug, Clone)]
+pub struct AccessTokenAuth {
+    pub token_id: Uuid,
+    pub device_id: Uuid,
+    pub admin_id: Uuid,
+    pub scopes: Vec<TokenScope>,
+}
+
+/// Resolve a personal access token by hash if it is live and its device is not revoked,
+/// recording the use.
+pub fn authenticate_access_token(
+    conn: &Connection,
+    token_hash: &str,
+) -> Result<Option<AccessTokenAuth>> {
+    let now = chrono_iso8601();
+    let row = conn.query_row(
+        "SELECT t.id, t.device_id, t.admin_id, t.scopes
+         FROM access_tokens t JOIN devices d ON d.id = t.device_id
+         WHERE t.token_hash = ?1 AND t.revoked_at IS NULL
+           AND (t.expires_at IS NULL OR t.expires_at > ?2)
+           AND d.revoked_at IS NULL",
+        params![token_hash, now],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        },
+    );
+    let (token_id, device_id, admin_id, scopes) = match row {
+        Ok(r) => r,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    conn.execute(
+        "UPDATE access_tokens SET last_used_at = ?1 WHERE id = ?2",
+        params![now, token_id],
+    )?;
+    Ok(Some(AccessTokenAuth {
+        token_id: Uuid::parse_str(&token_id)?,
+        device_id: Uuid::parse_str(&device_id)?,
+        admin_id: Uuid::parse_str(&admin_id)?,
+        scopes: parse_scopes(&scopes),
+    }))
+}
+
+/// Admin's personal access tokens, including expired and revoked ones, newest first.
+pub fn list_access_tokens(conn: &Connection, admin_id: Uuid) -> Result<Vec<AccessTokenRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM access_tokens WHERE admin_id = ?1 ORDER BY created_at DESC, id",
+        ACCESS_TOKEN_COLUMNS
+    ))?;
+    let rows = stmt.query_map([admin_id.to_string()], access_token_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Revoke one of admin's personal access tokens now. Returns false if not found or already
+/// revoked.
+pub fn revoke_access_token(conn: &Connection, id: Uuid, admin_id: Uuid) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE access_tokens SET revoked_at = ?1
+         WHERE id = ?2 AND admin_id = ?3 AND revoked_at IS NULL",
+        params![chrono_iso8601(), id.to_string(), admin_id.to_string()],
+    )?;
+    Ok(n > 0)
+}
+
+/// Store a WebAuthn challenge; `device_id` binds a register challenge to the device asking.
+/// Expired challenges are purged.
+pub fn create_webauthn_challenge(
+    conn: &Connection,
+    challenge: &str,
+    purpose: &str,
+    device_id: Option<Uuid>,
+    expires_at: &str,
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM webauthn_challenges WHERE expires_at <= ?1",
+        [chrono_iso8601()],
+    )?;
+    conn.execute(
+        "INSERT INTO webauthn_challenges (challenge, purpose, device_id, expires_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            challenge,
+            purpose,
+            device_id.map(|id| id.to_string()),
+            expires_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Use up a live WebAuthn challenge. Returns false if it was not issued for `purpose` and
+/// `device_id`, has expired or was already used.
+pub fn take_webauthn_challenge(
+    conn: &Connection,
+    challenge: &str,
+    purpose: &str,
+    device_id: Option<Uuid>,
+) -> Result<bool> {
+    let n = conn.execute(
+        "DELETE FROM webauthn_challenges
+         WHERE challenge = ?1 AND purpose = ?2 AND device_id IS ?3 AND expires_at > ?4",
+        params![
+            challenge,
+            purpose,
+            device_id.map(|id| id.to_string()),
+            chrono_iso8601()
+        ],
+    )?;
+    Ok(n > 0)
+}
+
+/// A passkey as listed to its device; the public key is never returned.
+#[derive(Debug, Clone)]
+pub struct WebAuthnCredentialRow {
+    pub id: Uuid,
+    pub credential_id: String,
+    pub name: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+/// Store a passkey for a controller device. Returns its id.
+pub fn insert_webauthn_credential(
+    conn: &Connection,
+    device_id: Uuid,
+    credential_id: &str,
+    public_key: &[u8],
+    sign_count: u32,
+    name: Option<&str>,
+) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    conn.execute(
+        "INSERT INTO webauthn_credentials (id, device_id, credential_id, public_key, sign_count, name, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id.to_string(),
+            device_id.to_string(),
+            credential_id,
+            public_key,
+            sign_count,
+            name,
+            chrono_iso8601()
+        ],
+    )?;
+    Ok(id)
+}
+
+/// Passkeys of a device, oldest first.
+pub fn list_webauthn_credentials(
+    conn: &Connection,
+    device_id: Uuid,
+) -> Result<Vec<WebAuthnCredentialRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, credential_id, name, created_at, last_used_at FROM webauthn_credentials
+         WHERE device_id = ?1 ORDER BY created_at, id",
+    )?;
+    let rows = stmt.query_map([device_id.to_string()], |row| {
+        Ok(WebAuthnCredentialRow {
+            id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
+            credential_id: row.get(1)?,
+            name: row.get(2)?,
+            created_at: row.get(3)?,
+            last_used_at: row.get(4)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Delete one of a device's passkeys. Returns false if not found.
+pub fn delete_webauthn_credential(conn: &Connection, id: Uuid, device_id: Uuid) -> Result<bool> {
+    let n = conn.execute(
+        "DELETE FROM webauthn_credentials WHERE id = ?1 AND device_id = ?2",
+        params![id.to_string(), device_id.to_string()],
+    )?;
+    Ok(n > 0)
+}
+
+/// What login needs of a passkey: its key, counter and the device it belongs to.
+#[derive(Debug, Clone)]
+pub struct WebAuthnLogin {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub admin_id: Uuid,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+/// Look up a passkey by its credential id. None if unknown or its device is revoked.
+pub fn find_webauthn_credential(
+    conn: &Connection,
+    credential_id: &str,
+) -> Result<Option<WebAuthnLogin>> {
+    let row = conn.query_row(
+        "SELECT w.id, w.device_id, d.admin_id, w.public_key, w.sign_count
+         FROM webauthn_credentials w JOIN devices d ON d.id = w.device_id
+         WHERE w.credential_id = ?1 AND d.revoked_at IS NULL AND d.role = 'controller'",
+        [credential_id],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+                row.get::<_, u32>(4)?,
+            ))
+        },
+    );
+    match row {
+        Ok((id, device_id, admin_id, public_key, sign_count)) => Ok(Some(WebAuthnLogin {
+            id: Uuid::parse_str(&id)?,
+            device_id: Uuid::parse_str(&device_id)?,
+            admin_id: Uuid::parse_str(&admin_id)?,
+            public_key,
+            sign_count,
+        })),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Record a login with a passkey and its new signature counter.
+pub fn record_webauthn_use(conn: &Connection, id: Uuid, sign_count: u32) -> Result<()> {
+    conn.execute(
+        "UPDATE webauthn_credentials SET sign_count = ?1, last_used_at = ?2 WHERE id = ?3",
+        params![sign_count, chrono_iso8601(), id.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Replace an admin's password hash. Returns false if there is no such admin.
+pub fn set_admin_password_hash(conn: &Connection, username: &str, hash: &str) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE admin SET password_hash = ?1, updated_at = ?2 WHERE username = ?3",
+        params![hash, chrono_iso8601(), username],
+    )?;
+    Ok(n > 0)
+}
+
+/// Replace an admin's TOTP secret. Returns false if there is no such admin.
+pub fn set_admin_totp_secret(conn: &Connection, username: &str, secret: &str) -> Result<bool> {
+    let n = conn.execute(
+        "UPDATE admin SET totp_secret = ?1, updated_at = ?2 WHERE username = ?3",
+        params![secret, chrono_iso8601(), username],
+    )?;
+    Ok(n > 0)
+}
+
+/// Delete registration codes that are used or expired. Returns how many were deleted.
+pub fn clear_expired_codes(conn: &Connection) -> Result<usize> {
+    Ok(conn.execute(
+        "DELETE FROM device_registration_codes WHERE used = 1 OR expires_at < ?1",
+        [chrono_iso8601()],
+    )?)
+}
+
+/// Command row as stored in the `commands` table.
+#[derive(Debug, Clone)]
+pub struct CommandRow {
+    pub id: Uuid,
+    pub device_id: Uuid,
+    pub input: String,
+    pub status: String,
+    pub output: Option<String>,
+    pub summary: Option<String>,
+    pub repo_path: Option<String>,
+    pub context_mode: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+    pub cursor_chat_id: Option<String>,
+    pub queue_position: Option<i64>,
+    pub max_runtime_secs: Option<i64>,
+    pub failure_reason: Option<String>,
+    pub chat_id: Option<Uuid>,
+    pub duration_ms: Option<i64>,
+    pub review_prompt: bool,
+    pub proposed_prompt: Option<String>,
+    pub approved_prompt: Option<String>,
+    pub schedule_id: Option<Uuid>,
+    pub rerun_of: Option<Uuid>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Columns selected for a `CommandRow`, in `command_from_row` order. Prefix with `c.` via
+/// `COMMAND_COLUMNS_C` when joining.
+const COMMAND_COLUMNS: &str = "id, device_id, input, status, output, summary, repo_path, context_mode, translator_model, workload_model, cursor_chat_id, queue_position, max_runtime_secs, failure_reason, chat_id, duration_ms, review_prompt, proposed_prompt, approved_prompt, schedule_id, rerun_of, created_at, updated_at";
+const COMMAND_COLUMNS_C: &str = "c.id, c.device_id, c.input, c.status, c.output, c.summary, c.repo_path, c.context_mode, c.translator_model, c.workload_model, c.cursor_chat_id, c.queue_position, c.max_runtime_secs, c.failure_reason, c.chat_id, c.duration_ms, c.review_prompt, c.proposed_prompt, c.approved_prompt, c.schedule_id, c.rerun_of, c.created_at, c.updated_at";
+
+fn command_from_row(row: &rusqlite::Row) -> rusqlite::Result<CommandRow> {
+    Ok(CommandRow {
+        id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
+        device_id: Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
+        input: row.get(2)?,
+        status: row.get(3)?,
+        output: row.get(4)?,
+        summary: row.get(5)?,
+        repo_path: row.get(6)?,
+        context_mode: row.get(7)?,
+        translator_model: row.get(8)?,
+        workload_model: row.get(9)?,
+        cursor_chat_id: row.get(10)?,
+        queue_position: row.get(11)?,
+        max_runtime_secs: row.get(12)?,
+        failure_reason: row.get(13)?,
+        chat_id: row
+            .get::<_, Option<String>>(14)?
+            .and_then(|s| Uuid::parse_str(&s).ok()),
+        duration_ms: row.get(15)?,
+        review_prompt: row.get(16)?,
+        proposed_prompt: row.get(17)?,
+        approved_prompt: row.get(18)?,
+        schedule_id: row
+            .get::<_, Option<String>>(19)?
+            .and_then(|s| Uuid::parse_str(&s).ok()),
+        rerun_of: row
+            .get::<_, Option<String>>(20)?
+            .and_then(|s| Uuid::parse_str(&s).ok()),
+        created_at: row.get(21)?,
+        updated_at: row.get(22)?,
+    })
+}
+
+/// Output of the command `{table}.id` rebuilt from its event log: the last `replace` event and
+/// the `append` events after it, or `{table}.output` if it has no events.
+fn folded_output(table: &str) -> String {
+    format!(
+        "COALESCE(
+    (SELECT group_concat(e.content, '' ORDER BY e.seq) FROM command_output_events e
+     WHERE e.command_id = {table}.id
+       AND e.seq >= COALESCE((SELECT MAX(r.seq) FROM command_output_events r
+                              WHERE r.command_id = {table}.id AND r.kind = 'replace'), 0)),
+    {table}.output)"
+    )
+}
+
+/// Output of the command `{table}.id` as clients see it: folded from its event log while
+/// unfinished, since `commands.output` is only brought up to date when the command finishes.
+/// Selected after the `CommandRow` columns for `live_command_from_row`.
+fn live_output(table: &str) -> String {
+    format!(
+        "CASE WHEN {table}.status IN ('done', 'failed', 'cancelled') THEN {table}.output
+         ELSE {} END",
+        folded_output(table)
+    )
+}
+
+/// Index of the `live_output` column, right after the `CommandRow` columns.
+const LIVE_OUTPUT_IDX: usize = 23;
+
+/// `command_from_row` for rows with a trailing `live_output` column.
+fn live_command_from_row(row: &rusqlite::Row) -> rusqlite::Result<CommandRow> {
+    Ok(CommandRow {
+        output: row.get(LIVE_OUTPUT_IDX)?,
+        ..command_from_row(row)?
+    })
+}
+
+/// Fields for a new command.
+#[derive(Debug, Clone, Default)]
+pub struct NewCommand<'a> {
+    pub input: &'a str,
+    pub repo_path: Option<&'a str>,
+    pub context_mode: Option<&'a str>,
+    pub translator_model: Option<&'a str>,
+    pub workload_model: Option<&'a str>,
+    pub cursor_chat_id: Option<&'a str>,
+    pub max_runtime_secs: Option<i64>,
+    pub chat_id: Option<Uuid>,
+    pub review_prompt: bool,
+    /// Workflow run and step index this command runs.
+    pub workflow_step: Option<(Uuid, i64)>,
+    /// Schedule that fired this command.
+    pub schedule_id: Option<Uuid>,
+    /// Command this one re-runs.
+    pub rerun_of: Option<Uuid>,
+    /// Client idempotency key, unique per device.
+    pub idempotency_key: Option<&'a str>,
+}
+
+/// Create a new command.
+pub fn create_command(conn: &Connection, device_id: Uuid, cmd: &NewCommand) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    let now = chrono_iso8601();
+    if let Some(key) = cmd.idempotency_key {
+        // Callers look up live keys first, so a command still holding this one has outlived
+        // the retention window.
+        conn.execute(
+            "UPDATE commands SET idempotency_key = NULL WHERE device_id = ?1 AND idempotency_key = ?2",
+            params![device_id.to_string(), key],
+        )?;
+    }
+    conn.execute(
+        "INSERT INTO commands (id, device_id, input, status, output, summary, repo_path, context_mode, translator_model, workload_model, cursor_chat_id, max_runtime_secs, chat_id, review_prompt, workflow_run_id, workflow_step, schedule_id, rerun_of, idempotency_key, created_at, updated_at)
+         VALUES (?1, ?2, ?3, 'pending', NULL, NULL, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?17)",
+        params![
+            id.to_string(),
+            device_id.to_string(),
+            cmd.input,
+            cmd.repo_path,
+            cmd.context_mode,
+            cmd.translator_model,
+            cmd.workload_model,
+            cmd.cursor_chat_id,
+            cmd.max_runtime_secs,
+            cmd.chat_id.map(|id| id.to_string()),
+            cmd.review_prompt,
+            cmd.workflow_step.map(|(run_id, _)| run_id.to_string()),
+            cmd.workflow_step.map(|(_, step)| step),
+            cmd.schedule_id.map(|id| id.to_string()),
+            cmd.rerun_of.map(|id| id.to_string()),
+            cmd.idempotency_key,
+            now,
+        ],
+    )?;
+    let actor = if cmd.schedule_id.is_some() || cmd.workflow_step.is_some() {
+        status_actors::RELAYER
+    } else {
+        status_actors::CONTROLLER
+    };
+    record_status_change(conn, id, None, CommandStatus::Pending, actor, None)?;
+    Ok(id)
+}
+
+/// Append a status change to a command's history.
+fn record_status_change(
+    conn: &Connection,
+    id: Uuid,
+    from: Option<CommandStatus>,
+    to: CommandStatus,
+    actor: &str,
+    revision: Option<i64>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO command_status_history (command_id, from_status, to_status, actor, revision, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            id.to_string(),
+            from.map(|s| s.as_str()),
+            to.as_str(),
+            actor,
+            revision,
+            chrono_iso8601()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Status changes of a command, oldest first.
+pub fn list_command_status_history(
+    conn: &Connection,
+    id: Uuid,
+) -> Result<Vec<CommandStatusChange>> {
+    let mut stmt = conn.prepare(
+        "SELECT from_status, to_status, actor, revision, created_at
+         FROM command_status_history WHERE command_id = ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map([id.to_string()], |row| {
+        Ok(CommandStatusChange {
+            from: row
+                .get::<_, Option<String>>(0)?
+                .and_then(|s| CommandStatus::parse(&s)),
+            to: CommandStatus::parse(&row.get::<_, String>(1)?).unwrap_or(CommandStatus::Pending),
+            actor: row.get(2)?,
+            revision: row
+                .get::<_, Option<i64>>(3)?
+                .and_then(|r| u64::try_from(r).ok()),
+            at: row.get(4)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Get command by id.
+pub fn get_command(conn: &Connection, id: Uuid) -> Result<Option<CommandRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {} FROM commands WHERE id = ?1",
+        COMMAND_COLUMNS,
+        live_output("commands")
+    ))?;
+    match stmt.query_row([id.to_string()], live_command_from_row) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Command `device_id` created with idempotency key `key` at or after `since` (ISO8601).
+pub fn find_command_by_idempotency_key(
+    conn: &Connection,
+    device_id: Uuid,
+    key: &str,
+    since: &str,
+) -> Result<Option<CommandRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {} FROM commands
+         WHERE device_id = ?1 AND idempotency_key = ?2 AND created_at >= ?3",
+        COMMAND_COLUMNS,
+        live_output("commands")
+    ))?;
+    match stmt.query_row(
+        params![device_id.to_string(), key, since],
+        live_command_from_row,
+    ) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Get command by id if it belongs to admin (any user's with None).
+pub fn get_command_for_admin(
+    conn: &Connection,
+    id: Uuid,
+    admin_id: Option<Uuid>,
+) -> Result<Option<CommandRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {} FROM commands c
+         JOIN devices d ON c.device_id = d.id
+         WHERE c.id = ?1 AND (?2 IS NULL OR d.admin_id = ?2)",
+        COMMAND_COLUMNS_C,
+        live_output("c")
+    ))?;
+    match stmt.query_row(
+        params![id.to_string(), admin_id.map(|id| id.to_string())],
+        live_command_from_row,
+    ) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The user whose device created a command.
+pub fn command_owner(conn: &Connection, id: Uuid) -> Result<Option<Uuid>> {
+    match conn.query_row(
+        "SELECT d.admin_id FROM commands c JOIN devices d ON c.device_id = d.id WHERE c.id = ?1",
+        [id.to_string()],
+        |row| row.get::<_, String>(0),
+    ) {
+        Ok(id) => Ok(Some(Uuid::parse_str(&id)?)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Filters for `list_commands`. `None` fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct CommandFilter<'a> {
+    pub status: Option<&'a str>,
+    pub repo_path: Option<&'a str>,
+    pub context_mode: Option<&'a str>,
+    /// Matches the translator or the workload model.
+    pub model: Option<&'a str>,
+    pub cursor_chat_id: Option<&'a str>,
+    pub schedule_id: Option<&'a str>,
+    /// Inclusive lower bound on `created_at` (ISO8601).
+    pub created_since: Option<&'a str>,
+    /// Exclusive upper bound on `created_at` (ISO8601).
+    pub created_until: Option<&'a str>,
+    /// Only commands that ran a shell command containing this text.
+    pub ran: Option<&'a str>,
+    /// Only commands that wrote a file whose path contains this text.
+    pub wrote: Option<&'a str>,
+    /// Page position: only commands after `(created_at, id)` in newest-first order.
+    pub after: Option<(&'a str, &'a str)>,
+}
+
+/// List admin's commands (every user's with None) matching `filter`, newest first (ties
+/// broken by id).
+pub fn list_commands(
+    conn: &Connection,
+    admin_id: Option<Uuid>,
+    filter: &CommandFilter,
+    limit: i64,
+) -> Result<Vec<CommandRow>> {
+    let admin_id = admin_id.map(|id| id.to_string());
+    let admin_id = admin_id.as_deref();
+    let mut clauses = vec!["1 = 1"];
+    let mut args: Vec<&dyn rusqlite::ToSql> = Vec::new();
+    for (clause, value) in [
+        ("d.admin_id = ?", &admin_id),
+        ("c.status = ?", &filter.status),
+        ("c.repo_path = ?", &filter.repo_path),
+        ("c.context_mode = ?", &filter.context_mode),
+        ("c.cursor_chat_id = ?", &filter.cursor_chat_id),
+        ("c.schedule_id = ?", &filter.schedule_id),
+        ("c.created_at >= ?", &filter.created_since),
+        ("c.created_at < ?", &filter.created_until),
+        (
+            "EXISTS (SELECT 1 FROM command_tool_calls t
+                     WHERE t.command_id = c.id AND t.kind = 'shell' AND instr(t.target, ?) > 0)",
+            &filter.ran,
+        ),
+        (
+            "EXISTS (SELECT 1 FROM command_tool_calls t
+                     WHERE t.command_id = c.id AND t.kind = 'write' AND instr(t.target, ?) > 0)",
+            &filter.wrote,
+        ),
+    ] {
+        if let Some(v) = value {
+            clauses.push(clause);
+            args.push(v);
+        }
+    }
+    if let Some(model) = &filter.model {
+        clauses.push("(c.translator_model = ? OR c.workload_model = ?)");
+        args.extend([model as &dyn rusqlite::ToSql, model]);
+    }
+    if let Some((created_at, id)) = &filter.after {
+        clauses.push("(c.created_at < ? OR (c.created_at = ? AND c.id < ?))");
+        args.extend([created_at as &dyn rusqlite::ToSql, created_at, id]);
+    }
+    args.push(&limit);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {}
          FROM commands c
          JOIN devices d ON c.device_id = d.id
-         WHERE d.admin_id = ?1
-         ORDER BY c.created_at DESC
-         LIMIT ?2",
+         WHERE {}
+         ORDER BY c.created_at DESC, c.id DESC
+         LIMIT ?",
+        COMMAND_COLUMNS_C,
+        live_output("c"),
+        clauses.join(" AND ")
+    ))?;
+    let rows = stmt.query_map(args.as_slice(), live_command_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// A full-text search hit: the command, a snippet around the best-matching text (matches
+/// wrapped in `<mark>`…`</mark>`), and its bm25 rank (lower is better).
+#[derive(Debug, Clone)]
+pub struct CommandSearchHit {
+    pub command: CommandRow,
+    pub snippet: String,
+    pub rank: f64,
+}
+
+/// Turn free text into an FTS5 query: every whitespace-separated term must match (quoted, so
+/// FTS5 operators in user input are literal); a trailing `*` keeps prefix matching.
+fn fts_query(q: &str) -> String {
+    q.split_whitespace()
+        .filter_map(|term| {
+            let (term, prefix) = match term.strip_suffix('*') {
+                Some(t) => (t, "*"),
+                None => (term, ""),
+            };
+            (!term.is_empty()).then(|| format!("\"{}\"{}", term.replace('"', "\"\""), prefix))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Search admin's commands (every user's with None) by input, output and summary, best match
+/// first. Output and summary are searchable once a command finishes.
+pub fn search_commands(
+    conn: &Connection,
+    admin_id: Option<Uuid>,
+    q: &str,
+    limit: i64,
+) -> Result<Vec<CommandSearchHit>> {
+    let query = fts_query(q);
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {}, snippet(commands_fts, -1, '<mark>', '</mark>', '…', 16), bm25(commands_fts) AS rank
+         FROM commands_fts
+         JOIN commands c ON c.id = commands_fts.command_id
+         JOIN devices d ON c.device_id = d.id
+         WHERE commands_fts MATCH ?1 AND (?2 IS NULL OR d.admin_id = ?2)
+         ORDER BY rank
+         LIMIT ?3",
+        COMMAND_COLUMNS_C,
+        live_output("c")
+    ))?;
+    // snippet and rank follow the live output.
+    let snippet_idx = LIVE_OUTPUT_IDX + 1;
+    let rows = stmt.query_map(
+        params![query, admin_id.map(|id| id.to_string()), limit],
+        |row| {
+            Ok(CommandSearchHit {
+                command: live_command_from_row(row)?,
+                snippet: row
+                    .get::<_, Option<String>>(snippet_idx)?
+                    .unwrap_or_default(),
+                rank: row.get(snippet_idx + 1)?,
+            })
+        },
+    )?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Delete command by id if it belongs to admin (any user's with None). Returns true if
+/// deleted.
+pub fn delete_command(conn: &Connection, id: Uuid, admin_id: Option<Uuid>) -> Result<bool> {
+    let rows = conn.execute(
+        "DELETE FROM commands WHERE id = ?1
+           AND (?2 IS NULL OR device_id IN (SELECT id FROM devices WHERE admin_id = ?2))",
+        params![id.to_string(), admin_id.map(|id| id.to_string())],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Fields to update on a command. `None` leaves the column unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct CommandUpdate<'a> {
+    pub status: Option<CommandStatus>,
+    pub output: Option<&'a str>,
+    pub summary: Option<&'a str>,
+    pub cursor_chat_id: Option<&'a str>,
+    /// Only kept while status is `queued`; any other status clears it.
+    pub queue_position: Option<i64>,
+    /// Only kept while status is `failed`; any other status clears it.
+    pub failure_reason: Option<&'a str>,
+    pub proposed_prompt: Option<&'a str>,
+    /// Executor revision of this update; must exceed the last applied one.
+    pub revision: Option<i64>,
+}
+
+/// Result of `update_command`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandUpdateOutcome {
+    Applied,
+    NotFound,
+    /// The update's revision is not newer than the last applied one.
+    Stale,
+    /// The command cannot move from its current status to the requested one (see
+    /// `CommandStatus::can_transition_to`); updates without a status count as staying put.
+    InvalidTransition {
+        from: CommandStatus,
+        to: CommandStatus,
+    },
+}
+
+/// Update command status, output, summary, cursor_chat_id, queue_position, failure_reason,
+/// proposed_prompt. Stale revisions and invalid transitions change nothing; a status change
+/// is recorded in the history as made by `actor` (see `status_actors`).
+pub fn update_command(
+    conn: &Connection,
+    id: Uuid,
+    update: &CommandUpdate,
+    actor: &str,
+) -> Result<CommandUpdateOutcome> {
+    let current = conn.query_row(
+        &format!(
+            "SELECT status, revision, {} FROM commands WHERE id = ?1",
+            folded_output("commands")
+        ),
+        [id.to_string()],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        },
+    );
+    let (from, last_revision, live_output) = match current {
+        Ok(c) => c,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(CommandUpdateOutcome::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+    if let (Some(revision), Some(last)) = (update.revision, last_revision) {
+        if revision <= last {
+            return Ok(CommandUpdateOutcome::Stale);
+        }
+    }
+    let from = CommandStatus::parse(&from).ok_or_else(|| anyhow!("unknown status: {}", from))?;
+    let to = update.status.unwrap_or(from);
+    if !from.can_transition_to(to) {
+        return Ok(CommandUpdateOutcome::InvalidTransition { from, to });
+    }
+    let now = chrono_iso8601();
+    let output = match update.output {
+        Some(output) => {
+            // Full output from the executor is logged as a `replace` event so the event log
+            // and `commands.output` never disagree. Unchanged output is not logged again.
+            if live_output.as_deref() != Some(output) {
+                conn.execute(
+                    "INSERT INTO command_output_events (command_id, seq, kind, content, created_at)
+                     SELECT ?1,
+                            COALESCE((SELECT MAX(seq) FROM command_output_events WHERE command_id = ?1), 0) + 1,
+                            'replace', ?2, ?3",
+                    params![id.to_string(), output, now],
+                )?;
+            }
+            Some(output.to_string())
+        }
+        // Streamed output is folded into `commands.output` once, when the command finishes,
+        // so the search index is rebuilt once per command rather than per event.
+        None if to.is_terminal() => live_output,
+        None => None,
+    };
+    conn.execute(
+        "UPDATE commands SET
+           status = ?1,
+           output = COALESCE(?2, output),
+           summary = COALESCE(?3, summary),
+           cursor_chat_id = COALESCE(?4, cursor_chat_id),
+           queue_position = CASE WHEN ?1 = 'queued' THEN COALESCE(?5, queue_position) ELSE NULL END,
+           failure_reason = CASE WHEN ?1 = 'failed' THEN COALESCE(?6, failure_reason) ELSE NULL END,
+           proposed_prompt = COALESCE(?7, proposed_prompt),
+           revision = COALESCE(?8, revision),
+           updated_at = ?9
+         WHERE id = ?10",
+        params![
+            to.as_str(),
+            output,
+            update.summary,
+            update.cursor_chat_id,
+            update.queue_position,
+            update.failure_reason,
+            update.proposed_prompt,
+            update.revision,
+            now,
+            id.to_string()
+        ],
+    )?;
+    if to != from {
+        record_status_change(conn, id, Some(from), to, actor, update.revision)?;
+    }
+    Ok(CommandUpdateOutcome::Applied)
+}
+
+/// Approve the proposed prompt of an `awaiting_approval` command: it goes back to `pending`
+/// (unacked, so it is replayed to the executor) and runs with `prompt`. Returns false if the
+/// command is not awaiting approval.
+pub fn approve_command_prompt(conn: &Connection, id: Uuid, prompt: &str) -> Result<bool> {
+    let rows = conn.execute(
+        "UPDATE commands SET status = 'pending', approved_prompt = ?1, acked_at = NULL,
+                             updated_at = ?2
+         WHERE id = ?3 AND status = 'awaiting_approval'",
+        params![prompt, chrono_iso8601(), id.to_string()],
+    )?;
+    if rows > 0 {
+        record_status_change(
+            conn,
+            id,
+            Some(CommandStatus::AwaitingApproval),
+            CommandStatus::Pending,
+            status_actors::CONTROLLER,
+            None,
+        )?;
+    }
+    Ok(rows > 0)
+}
+
+/// Reject the proposed prompt of an `awaiting_approval` command, cancelling it. Returns false
+/// if the command is not awaiting approval.
+pub fn reject_command_prompt(conn: &Connection, id: Uuid) -> Result<bool> {
+    let rows = conn.execute(
+        &format!(
+            "UPDATE commands SET status = 'cancelled', output = {}, updated_at = ?1
+             WHERE id = ?2 AND status = 'awaiting_approval'",
+            folded_output("commands")
+        ),
+        params![chrono_iso8601(), id.to_string()],
+    )?;
+    if rows > 0 {
+        record_status_change(
+            conn,
+            id,
+            Some(CommandStatus::AwaitingApproval),
+            CommandStatus::Cancelled,
+            status_actors::CONTROLLER,
+            None,
+        )?;
+    }
+    Ok(rows > 0)
+}
+
+/// Highest output event seq stored for a command (0 when it has none).
+pub fn last_output_seq(conn: &Connection, command_id: Uuid) -> Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(seq), 0) FROM command_output_events WHERE command_id = ?1",
+        [command_id.to_string()],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+/// Output event row as stored in `command_output_events`.
+#[derive(Debug, Clone)]
+pub struct OutputEventRow {
+    pub seq: i64,
+    pub kind: String,
+    pub content: String,
+    pub created_at: String,
+}
+
+/// Append an output event. Events at or below the last stored `seq` (retries, late
+/// deliveries) and events for finished commands are ignored. The events are folded into
+/// `commands.output` when the command finishes (see `update_command`). Returns whether the
+/// event was applied.
+pub fn append_output_event(
+    conn: &Connection,
+    command_id: Uuid,
+    seq: i64,
+    kind: &str,
+    content: &str,
+) -> Result<bool> {
+    let inserted = conn.execute(
+        "INSERT INTO command_output_events (command_id, seq, kind, content, created_at)
+         SELECT id, ?2, ?3, ?4, ?5 FROM commands
+         WHERE id = ?1
+           AND status NOT IN ('done', 'failed', 'cancelled')
+           AND ?2 > COALESCE((SELECT MAX(seq) FROM command_output_events WHERE command_id = ?1), 0)",
+        params![command_id.to_string(), seq, kind, content, chrono_iso8601()],
+    )?;
+    Ok(inserted > 0)
+}
+
+/// Output events of a command with `seq` greater than `after`, in order.
+pub fn list_output_events(
+    conn: &Connection,
+    command_id: Uuid,
+    after: i64,
+    limit: usize,
+) -> Result<Vec<OutputEventRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT seq, kind, content, created_at FROM command_output_events
+         WHERE command_id = ?1 AND seq > ?2
+         ORDER BY seq ASC LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(
+        params![command_id.to_string(), after, limit as i64],
+        |row| {
+            Ok(OutputEventRow {
+                seq: row.get(0)?,
+                kind: row.get(1)?,
+                content: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        },
+    )?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Insert or update a tool call of a command, keyed by `call_id`. Fields already recorded
+/// are kept when a later report leaves them unset, so start and completion may arrive in
+/// either order. Returns false if the command does not exist.
+pub fn upsert_tool_call(
+    conn: &Connection,
+    command_id: Uuid,
+    call: &ToolCallRecord,
+) -> Result<bool> {
+    let args = call.args.as_ref().map(|a| a.to_string());
+    let rows = conn.execute(
+        "INSERT INTO command_tool_calls
+           (command_id, call_id, seq, tool, kind, target, args, started_at, completed_at,
+            duration_ms, exit_code, error, result)
+         SELECT id, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13 FROM commands WHERE id = ?1
+         ON CONFLICT (command_id, call_id) DO UPDATE SET
+           target = COALESCE(excluded.target, target),
+           args = COALESCE(excluded.args, args),
+           started_at = MIN(excluded.started_at, started_at),
+           completed_at = COALESCE(excluded.completed_at, completed_at),
+           duration_ms = COALESCE(excluded.duration_ms, duration_ms),
+           exit_code = COALESCE(excluded.exit_code, exit_code),
+           error = COALESCE(excluded.error, error),
+           result = COALESCE(excluded.result, result)",
+        params![
+            command_id.to_string(),
+            call.call_id,
+            call.seq,
+            call.tool,
+            call.kind,
+            call.target,
+            args,
+            call.started_at,
+            call.completed_at,
+            call.duration_ms.map(|d| d as i64),
+            call.exit_code,
+            call.error,
+            call.result,
+        ],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Tool calls of a command in the order they started.
+pub fn list_tool_calls(conn: &Connection, command_id: Uuid) -> Result<Vec<ToolCallRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT call_id, seq, tool, kind, target, args, started_at, completed_at, duration_ms,
+                exit_code, error, result
+         FROM command_tool_calls WHERE command_id = ?1
+         ORDER BY seq ASC",
+    )?;
+    let rows = stmt.query_map([command_id.to_string()], |row| {
+        Ok(ToolCallRecord {
+            call_id: row.get(0)?,
+            seq: row.get(1)?,
+            tool: row.get(2)?,
+            kind: row.get(3)?,
+            target: row.get(4)?,
+            args: row
+                .get::<_, Option<String>>(5)?
+                .and_then(|a| serde_json::from_str(&a).ok()),
+            started_at: row.get(6)?,
+            completed_at: row.get(7)?,
+            duration_ms: row.get::<_, Option<i64>>(8)?.map(|d| d as u64),
+            exit_code: row.get(9)?,
+            error: row.get(10)?,
+            result: row.get(11)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Record phases of a command, replacing earlier reports of the same phases (a reviewed
+/// command reports `translate` before approval and the rest after), and set its total
+/// `duration_ms`.
+pub fn set_command_phases(
+    conn: &Connection,
+    command_id: Uuid,
+    phases: &[CommandPhase],
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    for p in phases {
+        tx.execute(
+            "INSERT OR REPLACE INTO command_phases
+               (command_id, phase, model, duration_ms, input_tokens, output_tokens,
+                cache_read_tokens, cache_write_tokens, cost_usd, outcome)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                command_id.to_string(),
+                p.phase,
+                p.model,
+                p.duration_ms as i64,
+                p.input_tokens.map(|t| t as i64),
+                p.output_tokens.map(|t| t as i64),
+                p.cache_read_tokens.map(|t| t as i64),
+                p.cache_write_tokens.map(|t| t as i64),
+                p.cost_usd,
+                p.outcome,
+            ],
+        )?;
+    }
+    tx.execute(
+        "UPDATE commands SET duration_ms =
+           (SELECT SUM(duration_ms) FROM command_phases WHERE command_id = ?1)
+         WHERE id = ?1",
+        [command_id.to_string()],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Recorded phases of a command, in pipeline order.
+pub fn list_command_phases(conn: &Connection, command_id: Uuid) -> Result<Vec<CommandPhase>> {
+    let mut stmt = conn.prepare(
+        "SELECT phase, model, duration_ms, input_tokens, output_tokens, cache_read_tokens,
+                cache_write_tokens, cost_usd, outcome
+         FROM command_phases WHERE command_id = ?1
+         ORDER BY CASE phase WHEN 'translate' THEN 0 WHEN 'create_chat' THEN 1
+                             WHEN 'run' THEN 2 WHEN 'summarize' THEN 3 ELSE 4 END",
+    )?;
+    let tokens = |row: &rusqlite::Row, i: usize| -> rusqlite::Result<Option<u64>> {
+        Ok(row.get::<_, Option<i64>>(i)?.map(|t| t as u64))
+    };
+    let rows = stmt.query_map([command_id.to_string()], |row| {
+        Ok(CommandPhase {
+            phase: row.get(0)?,
+            model: row.get(1)?,
+            duration_ms: row.get::<_, i64>(2)? as u64,
+            input_tokens: tokens(row, 3)?,
+            output_tokens: tokens(row, 4)?,
+            cache_read_tokens: tokens(row, 5)?,
+            cache_write_tokens: tokens(row, 6)?,
+            cost_usd: row.get(7)?,
+            outcome: row.get(8)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// How `usage_stats` groups phases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsGroup {
+    /// Model that ran the phase.
+    Model,
+    /// Repo of the command.
+    Repo,
+    /// UTC day the command was created.
+    Day,
+}
+
+/// Usage of the admin's commands (every user's with None) created in `[since, until)`
+/// (ISO8601, both optional), grouped by `group`, most expensive first.
+pub fn usage_stats(
+    conn: &Connection,
+    admin_id: Option<Uuid>,
+    group: StatsGroup,
+    since: Option<&str>,
+    until: Option<&str>,
+) -> Result<Vec<UsageStats>> {
+    let key = match group {
+        StatsGroup::Model => "p.model",
+        StatsGroup::Repo => "c.repo_path",
+        StatsGroup::Day => "substr(c.created_at, 1, 10)",
+    };
+    let admin_id = admin_id.map(|id| id.to_string());
+    let admin_id = admin_id.as_deref();
+    let mut clauses = vec!["1 = 1"];
+    let mut args: Vec<&dyn rusqlite::ToSql> = Vec::new();
+    for (clause, value) in [
+        ("d.admin_id = ?", &admin_id),
+        ("c.created_at >= ?", &since),
+        ("c.created_at < ?", &until),
+    ] {
+        if let Some(v) = value {
+            clauses.push(clause);
+            args.push(v);
+        }
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {key}, COUNT(DISTINCT p.command_id), SUM(p.duration_ms),
+                COALESCE(SUM(p.input_tokens), 0), COALESCE(SUM(p.output_tokens), 0),
+                COALESCE(SUM(p.cache_read_tokens), 0), COALESCE(SUM(p.cache_write_tokens), 0),
+                COALESCE(SUM(p.cost_usd), 0.0)
+         FROM command_phases p
+         JOIN commands c ON c.id = p.command_id
+         JOIN devices d ON c.device_id = d.id
+         WHERE {}
+         GROUP BY 1
+         ORDER BY 8 DESC, 3 DESC",
+        clauses.join(" AND ")
+    ))?;
+    let rows = stmt.query_map(args.as_slice(), |row| {
+        Ok(UsageStats {
+            key: row.get(0)?,
+            commands: row.get::<_, i64>(1)? as u64,
+            duration_ms: row.get::<_, i64>(2)? as u64,
+            input_tokens: row.get::<_, i64>(3)? as u64,
+            output_tokens: row.get::<_, i64>(4)? as u64,
+            cache_read_tokens: row.get::<_, i64>(5)? as u64,
+            cache_write_tokens: row.get::<_, i64>(6)? as u64,
+            cost_usd: row.get(7)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Prior turns of a chat, for translator context: (input, output) of finished commands,
+/// oldest first.
+pub fn list_chat_history(
+    conn: &Connection,
+    chat_id: Uuid,
+) -> Result<Vec<(String, Option<String>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT input, output FROM commands
+         WHERE chat_id = ?1 AND status IN ('done', 'failed')
+         ORDER BY created_at ASC, rowid ASC",
+    )?;
+    let rows = stmt.query_map([chat_id.to_string()], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Chat row as stored in the `chats` table.
+#[derive(Debug, Clone)]
+pub struct ChatRow {
+    pub id: Uuid,
+    pub admin_id: Uuid,
+    pub title: String,
+    pub repo_path: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+    pub cursor_chat_id: Option<String>,
+    pub archived: bool,
+    pub last_activity_at: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const CHAT_COLUMNS: &str = "id, admin_id, title, repo_path, translator_model, workload_model, cursor_chat_id, archived, last_activity_at, created_at, updated_at";
+
+fn chat_from_row(row: &rusqlite::Row) -> rusqlite::Result<ChatRow> {
+    Ok(ChatRow {
+        id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
+        admin_id: Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
+        title: row.get(2)?,
+        repo_path: row.get(3)?,
+        translator_model: row.get(4)?,
+        workload_model: row.get(5)?,
+        cursor_chat_id: row.get(6)?,
+        archived: row.get(7)?,
+        last_activity_at: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+/// Placeholder title until the first turn names the chat.
+pub const NEW_CHAT_TITLE: &str = "New chat";
+
+/// Title for a chat from its first turn: the first non-empty line, at most 60 characters.
+pub fn chat_title_from_input(input: &str) -> String {
+    let line = input
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or(NEW_CHAT_TITLE);
+    if line.chars().count() > 60 {
+        format!("{}…", line.chars().take(59).collect::<String>().trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Fields for a new chat. Without a title the chat is named after its first turn.
+#[derive(Debug, Clone, Default)]
+pub struct NewChat<'a> {
+    pub title: Option<&'a str>,
+    pub repo_path: Option<&'a str>,
+    pub translator_model: Option<&'a str>,
+    pub workload_model: Option<&'a str>,
+    pub cursor_chat_id: Option<&'a str>,
+}
+
+/// Create a chat for admin.
+pub fn create_chat(conn: &Connection, admin_id: Uuid, chat: &NewChat) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    let now = chrono_iso8601();
+    conn.execute(
+        "INSERT INTO chats (id, admin_id, title, title_pending, repo_path, translator_model, workload_model, cursor_chat_id, archived, last_activity_at, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 0, ?9, ?9, ?9)",
+        params![
+            id.to_string(),
+            admin_id.to_string(),
+            chat.title.unwrap_or(NEW_CHAT_TITLE),
+            chat.title.is_none(),
+            chat.repo_path,
+            chat.translator_model,
+            chat.workload_model,
+            chat.cursor_chat_id,
+            now,
+        ],
+    )?;
+    Ok(id)
+}
+
+/// Get chat by id if it belongs to admin (any user's with None).
+pub fn get_chat(conn: &Connection, id: Uuid, admin_id: Option<Uuid>) -> Result<Option<ChatRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM chats WHERE id = ?1 AND (?2 IS NULL OR admin_id = ?2)",
+        CHAT_COLUMNS
+    ))?;
+    match stmt.query_row(
+        params![id.to_string(), admin_id.map(|id| id.to_string())],
+        chat_from_row,
+    ) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Find admin's chat for a Cursor CLI session.
+pub fn find_chat_by_cursor_chat_id(
+    conn: &Connection,
+    admin_id: Uuid,
+    cursor_chat_id: &str,
+) -> Result<Option<ChatRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM chats WHERE admin_id = ?1 AND cursor_chat_id = ?2
+         ORDER BY created_at ASC LIMIT 1",
+        CHAT_COLUMNS
+    ))?;
+    match stmt.query_row(params![admin_id.to_string(), cursor_chat_id], chat_from_row) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// List admin's chats (every user's with None), archived or not, most recently active first.
+pub fn list_chats(
+    conn: &Connection,
+    admin_id: Option<Uuid>,
+    archived: bool,
+) -> Result<Vec<ChatRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM chats WHERE (?1 IS NULL OR admin_id = ?1) AND archived = ?2
+         ORDER BY last_activity_at DESC, created_at DESC",
+        CHAT_COLUMNS
+    ))?;
+    let rows = stmt.query_map(
+        params![admin_id.map(|id| id.to_string()), archived],
+        chat_from_row,
+    )?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Fields to update on a chat. `None` leaves the column unchanged; setting a title stops it
+/// being replaced by the first turn.
+#[derive(Debug, Clone, Default)]
+pub struct ChatUpdate<'a> {
+    pub title: Option<&'a str>,
+    pub repo_path: Option<&'a str>,
+    pub translator_model: Option<&'a str>,
+    pub workload_model: Option<&'a str>,
+    pub archived: Option<bool>,
+}
+
+/// Update admin's chat (any user's with None). Returns true if it exists.
+pub fn update_chat(
+    conn: &Connection,
+    id: Uuid,
+    admin_id: Option<Uuid>,
+    update: &ChatUpdate,
+) -> Result<bool> {
+    let now = chrono_iso8601();
+    let rows = conn.execute(
+        "UPDATE chats SET
+           title = COALESCE(?1, title),
+           title_pending = CASE WHEN ?1 IS NULL THEN title_pending ELSE 0 END,
+           repo_path = COALESCE(?2, repo_path),
+           translator_model = COALESCE(?3, translator_model),
+           workload_model = COALESCE(?4, workload_model),
+           archived = COALESCE(?5, archived),
+           updated_at = ?6
+         WHERE id = ?7 AND (?8 IS NULL OR admin_id = ?8)",
+        params![
+            update.title,
+            update.repo_path,
+            update.translator_model,
+            update.workload_model,
+            update.archived,
+            now,
+            id.to_string(),
+            admin_id.map(|id| id.to_string())
+        ],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Delete admin's chat (any user's with None) and its commands. Returns true if it existed.
+pub fn delete_chat(conn: &Connection, id: Uuid, admin_id: Option<Uuid>) -> Result<bool> {
+    let admin_id = admin_id.map(|id| id.to_string());
+    conn.execute(
+        "DELETE FROM commands WHERE chat_id = ?1
+           AND chat_id IN (SELECT id FROM chats WHERE ?2 IS NULL OR admin_id = ?2)",
+        params![id.to_string(), admin_id],
+    )?;
+    let rows = conn.execute(
+        "DELETE FROM chats WHERE id = ?1 AND (?2 IS NULL OR admin_id = ?2)",
+        params![id.to_string(), admin_id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Record a new turn in a chat: bump its last activity and, if the title is still the
+/// placeholder, name the chat after `input`.
+pub fn record_chat_turn(conn: &Connection, chat_id: Uuid, input: &str) -> Result<()> {
+    let now = chrono_iso8601();
+    conn.execute(
+        "UPDATE chats SET
+           title = CASE WHEN title_pending = 1 THEN ?1 ELSE title END,
+           title_pending = 0,
+           last_activity_at = ?2,
+           updated_at = ?2
+         WHERE id = ?3",
+        params![chat_title_from_input(input), now, chat_id.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Remember the Cursor CLI session the executor created for a command's chat, so later turns
+/// resume it. Keeps an existing session.
+pub fn set_chat_cursor_id_for_command(
+    conn: &Connection,
+    command_id: Uuid,
+    cursor_chat_id: &str,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE chats SET cursor_chat_id = COALESCE(cursor_chat_id, ?1)
+         WHERE id = (SELECT chat_id FROM commands WHERE id = ?2)",
+        params![cursor_chat_id, command_id.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Commands in a chat, oldest first.
+pub fn list_chat_commands(conn: &Connection, chat_id: Uuid) -> Result<Vec<CommandRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {} FROM commands WHERE chat_id = ?1 ORDER BY created_at ASC, rowid ASC",
+        COMMAND_COLUMNS,
+        live_output("commands")
+    ))?;
+    let rows = stmt.query_map([chat_id.to_string()], live_command_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// List pending (or executor-queued) commands of every user not yet acked, oldest first.
+/// Replayed to the executor, which all users share, when it (re)connects.
+pub fn list_pending_commands(conn: &Connection) -> Result<Vec<CommandRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, {}
+         FROM commands c
+         WHERE c.status IN ('pending', 'queued') AND c.acked_at IS NULL
+         ORDER BY c.created_at ASC, c.rowid ASC",
+        COMMAND_COLUMNS_C,
+        live_output("c")
+    ))?;
+    let rows = stmt.query_map([], live_command_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Record that the executor received a command. Returns true if the command exists.
+pub fn ack_command(conn: &Connection, id: Uuid) -> Result<bool> {
+    let now = chrono_iso8601();
+    let rows = conn.execute(
+        "UPDATE commands SET acked_at = COALESCE(acked_at, ?1) WHERE id = ?2",
+        params![now, id.to_string()],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Clear acks on commands that have not started, so they are replayed to the next executor
+/// connection. Called when an executor connection closes.
+pub fn clear_pending_acks(conn: &Connection) -> Result<usize> {
+    let rows = conn.execute(
+        "UPDATE commands SET acked_at = NULL
+         WHERE status IN ('pending', 'queued') AND acked_at IS NOT NULL",
+        [],
+    )?;
+    Ok(rows)
+}
+
+/// Fail `running` commands with no update or output event since `stale_before` (ISO8601),
+/// recording `failure_reason`. Returns the failed commands.
+pub fn fail_stale_commands(
+    conn: &Connection,
+    stale_before: &str,
+    failure_reason: &str,
+) -> Result<Vec<CommandRow>> {
+    let now = chrono_iso8601();
+    let mut stmt = conn.prepare(&format!(
+        "UPDATE commands SET status = 'failed', output = {}, failure_reason = ?1, updated_at = ?2
+         WHERE status = 'running' AND updated_at < ?3
+           AND NOT EXISTS (SELECT 1 FROM command_output_events
+                           WHERE command_id = commands.id AND created_at >= ?3)
+         RETURNING {}",
+        folded_output("commands"),
+        COMMAND_COLUMNS
+    ))?;
+    let failed = stmt
+        .query_map(params![failure_reason, now, stale_before], command_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    for cmd in &failed {
+        record_status_change(
+            conn,
+            cmd.id,
+            Some(CommandStatus::Running),
+            CommandStatus::Failed,
+            status_actors::RELAYER,
+            None,
+        )?;
+    }
+    Ok(failed)
+}
+
+/// Template row as stored in the `templates` table.
+#[derive(Debug, Clone)]
+pub struct TemplateRow {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub body: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const TEMPLATE_COLUMNS: &str = "id, name, description, body, created_at, updated_at";
+
+fn template_from_row(row: &rusqlite::Row) -> rusqlite::Result<TemplateRow> {
+    Ok(TemplateRow {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        body: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+/// List templates by name.
+pub fn list_templates(conn: &Connection) -> Result<Vec<TemplateRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM templates ORDER BY name COLLATE NOCASE, id",
+        TEMPLATE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], template_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+pub fn get_template(conn: &Connection, id: &str) -> Result<Option<TemplateRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM templates WHERE id = ?1",
+        TEMPLATE_COLUMNS
+    ))?;
+    let mut rows = stmt.query([id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(template_from_row(row)?)),
+        None => Ok(None),
+    }
+}
+
+/// Create a template. Returns false if the id is taken.
+pub fn create_template(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+    description: Option<&str>,
+    body: &str,
+) -> Result<bool> {
+    let now = chrono_iso8601();
+    let rows = conn.execute(
+        "INSERT OR IGNORE INTO templates (id, name, description, body, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        params![id, name, description, body, now],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Fields to update on a template. `None` leaves the column unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateUpdate<'a> {
+    pub name: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub body: Option<&'a str>,
+}
+
+pub fn update_template(conn: &Connection, id: &str, update: &TemplateUpdate) -> Result<bool> {
+    let now = chrono_iso8601();
+    let rows = conn.execute(
+        "UPDATE templates SET
+           name = COALESCE(?1, name),
+           description = COALESCE(?2, description),
+           body = COALESCE(?3, body),
+           updated_at = ?4
+         WHERE id = ?5",
+        params![update.name, update.description, update.body, now, id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Delete a template. Commands that used it keep their `context_mode`.
+pub fn delete_template(conn: &Connection, id: &str) -> Result<bool> {
+    let rows = conn.execute("DELETE FROM templates WHERE id = ?1", [id])?;
+    Ok(rows > 0)
+}
+
+/// Workflow row as stored in the `workflows` table.
+#[derive(Debug, Clone)]
+pub struct WorkflowRow {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<WorkflowStep>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const WORKFLOW_COLUMNS: &str = "id, name, description, steps, created_at, updated_at";
+
+/// Parse a JSON `steps` column.
+fn steps_from_column(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<Vec<WorkflowStep>> {
+    let json: String = row.get(idx)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn workflow_from_row(row: &rusqlite::Row) -> rusqlite::Result<WorkflowRow> {
+    Ok(WorkflowRow {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        steps: steps_from_column(row, 3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+/// List workflows by name.
+pub fn list_workflows(conn: &Connection) -> Result<Vec<WorkflowRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM workflows ORDER BY name COLLATE NOCASE, id",
+        WORKFLOW_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], workflow_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+pub fn get_workflow(conn: &Connection, id: &str) -> Result<Option<WorkflowRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM workflows WHERE id = ?1",
+        WORKFLOW_COLUMNS
+    ))?;
+    let mut rows = stmt.query([id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(workflow_from_row(row)?)),
+        None => Ok(None),
+    }
+}
+
+/// Create a workflow. Returns false if the id is taken.
+pub fn create_workflow(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+    description: Option<&str>,
+    steps: &[WorkflowStep],
+) -> Result<bool> {
+    let now = chrono_iso8601();
+    let rows = conn.execute(
+        "INSERT OR IGNORE INTO workflows (id, name, description, steps, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        params![id, name, description, serde_json::to_string(steps)?, now],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Fields to update on a workflow. `None` leaves the column unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct WorkflowUpdate<'a> {
+    pub name: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub steps: Option<&'a [WorkflowStep]>,
+}
+
+pub fn update_workflow(conn: &Connection, id: &str, update: &WorkflowUpdate) -> Result<bool> {
+    let now = chrono_iso8601();
+    let steps = update.steps.map(serde_json::to_string).transpose()?;
+    let rows = conn.execute(
+        "UPDATE workflows SET
+           name = COALESCE(?1, name),
+           description = COALESCE(?2, description),
+           steps = COALESCE(?3, steps),
+           updated_at = ?4
+         WHERE id = ?5",
+        params![update.name, update.description, steps, now, id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Delete a workflow. Its runs keep their steps and continue.
+pub fn delete_workflow(conn: &Connection, id: &str) -> Result<bool> {
+    let rows = conn.execute("DELETE FROM workflows WHERE id = ?1", [id])?;
+    Ok(rows > 0)
+}
+
+/// Workflow run row as stored in `workflow_runs`.
+#[derive(Debug, Clone)]
+pub struct WorkflowRunRow {
+    pub id: Uuid,
+    pub admin_id: Uuid,
+    pub device_id: Uuid,
+    pub workflow_id: Option<String>,
+    pub name: String,
+    pub steps: Vec<WorkflowStep>,
+    pub input: String,
+    pub repo_path: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+    pub chat_id: Option<Uuid>,
+    pub status: String,
+    pub current_step: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const WORKFLOW_RUN_COLUMNS: &str = "id, admin_id, device_id, workflow_id, name, steps, input, repo_path, translator_model, workload_model, chat_id, status, current_step, created_at, updated_at";
+
+fn workflow_run_from_row(row: &rusqlite::Row) -> rusqlite::Result<WorkflowRunRow> {
+    let uuid = |idx: usize| -> rusqlite::Result<Option<Uuid>> {
+        Ok(row
+            .get::<_, Option<String>>(idx)?
+            .and_then(|s| Uuid::parse_str(&s).ok()))
+    };
+    Ok(WorkflowRunRow {
+        id: uuid(0)?.unwrap(),
+        admin_id: uuid(1)?.unwrap(),
+        device_id: uuid(2)?.unwrap(),
+        workflow_id: row.get(3)?,
+        name: row.get(4)?,
+        steps: steps_from_column(row, 5)?,
+        input: row.get(6)?,
+        repo_path: row.get(7)?,
+        translator_model: row.get(8)?,
+        workload_model: row.get(9)?,
+        chat_id: uuid(10)?,
+        status: row.get(11)?,
+        current_step: row.get(12)?,
+        created_at: row.get(13)?,
+        updated_at: row.get(14)?,
+    })
+}
+
+/// Fields for a new workflow run.
+#[derive(Debug, Clone)]
+pub struct NewWorkflowRun<'a> {
+    pub device_id: Uuid,
+    pub workflow_id: &'a str,
+    pub name: &'a str,
+    pub steps: &'a [WorkflowStep],
+    pub input: &'a str,
+    pub repo_path: Option<&'a str>,
+    pub translator_model: Option<&'a str>,
+    pub workload_model: Option<&'a str>,
+    pub chat_id: Option<Uuid>,
+    /// `running` or, when the first step needs approval, `awaiting_approval`.
+    pub status: &'a str,
+}
+
+/// Create a workflow run at step 0.
+pub fn create_workflow_run(
+    conn: &Connection,
+    admin_id: Uuid,
+    run: &NewWorkflowRun,
+) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    let now = chrono_iso8601();
+    conn.execute(
+        "INSERT INTO workflow_runs (id, admin_id, device_id, workflow_id, name, steps, input,
+                                    repo_path, translator_model, workload_model, chat_id, status,
+                                    current_step, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, 0, ?13, ?13)",
+        params![
+            id.to_string(),
+            admin_id.to_string(),
+            run.device_id.to_string(),
+            run.workflow_id,
+            run.name,
+            serde_json::to_string(run.steps)?,
+            run.input,
+            run.repo_path,
+            run.translator_model,
+            run.workload_model,
+            run.chat_id.map(|id| id.to_string()),
+            run.status,
+            now,
+        ],
+    )?;
+    Ok(id)
+}
+
+pub fn get_workflow_run(conn: &Connection, id: Uuid) -> Result<Option<WorkflowRunRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM workflow_runs WHERE id = ?1",
+        WORKFLOW_RUN_COLUMNS
+    ))?;
+    match stmt.query_row([id.to_string()], workflow_run_from_row) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Admin's workflow runs (every user's with None), newest first.
+pub fn list_workflow_runs(
+    conn: &Connection,
+    admin_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<WorkflowRunRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM workflow_runs WHERE ?1 IS NULL OR admin_id = ?1
+         ORDER BY created_at DESC, rowid DESC LIMIT ?2",
+        WORKFLOW_RUN_COLUMNS
+    ))?;
+    let rows = stmt.query_map(
+        params![admin_id.map(|id| id.to_string()), limit],
+        workflow_run_from_row,
+    )?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Move a run from (`from_status`, `from_step`) to (`to_status`, `to_step`). Returns false if
+/// the run is no longer in the expected state, so concurrent or repeated transitions apply once.
+pub fn transition_workflow_run(
+    conn: &Connection,
+    id: Uuid,
+    from: (&str, i64),
+    to: (&str, i64),
+) -> Result<bool> {
+    let rows = conn.execute(
+        "UPDATE workflow_runs SET status = ?1, current_step = ?2, updated_at = ?3
+         WHERE id = ?4 AND status = ?5 AND current_step = ?6",
+        params![to.0, to.1, chrono_iso8601(), id.to_string(), from.0, from.1],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Commands of a run as (step, command id, status), in step order.
+pub fn list_workflow_run_commands(
+    conn: &Connection,
+    run_id: Uuid,
+) -> Result<Vec<(i64, Uuid, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT workflow_step, id, status FROM commands
+         WHERE workflow_run_id = ?1 ORDER BY workflow_step, created_at",
+    )?;
+    let rows = stmt.query_map([run_id.to_string()], |row| {
+        Ok((
+            row.get(0)?,
+            Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
+            row.get(2)?,
+        ))
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Workflow run and step a command belongs to, if any.
+pub fn command_workflow_step(conn: &Connection, command_id: Uuid) -> Result<Option<(Uuid, i64)>> {
+    let found = conn.query_row(
+        "SELECT workflow_run_id, workflow_step FROM commands
+         WHERE id = ?1 AND workflow_run_id IS NOT NULL",
+        [command_id.to_string()],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+    );
+    match found {
+        Ok((run_id, step)) => Ok(Uuid::parse_str(&run_id).ok().map(|id| (id, step))),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Schedule row as stored in `schedules`, with the most recent command it created.
+#[derive(Debug, Clone)]
+pub struct ScheduleRow {
+    pub id: Uuid,
+    pub admin_id: Uuid,
+    pub device_id: Uuid,
+    pub name: String,
+    pub cron: String,
+    pub input: String,
+    pub context_mode: Option<String>,
+    pub repo_path: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+    pub max_runtime_secs: Option<i64>,
+    pub enabled: bool,
+    pub next_run_at: Option<String>,
+    pub last_run_at: Option<String>,
+    pub last_command_id: Option<Uuid>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Columns selected for a `ScheduleRow` from `schedules s`.
+const SCHEDULE_COLUMNS: &str = "s.id, s.admin_id, s.device_id, s.name, s.cron, s.input, s.context_mode, s.repo_path, s.translator_model, s.workload_model, s.max_runtime_secs, s.enabled, s.next_run_at, s.last_run_at, (SELECT c.id FROM commands c WHERE c.schedule_id = s.id ORDER BY c.created_at DESC, c.rowid DESC LIMIT 1), s.created_at, s.updated_at";
+
+fn schedule_from_row(row: &rusqlite::Row) -> rusqlite::Result<ScheduleRow> {
+    let uuid = |idx: usize| -> rusqlite::Result<Option<Uuid>> {
+        Ok(row
+            .get::<_, Option<String>>(idx)?
+            .and_then(|s| Uuid::parse_str(&s).ok()))
+    };
+    Ok(ScheduleRow {
+        id: uuid(0)?.unwrap(),
+        admin_id: uuid(1)?.unwrap(),
+        device_id: uuid(2)?.unwrap(),
+        name: row.get(3)?,
+        cron: row.get(4)?,
+        input: row.get(5)?,
+        context_mode: row.get(6)?,
+        repo_path: row.get(7)?,
+        translator_model: row.get(8)?,
+        workload_model: row.get(9)?,
+        max_runtime_secs: row.get(10)?,
+        enabled: row.get(11)?,
+        next_run_at: row.get(12)?,
+        last_run_at: row.get(13)?,
+        last_command_id: uuid(14)?,
+        created_at: row.get(15)?,
+        updated_at: row.get(16)?,
+    })
+}
+
+/// Admin's schedules (every user's with None) by name.
+pub fn list_schedules(conn: &Connection, admin_id: Option<Uuid>) -> Result<Vec<ScheduleRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM schedules s WHERE ?1 IS NULL OR s.admin_id = ?1
+         ORDER BY s.name COLLATE NOCASE, s.id",
+        SCHEDULE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([admin_id.map(|id| id.to_string())], schedule_from_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Get schedule by id if it belongs to admin (any user's with None).
+pub fn get_schedule(
+    conn: &Connection,
+    id: Uuid,
+    admin_id: Option<Uuid>,
+) -> Result<Option<ScheduleRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM schedules s WHERE s.id = ?1 AND (?2 IS NULL OR s.admin_id = ?2)",
+        SCHEDULE_COLUMNS
+    ))?;
+    match stmt.query_row(
+        params![id.to_string(), admin_id.map(|id| id.to_string())],
+        schedule_from_row,
+    ) {
+        Ok(r) => Ok(Some(r)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Fields for a schedule, on create and (merged with the stored row) on update.
+#[derive(Debug, Clone)]
+pub struct ScheduleFields<'a> {
+    pub name: &'a str,
+    pub cron: &'a str,
+    pub input: &'a str,
+    pub context_mode: Option<&'a str>,
+    pub repo_path: Option<&'a str>,
+    pub translator_model: Option<&'a str>,
+    pub workload_model: Option<&'a str>,
+    pub max_runtime_secs: Option<i64>,
+    pub enabled: bool,
+    /// `None` while disabled.
+    pub next_run_at: Option<&'a str>,
+}
+
+/// Create a schedule whose commands run as `device_id`.
+pub fn create_schedule(
+    conn: &Connection,
+    admin_id: Uuid,
+    device_id: Uuid,
+    fields: &ScheduleFields,
+) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    let now = chrono_iso8601();
+    conn.execute(
+        "INSERT INTO schedules (id, admin_id, device_id, name, cron, input, context_mode, repo_path,
+                                translator_model, workload_model, max_runtime_secs, enabled,
+                                next_run_at, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?14)",
+        params![
+            id.to_string(),
+            admin_id.to_string(),
+            device_id.to_string(),
+            fields.name,
+            fields.cron,
+            fields.input,
+            fields.context_mode,
+            fields.repo_path,
+            fields.translator_model,
+            fields.workload_model,
+            fields.max_runtime_secs,
+            fields.enabled,
+            fields.next_run_at,
+            now,
+        ],
     )?;
-    let rows = stmt.query_map(params![admin_id.to_string(), limit], |row| {
-        Ok((
-            Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
-            Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
-            row.get(2)?,
-            row.get(3)?,
-            row.get(4)?,
-            row.get(5)?,
-            row.get(6)?,
-            row.get(7)?,
-            row.get(8)?,
-            row.get(9)?,
-            row.get(10)?,
-            row.get(11)?,
-            row.get(12)?,
-        ))
-    })?;
-    rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    Ok(id)
 }
 
-/// Delete command by id if it belongs to admin. Returns true if deleted.
-pub fn delete_command(conn: &Connection, id: Uuid, admin_id: Uuid) -> Result<bool> {
+/// Replace a schedule's fields. Returns false if it doesn't exist or isn't admin's (with None,
+/// any user's).
+pub fn update_schedule(
+    conn: &Connection,
+    id: Uuid,
+    admin_id: Option<Uuid>,
+    fields: &ScheduleFields,
+) -> Result<bool> {
     let rows = conn.execute(
-        "DELETE FROM commands WHERE id = ?1 AND device_id IN (SELECT id FROM devices WHERE admin_id = ?2)",
-        params![id.to_string(), admin_id.to_string()],
+        "UPDATE schedules SET name = ?1, cron = ?2, input = ?3, context_mode = ?4, repo_path = ?5,
+                              translator_model = ?6, workload_model = ?7, max_runtime_secs = ?8,
+                              enabled = ?9, next_run_at = ?10, updated_at = ?11
+         WHERE id = ?12 AND (?13 IS NULL OR admin_id = ?13)",
+        params![
+            fields.name,
+            fields.cron,
+            fields.input,
+            fields.context_mode,
+            fields.repo_path,
+            fields.translator_model,
+            fields.workload_model,
+            fields.max_runtime_secs,
+            fields.enabled,
+            fields.next_run_at,
+            chrono_iso8601(),
+            id.to_string(),
+            admin_id.map(|id| id.to_string()),
+        ],
     )?;
     Ok(rows > 0)
 }
 
-/// Update command status, output, summary, cursor_chat_id.
-pub fn update_command(
-    conn: &Connection,
-    id: Uuid,
-    status: Option<&str>,
-    output: Option<&str>,
-    summary: Option<&str>,
-    cursor_chat_id: Option<&str>,
-) -> Result<bool> {
-    let now = chrono_iso8601();
-    let rows = if let Some(s) = status {
-        conn.execute(
-            "UPDATE commands SET status = ?1, output = COALESCE(?2, output), summary = COALESCE(?3, summary), cursor_chat_id = COALESCE(?4, cursor_chat_id), updated_at = ?5 WHERE id = ?6",
-            params![s, output, summary, cursor_chat_id, now, id.to_string()],
-        )?
-    } else {
-        conn.execute(
-            "UPDATE commands SET output = COALESCE(?1, output), summary = COALESCE(?2, summary), cursor_chat_id = COALESCE(?3, cursor_chat_id), updated_at = ?4 WHERE id = ?5",
-            params![output, summary, cursor_chat_id, now, id.to_string()],
-        )?
-    };
+/// Delete admin's schedule (any user's with None). Its commands stay, unlinked.
+pub fn delete_schedule(conn: &Connection, id: Uuid, admin_id: Option<Uuid>) -> Result<bool> {
+    let rows = conn.execute(
+        "DELETE FROM schedules WHERE id = ?1 AND (?2 IS NULL OR admin_id = ?2)",
+        params![id.to_string(), admin_id.map(|id| id.to_string())],
+    )?;
     Ok(rows > 0)
 }
 
-/// List prior commands in the same chat (by cursor_chat_id and device_id), for translator context.
-/// Returns (input, output) ordered by created_at asc. Excludes commands with no output.
-pub fn list_commands_by_cursor_chat_id(
-    conn: &Connection,
-    device_id: Uuid,
-    cursor_chat_id: &str,
-) -> Result<Vec<(String, Option<String>)>> {
-    let mut stmt = conn.prepare(
-        "SELECT input, output FROM commands
-         WHERE device_id = ?1 AND cursor_chat_id = ?2 AND status IN ('done', 'failed')
-         ORDER BY created_at ASC",
-    )?;
-    let rows = stmt.query_map(params![device_id.to_string(), cursor_chat_id], |row| {
-        Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
-    })?;
+/// Enabled schedules whose `next_run_at` is at or before `now` (ISO8601), oldest first.
+pub fn due_schedules(conn: &Connection, now: &str) -> Result<Vec<ScheduleRow>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM schedules s WHERE s.enabled = 1 AND s.next_run_at <= ?1
+         ORDER BY s.next_run_at, s.id",
+        SCHEDULE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([now], schedule_from_row)?;
     rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
 }
 
-/// Get next pending command for executor (by admin_id).
-pub fn get_pending_command(
-    conn: &Connection,
-    admin_id: Uuid,
-) -> Result<
-    Option<(
-        Uuid,
-        Uuid,
-        String,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-    )>,
-> {
-    let row = conn.query_row(
-        "SELECT c.id, c.device_id, c.input, c.repo_path, c.context_mode, c.translator_model, c.workload_model
-         FROM commands c
-         JOIN devices d ON c.device_id = d.id
-         WHERE d.admin_id = ?1 AND c.status = 'pending'
-         ORDER BY c.created_at ASC
-         LIMIT 1",
-        [admin_id.to_string()],
-        |row| {
-            Ok((
-                Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
-                Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
-                row.get(2)?,
-                row.get(3)?,
-                row.get(4)?,
-                row.get(5)?,
-                row.get(6)?,
-            ))
-        },
-    );
-    match row {
-        Ok(r) => Ok(Some(r)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.into()),
-    }
+/// Whether a command the schedule created is still pending, awaiting approval or running.
+pub fn schedule_has_active_command(conn: &Connection, id: Uuid) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM commands
+                        WHERE schedule_id = ?1 AND status NOT IN ('done', 'failed', 'cancelled'))",
+        [id.to_string()],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
 }
 
-/// List repos for admin.
-pub fn list_repos(
+/// Record a firing: set the next run and, when it created a command, the last run time.
+pub fn record_schedule_run(
     conn: &Connection,
-    admin_id: Uuid,
-) -> Result<Vec<(Uuid, String, Option<String>, String)>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, path, name, created_at FROM repos WHERE admin_id = ?1 ORDER BY created_at DESC",
+    id: Uuid,
+    next_run_at: Option<&str>,
+    last_run_at: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE schedules SET next_run_at = ?1, last_run_at = COALESCE(?2, last_run_at)
+         WHERE id = ?3",
+        params![next_run_at, last_run_at, id.to_string()],
     )?;
-    let rows = stmt.query_map([admin_id.to_string()], |row| {
+    Ok(())
+}
+
+/// Repo row: (id, path, name, created_at).
+pub type RepoRow = (Uuid, String, Option<String>, String);
+
+/// List repos. They are shared by all users, like the executor that syncs them.
+pub fn list_repos(conn: &Connection) -> Result<Vec<RepoRow>> {
+    let mut stmt =
+        conn.prepare("SELECT id, path, name, created_at FROM repos ORDER BY created_at DESC")?;
+    let rows = stmt.query_map([], |row| {
         Ok((
             Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
             row.get(1)?,
@@ -560,6 +3281,7 @@ mod tests {
     use super::*;
     use crate::auth::{generate_api_key, generate_totp_secret, hash_api_key};
     use sha2::{Digest, Sha256};
+    use shared::phase_outcomes;
 
     const TEST_CLIENT_SALT: &str = "test-client-salt";
     const TEST_SERVER_SALT: &str = "test-server-salt";
@@ -636,6 +3358,44 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn touch_device_writes_last_seen_at_at_most_once_a_minute() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        let api_key_hash = hash_api_key(&api_key).unwrap();
+        let totp_secret = generate_totp_secret().unwrap();
+        let ch = client_hash("p");
+        let password_hash =
+            bcrypt::hash(format!("{}{}", TEST_SERVER_SALT, ch), bcrypt::DEFAULT_COST).unwrap();
+        setup_admin(&conn, "a", &password_hash, &totp_secret, &api_key_hash).unwrap();
+        let (device_id, admin_id, _) = validate_device(&conn, &api_key).unwrap().unwrap();
+        let seen_ago = |secs: i64| {
+            let at = (chrono::Utc::now() - chrono::Duration::seconds(secs))
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string();
+            conn.execute("UPDATE devices SET last_seen_at = ?1", [&at])
+                .unwrap();
+            at
+        };
+        let last_seen = || -> String {
+            conn.query_row(
+                "SELECT last_seen_at FROM devices WHERE id = ?1",
+                [device_id.to_string()],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        let recent = seen_ago(10);
+        assert!(touch_device(&conn, device_id, admin_id).unwrap());
+        assert_eq!(last_seen(), recent);
+        let stale = seen_ago(120);
+        assert!(touch_device(&conn, device_id, admin_id).unwrap());
+        assert!(last_seen() > stale);
+        // A recent touch doesn't make another admin's device valid.
+        assert!(!touch_device(&conn, device_id, Uuid::new_v4()).unwrap());
+    }
+
     #[test]
     fn reserve_code_and_register_device() {
         let conn = in_memory_db_with_migrations();
@@ -660,13 +3420,193 @@ mod tests {
         let new_api_key_hash = hash_api_key(&new_api_key).unwrap();
 
         let out = register_device(&conn, code, &ch, &new_api_key_hash, TEST_SERVER_SALT).unwrap();
-        assert!(out.is_some());
-        assert_eq!(out.as_deref(), Some(totp_secret.as_str()));
+        let (admin_id, secret) = out.unwrap();
+        assert_eq!(secret, totp_secret);
+        assert_eq!(
+            admin_id,
+            validate_device(&conn, &api_key).unwrap().unwrap().1
+        );
 
         let validated = validate_device(&conn, &new_api_key).unwrap();
         assert!(validated.is_some());
     }
 
+    #[test]
+    fn totp_steps_and_recovery_codes_are_single_use() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        setup_admin(
+            &conn,
+            "admin1",
+            "hash",
+            &generate_totp_secret().unwrap(),
+            &hash_api_key(&api_key).unwrap(),
+        )
+        .unwrap();
+        let (_, admin_id, _) = validate_device(&conn, &api_key).unwrap().unwrap();
+
+        assert!(accept_totp_step(&conn, admin_id, 100).unwrap());
+        assert!(!accept_totp_step(&conn, admin_id, 100).unwrap());
+        assert!(!accept_totp_step(&conn, admin_id, 99).unwrap());
+        assert!(accept_totp_step(&conn, admin_id, 101).unwrap());
+
+        let hashes = vec!["a".to_string(), "b".to_string()];
+        replace_recovery_codes(&conn, admin_id, &hashes).unwrap();
+        assert!(use_recovery_code(&conn, admin_id, "a").unwrap());
+        assert!(!use_recovery_code(&conn, admin_id, "a").unwrap());
+        assert!(!use_recovery_code(&conn, Uuid::new_v4(), "b").unwrap());
+        replace_recovery_codes(&conn, admin_id, &["c".to_string()]).unwrap();
+        assert!(!use_recovery_code(&conn, admin_id, "b").unwrap());
+        assert!(has_unused_recovery_codes(&conn, admin_id).unwrap());
+        assert!(use_recovery_code(&conn, admin_id, "c").unwrap());
+        assert!(!has_unused_recovery_codes(&conn, admin_id).unwrap());
+    }
+
+    #[test]
+    fn access_tokens_stop_at_expiry_revocation_and_device_revocation() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        let api_key_hash = hash_api_key(&api_key).unwrap();
+        let totp_secret = generate_totp_secret().unwrap();
+        setup_admin(&conn, "a", "hash", &totp_secret, &api_key_hash).unwrap();
+        let (device_id, admin_id, _) = validate_device(&conn, &api_key).unwrap().unwrap();
+        let insert = |hash: &str, expires_at: Option<&str>| {
+            insert_access_token(
+                &conn,
+                &NewAccessToken {
+                    admin_id,
+                    device_id,
+                    name: "ci",
+                    token_hash: hash,
+                    prefix: "pat_0000",
+                    scopes: &[TokenScope::CommandsRead, TokenScope::FilesRead],
+                    expires_at,
+                },
+            )
+            .unwrap()
+        };
+
+        let live = insert("live", Some("2999-01-01T00:00:00Z"));
+        assert_eq!(
+            live.scopes,
+            vec![TokenScope::CommandsRead, TokenScope::FilesRead]
+        );
+        insert("expired", Some("2000-01-01T00:00:00Z"));
+        let revoked = insert("revoked", None);
+        assert!(revoke_access_token(&conn, revoked.id, admin_id).unwrap());
+        assert!(!revoke_access_token(&conn, revoked.id, admin_id).unwrap());
+
+        let auth = authenticate_access_token(&conn, "live").unwrap().unwrap();
+        assert_eq!((auth.token_id, auth.admin_id), (live.id, admin_id));
+        assert!(authenticate_access_token(&conn, "expired")
+            .unwrap()
+            .is_none());
+        assert!(authenticate_access_token(&conn, "revoked")
+            .unwrap()
+            .is_none());
+        let listed = list_access_tokens(&conn, admin_id).unwrap();
+        assert_eq!(listed.len(), 3);
+        assert!(listed
+            .iter()
+            .any(|t| t.id == live.id && t.last_used_at.is_some()));
+
+        assert!(revoke_device(&conn, device_id, admin_id).unwrap());
+        assert!(authenticate_access_token(&conn, "live").unwrap().is_none());
+    }
+
+    #[test]
+    fn invitations_are_single_use_and_disabling_revokes_devices() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        let api_key_hash = hash_api_key(&api_key).unwrap();
+        let totp_secret = generate_totp_secret().unwrap();
+        let owner_id = setup_admin(&conn, "a", "hash", &totp_secret, &api_key_hash).unwrap();
+        assert_eq!(
+            get_user(&conn, owner_id).unwrap().unwrap().role,
+            UserRole::Owner
+        );
+
+        let live = create_invitation(
+            &conn,
+            "live",
+            UserRole::Member,
+            owner_id,
+            "2999-01-01T00:00:00Z",
+        )
+        .unwrap();
+        create_invitation(
+            &conn,
+            "expired",
+            UserRole::Member,
+            owner_id,
+            "2000-01-01T00:00:00Z",
+        )
+        .unwrap();
+        let revoked = create_invitation(
+            &conn,
+            "revoked",
+            UserRole::Viewer,
+            owner_id,
+            "2999-01-01T00:00:00Z",
+        )
+        .unwrap();
+        assert!(revoke_invitation(&conn, revoked.id).unwrap());
+        assert!(!revoke_invitation(&conn, revoked.id).unwrap());
+
+        let member_key = generate_api_key();
+        let member_key_hash = hash_api_key(&member_key).unwrap();
+        let accept = |code_hash: &str| {
+            accept_invitation(
+                &conn,
+                code_hash,
+                "b",
+                "hash",
+                &totp_secret,
+                &member_key_hash,
+            )
+            .unwrap()
+        };
+        assert!(accept("expired").is_none());
+        assert!(accept("revoked").is_none());
+        // A failure part way through leaves no user behind and the invitation usable.
+        conn.execute_batch(
+            "CREATE TEMP TRIGGER fail_device BEFORE INSERT ON devices
+             BEGIN SELECT RAISE(ABORT, 'no devices'); END;",
+        )
+        .unwrap();
+        assert!(
+            accept_invitation(&conn, "live", "b", "hash", &totp_secret, &member_key_hash).is_err()
+        );
+        conn.execute_batch("DROP TRIGGER fail_device").unwrap();
+        assert!(get_admin(&conn, "b").unwrap().is_none());
+        let member_id = accept("live").unwrap();
+        assert!(accept("live").is_none());
+        assert!(!revoke_invitation(&conn, live.id).unwrap());
+        let member = get_user(&conn, member_id).unwrap().unwrap();
+        assert_eq!(
+            (member.username.as_str(), member.role),
+            ("b", UserRole::Member)
+        );
+        let listed = list_invitations(&conn).unwrap();
+        assert!(listed
+            .iter()
+            .any(|i| i.id == live.id && i.accepted_by == Some(member_id)));
+
+        let (member_device, _, _) = validate_device(&conn, &member_key).unwrap().unwrap();
+        assert!(set_user_role(&conn, member_id, UserRole::Viewer).unwrap());
+        assert_eq!(
+            disable_user(&conn, member_id).unwrap(),
+            Some(vec![member_device])
+        );
+        assert_eq!(disable_user(&conn, member_id).unwrap(), None);
+        assert!(!set_user_role(&conn, member_id, UserRole::Owner).unwrap());
+        assert!(get_user(&conn, member_id)
+            .unwrap()
+            .unwrap()
+            .disabled_at
+            .is_some());
+    }
+
     #[test]
     fn create_command_and_get_command() {
         let conn = in_memory_db_with_migrations();
@@ -683,33 +3623,459 @@ mod tests {
         let id = create_command(
             &conn,
             device_id,
-            "hello world",
-            Some("~/repos/foo"),
-            Some("continue"),
-            Some("claude-4"),
-            Some("cursor"),
-            None,
+            &NewCommand {
+                input: "hello world",
+                repo_path: Some("~/repos/foo"),
+                context_mode: Some("continue"),
+                translator_model: Some("claude-4"),
+                workload_model: Some("cursor"),
+                cursor_chat_id: None,
+                max_runtime_secs: Some(600),
+                chat_id: None,
+                review_prompt: false,
+                workflow_step: None,
+                schedule_id: None,
+                rerun_of: None,
+                idempotency_key: None,
+            },
         )
         .unwrap();
 
         let cmd = get_command(&conn, id).unwrap().unwrap();
-        assert_eq!(cmd.2, "hello world");
-        assert_eq!(cmd.3, "pending");
-        assert_eq!(cmd.6, Some("~/repos/foo".to_string()));
-        assert_eq!(cmd.8, Some("claude-4".to_string()));
+        assert_eq!(cmd.input, "hello world");
+        assert_eq!(cmd.status, "pending");
+        assert_eq!(cmd.repo_path, Some("~/repos/foo".to_string()));
+        assert_eq!(cmd.translator_model, Some("claude-4".to_string()));
+        assert_eq!(cmd.max_runtime_secs, Some(600));
 
         update_command(
             &conn,
             id,
-            Some("done"),
-            Some("output"),
-            Some("summary"),
-            None,
+            &CommandUpdate {
+                status: Some(CommandStatus::Done),
+                output: Some("output"),
+                summary: Some("summary"),
+                ..Default::default()
+            },
+            status_actors::EXECUTOR,
         )
         .unwrap();
         let cmd2 = get_command(&conn, id).unwrap().unwrap();
-        assert_eq!(cmd2.3, "done");
-        assert_eq!(cmd2.4, Some("output".to_string()));
+        assert_eq!(cmd2.status, "done");
+        assert_eq!(cmd2.output, Some("output".to_string()));
+    }
+
+    #[test]
+    fn pending_commands_replay_until_acked() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        let api_key_hash = hash_api_key(&api_key).unwrap();
+        let totp_secret = generate_totp_secret().unwrap();
+        let ch = client_hash("p");
+        let password_hash =
+            bcrypt::hash(format!("{}{}", TEST_SERVER_SALT, ch), bcrypt::DEFAULT_COST).unwrap();
+
+        setup_admin(&conn, "a", &password_hash, &totp_secret, &api_key_hash).unwrap();
+        let (device_id, _, _) = validate_device(&conn, &api_key).unwrap().unwrap();
+
+        let first = create_command(
+            &conn,
+            device_id,
+            &NewCommand {
+                input: "first",
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let second = create_command(
+            &conn,
+            device_id,
+            &NewCommand {
+                input: "second",
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let pending: Vec<_> = list_pending_commands(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(pending, vec![first, second]);
+
+        assert!(ack_command(&conn, first).unwrap());
+        let pending: Vec<_> = list_pending_commands(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(pending, vec![second]);
+
+        // Connection dropped before the command started: replay it again.
+        assert_eq!(clear_pending_acks(&conn).unwrap(), 1);
+        assert_eq!(list_pending_commands(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn search_commands_indexes_finished_output_and_tracks_deletes() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        let api_key_hash = hash_api_key(&api_key).unwrap();
+        let totp_secret = generate_totp_secret().unwrap();
+        let ch = client_hash("p");
+        let password_hash =
+            bcrypt::hash(format!("{}{}", TEST_SERVER_SALT, ch), bcrypt::DEFAULT_COST).unwrap();
+
+        setup_admin(&conn, "a", &password_hash, &totp_secret, &api_key_hash).unwrap();
+        let (device_id, admin_id, _) = validate_device(&conn, &api_key).unwrap().unwrap();
+
+        let auth = create_command(
+            &conn,
+            device_id,
+            &NewCommand {
+                input: "fix the auth middleware",
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let other = create_command(
+            &conn,
+            device_id,
+            &NewCommand {
+                input: "bump dependencies",
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let set_output = |status, output| {
+            update_command(
+                &conn,
+                other,
+                &CommandUpdate {
+                    status: Some(status),
+                    output: Some(output),
+                    ..Default::default()
+                },
+                status_actors::EXECUTOR,
+            )
+            .unwrap()
+        };
+        // Output of a running command is not indexed until it finishes.
+        set_output(
+            CommandStatus::Running,
+            "Updated tokio; middleware untouched",
+        );
+        let hits = search_commands(&conn, Some(admin_id), "middleware", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        set_output(CommandStatus::Done, "Updated tokio; middleware untouched");
+
+        let hits = search_commands(&conn, Some(admin_id), "middleware", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        let hits = search_commands(&conn, Some(admin_id), "auth middlew*", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].command.id, auth);
+        assert!(hits[0].snippet.contains("<mark>auth</mark>"));
+
+        // FTS syntax in user input is treated literally.
+        assert!(search_commands(&conn, Some(admin_id), "\"auth OR (", 10).is_ok());
+        // Other admins see nothing.
+        assert!(
+            search_commands(&conn, Some(Uuid::new_v4()), "middleware", 10)
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(delete_command(&conn, auth, Some(admin_id)).unwrap());
+        let hits = search_commands(&conn, Some(admin_id), "middleware", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].command.id, other);
+        // Search rows are keyed on their own rowid, so VACUUM can't desync them.
+        conn.execute_batch("VACUUM").unwrap();
+        assert_eq!(
+            search_commands(&conn, Some(admin_id), "tokio", 10)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(delete_command(&conn, other, Some(admin_id)).unwrap());
+        let indexed: i64 = conn
+            .query_row("SELECT COUNT(*) FROM commands_fts", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(indexed, 0);
+    }
+
+    #[test]
+    fn output_events_fold_into_output_when_finished() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        let api_key_hash = hash_api_key(&api_key).unwrap();
+        let totp_secret = generate_totp_secret().unwrap();
+        let ch = client_hash("p");
+        let password_hash =
+            bcrypt::hash(format!("{}{}", TEST_SERVER_SALT, ch), bcrypt::DEFAULT_COST).unwrap();
+
+        setup_admin(&conn, "a", &password_hash, &totp_secret, &api_key_hash).unwrap();
+        let (device_id, admin_id, _) = validate_device(&conn, &api_key).unwrap().unwrap();
+        let id = create_command(
+            &conn,
+            device_id,
+            &NewCommand {
+                input: "x",
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let output = |conn: &Connection| {
+            get_command_for_admin(conn, id, Some(admin_id))
+                .unwrap()
+                .unwrap()
+                .output
+        };
+
+        assert!(append_output_event(&conn, id, 1, "append", "Hello").unwrap());
+        assert!(append_output_event(&conn, id, 2, "append", ", world").unwrap());
+        // Retries and late deliveries are ignored.
+        assert!(!append_output_event(&conn, id, 2, "append", ", world").unwrap());
+        assert!(!append_output_event(&conn, id, 1, "append", "Hello").unwrap());
+        assert_eq!(output(&conn).as_deref(), Some("Hello, world"));
+        // Lists read the same folded output from their own row.
+        let listed = list_commands(&conn, Some(admin_id), &CommandFilter::default(), 10).unwrap();
+        assert_eq!(listed[0].output.as_deref(), Some("Hello, world"));
+        assert_eq!(COMMAND_COLUMNS.split(',').count(), LIVE_OUTPUT_IDX);
+
+        assert!(append_output_event(&conn, id, 5, "replace", "Reset").unwrap());
+        assert_eq!(output(&conn).as_deref(), Some("Reset"));
+        let stored = |conn: &Connection| {
+            conn.query_row(
+                "SELECT output FROM commands WHERE id = ?1",
+                [id.to_string()],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .unwrap()
+        };
+        // Deltas are not written to the command row while it runs.
+        assert_eq!(stored(&conn), None);
+
+        // Full output on update is logged as a replace event; unchanged output is not.
+        for out in ["Final", "Final"] {
+            update_command(
+                &conn,
+                id,
+                &CommandUpdate {
+                    status: Some(CommandStatus::Running),
+                    output: Some(out),
+                    ..Default::default()
+                },
+                status_actors::EXECUTOR,
+            )
+            .unwrap();
+        }
+        let events = list_output_events(&conn, id, 0, 100).unwrap();
+        let seqs: Vec<_> = events.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![1, 2, 5, 6]);
+        assert_eq!(events[3].kind, "replace");
+        assert_eq!(events[3].content, "Final");
+        assert_eq!(list_output_events(&conn, id, 2, 1).unwrap()[0].seq, 5);
+
+        assert!(append_output_event(&conn, id, 7, "append", "!").unwrap());
+        update_command(
+            &conn,
+            id,
+            &CommandUpdate {
+                status: Some(CommandStatus::Done),
+                ..Default::default()
+            },
+            status_actors::EXECUTOR,
+        )
+        .unwrap();
+        assert_eq!(stored(&conn).as_deref(), Some("Final!"));
+        // Finished commands take no more deltas.
+        assert!(!append_output_event(&conn, id, 8, "append", "late").unwrap());
+        assert_eq!(output(&conn).as_deref(), Some("Final!"));
+    }
+
+    #[test]
+    fn usage_stats_group_phases() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        let api_key_hash = hash_api_key(&api_key).unwrap();
+        let totp_secret = generate_totp_secret().unwrap();
+        let ch = client_hash("p");
+        let password_hash =
+            bcrypt::hash(format!("{}{}", TEST_SERVER_SALT, ch), bcrypt::DEFAULT_COST).unwrap();
+
+        setup_admin(&conn, "a", &password_hash, &totp_secret, &api_key_hash).unwrap();
+        let (device_id, admin_id, _) = validate_device(&conn, &api_key).unwrap().unwrap();
+        let phase =
+            |phase: &str, model: &str, duration_ms: u64, input_tokens: Option<u64>| CommandPhase {
+                phase: phase.to_string(),
+                model: Some(model.to_string()),
+                duration_ms,
+                input_tokens,
+                cost_usd: input_tokens.map(|t| t as f64 / 1000.0),
+                ..Default::default()
+            };
+        for repo in ["~/repos/a", "~/repos/b"] {
+            let id = create_command(
+                &conn,
+                device_id,
+                &NewCommand {
+                    input: "x",
+                    repo_path: Some(repo),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            set_command_phases(
+                &conn,
+                id,
+                &[
+                    phase("translate", "fast", 1000, None),
+                    CommandPhase {
+                        outcome: Some(phase_outcomes::FAILED.to_string()),
+                        ..phase("run", "big", 9000, Some(2000))
+                    },
+                ],
+            )
+            .unwrap();
+            assert_eq!(
+                get_command(&conn, id).unwrap().unwrap().duration_ms,
+                Some(10000)
+            );
+            let phases = list_command_phases(&conn, id).unwrap();
+            assert_eq!(phases[1].phase, "run");
+            assert_eq!(phases[1].outcome.as_deref(), Some(phase_outcomes::FAILED));
+        }
+
+        let by_model = usage_stats(&conn, Some(admin_id), StatsGroup::Model, None, None).unwrap();
+        assert_eq!(by_model.len(), 2);
+        assert_eq!(by_model[0].key.as_deref(), Some("big"));
+        assert_eq!(by_model[0].commands, 2);
+        assert_eq!(by_model[0].duration_ms, 18000);
+        assert_eq!(by_model[0].input_tokens, 4000);
+        assert!((by_model[0].cost_usd - 4.0).abs() < 1e-9);
+        assert_eq!(by_model[1].input_tokens, 0);
+
+        let by_repo = usage_stats(&conn, Some(admin_id), StatsGroup::Repo, None, None).unwrap();
+        assert_eq!(by_repo.len(), 2);
+        assert!(by_repo.iter().all(|s| s.duration_ms == 10000));
+        let later = usage_stats(
+            &conn,
+            Some(admin_id),
+            StatsGroup::Day,
+            Some("2999-01-01T00:00:00Z"),
+            None,
+        )
+        .unwrap();
+        assert!(later.is_empty());
+    }
+
+    #[test]
+    fn chat_title_from_first_line() {
+        assert_eq!(chat_title_from_input("\n  Fix auth  \nrest"), "Fix auth");
+        assert_eq!(chat_title_from_input("   "), NEW_CHAT_TITLE);
+        let long = "x".repeat(80);
+        let title = chat_title_from_input(&long);
+        assert_eq!(title.chars().count(), 60);
+        assert!(title.ends_with('…'));
+    }
+
+    #[test]
+    fn chat_commands_use_the_chat_id_index() {
+        let conn = in_memory_db_with_migrations();
+        let plan: String = conn
+            .query_row(
+                "EXPLAIN QUERY PLAN SELECT id FROM commands WHERE chat_id = ?1 ORDER BY created_at",
+                ["c"],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(plan.contains("idx_commands_chat_id_created"), "{}", plan);
+    }
+
+    #[test]
+    fn fail_stale_commands_only_fails_stale_running() {
+        let conn = in_memory_db_with_migrations();
+        let api_key = generate_api_key();
+        let api_key_hash = hash_api_key(&api_key).unwrap();
+        let totp_secret = generate_totp_secret().unwrap();
+        let ch = client_hash("p");
+        let password_hash =
+            bcrypt::hash(format!("{}{}", TEST_SERVER_SALT, ch), bcrypt::DEFAULT_COST).unwrap();
+
+        setup_admin(&conn, "a", &password_hash, &totp_secret, &api_key_hash).unwrap();
+        let (device_id, _, _) = validate_device(&conn, &api_key).unwrap().unwrap();
+
+        let mut ids = Vec::new();
+        for status in ["running", "running", "pending", "running"] {
+            let id = create_command(
+                &conn,
+                device_id,
+                &NewCommand {
+                    input: status,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            update_command(
+                &conn,
+                id,
+                &CommandUpdate {
+                    status: CommandStatus::parse(status),
+                    ..Default::default()
+                },
+                status_actors::EXECUTOR,
+            )
+            .unwrap();
+            ids.push(id);
+        }
+        conn.execute(
+            "UPDATE commands SET updated_at = '2000-01-01T00:00:00Z' WHERE id != ?1",
+            [ids[1].to_string()],
+        )
+        .unwrap();
+        // Streamed output counts as activity.
+        assert!(append_output_event(&conn, ids[3], 1, "append", "still going").unwrap());
+
+        let failed = fail_stale_commands(&conn, "2001-01-01T00:00:00Z", "stale").unwrap();
+        assert_eq!(
+            failed.iter().map(|c| c.id).collect::<Vec<_>>(),
+            vec![ids[0]]
+        );
+        let cmd = get_command(&conn, ids[0]).unwrap().unwrap();
+        assert_eq!(cmd.status, "failed");
+        assert_eq!(cmd.failure_reason.as_deref(), Some("stale"));
+        assert_eq!(
+            get_command(&conn, ids[2]).unwrap().unwrap().status,
+            "pending"
+        );
+
+        // A late result from the executor does not overwrite the reaper's verdict.
+        let late = update_command(
+            &conn,
+            ids[0],
+            &CommandUpdate {
+                status: Some(CommandStatus::Done),
+                ..Default::default()
+            },
+            status_actors::EXECUTOR,
+        )
+        .unwrap();
+        assert_eq!(
+            late,
+            CommandUpdateOutcome::InvalidTransition {
+                from: CommandStatus::Failed,
+                to: CommandStatus::Done
+            }
+        );
+        let cmd = get_command(&conn, ids[0]).unwrap().unwrap();
+        assert_eq!(cmd.status, "failed");
+        assert_eq!(cmd.failure_reason.as_deref(), Some("stale"));
+        let history = list_command_status_history(&conn, ids[0]).unwrap();
+        let last = history.last().unwrap();
+        assert_eq!(last.to, CommandStatus::Failed);
+        assert_eq!(last.actor, status_actors::RELAYER);
     }
 
     #[test]
@@ -727,7 +4093,7 @@ mod tests {
 
         let result = add_repo(&conn, admin_id, "~/repos/my-project", Some("My Project"));
         assert!(result.is_ok());
-        let repos = list_repos(&conn, admin_id).unwrap();
+        let repos = list_repos(&conn).unwrap();
         assert_eq!(repos.len(), 1);
         assert_eq!(repos[0].1, "~/repos/my-project");
     }
@@ -776,7 +4142,7 @@ mod tests {
         ];
         replace_repos(&conn, admin_id, &paths).unwrap();
 
-        let repos = list_repos(&conn, admin_id).unwrap();
+        let repos = list_repos(&conn).unwrap();
         assert_eq!(repos.len(), 2, "only valid paths should be added");
         let paths: Vec<_> = repos.iter().map(|r| r.1.as_str()).collect();
         assert!(paths.contains(&"~/repos/valid-project"));
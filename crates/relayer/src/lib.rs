@@ -2,6 +2,11 @@
 
 pub mod api;
 pub mod auth;
+pub mod cli;
 pub mod config;
 pub mod db;
+pub mod reaper;
+pub mod recover;
 pub mod relay;
+pub mod scheduler;
+pub mod workflows;
@@ -1,18 +1,30 @@
 //! Dev PM Agent Relayer — HTTP + WebSocket backend.
 //!
-//! Required env: JWT_SECRET, EXECUTOR_API_KEY
-//! Optional: HOST, PORT, DATABASE_PATH, JWT_TTL_SECS
+//! `relayer [serve]` runs the server. Required env: JWT_SECRET, EXECUTOR_API_KEY
+//! Optional: HOST, PORT, DATABASE_PATH, JWT_TTL_SECS, STALE_COMMAND_SECS
+//!
+//! `relayer recover ...` runs manual recovery against the database (docs/RECOVERY_CLI.md).
 
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
 
-use relayer::{api, config, db, relay};
+use clap::Parser;
+use relayer::{api, cli, config, db, reaper, recover, relay, scheduler};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
+    let cli = cli::Cli::parse();
+
+    match cli.command.unwrap_or(cli::Commands::Serve) {
+        cli::Commands::Serve => serve().await,
+        cli::Commands::Recover(args) => recover::run(args),
+    }
+}
+
+async fn serve() -> anyhow::Result<()> {
     let config = config::Config::from_env().map_err(|e| anyhow::anyhow!("config: {}", e))?;
     let config = Arc::new(config);
 
@@ -41,6 +53,9 @@ async fn main() -> anyhow::Result<()> {
         file_search_pending: Arc::new(RwLock::new(HashMap::new())),
     };
 
+    tokio::spawn(reaper::run(state.clone()));
+    tokio::spawn(scheduler::run(state.clone()));
+
     let app = api::router(state);
 
     tracing::info!("Relayer listening on {}", addr);
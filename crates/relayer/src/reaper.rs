@@ -0,0 +1,60 @@
+//! Stale-run reaper. Only the executor moves a command out of `running`, so a command whose
+//! executor crashed or lost its connection mid-run would stay `running` forever. This task
+//! fails such commands once they go `stale_command_secs` without an update.
+
+use std::time::Duration;
+
+use shared::{failure_reasons, CommandStatus, WsCommandUpdatePayload};
+
+use crate::api::AppState;
+use crate::db;
+use crate::relay::BroadcastMessage;
+use crate::workflows;
+
+/// Fail stale `running` commands and notify controllers. Returns how many were failed.
+pub fn reap_stale_commands(state: &AppState) -> anyhow::Result<usize> {
+    let stale_secs = i64::try_from(state.config.stale_command_secs).unwrap_or(i64::MAX);
+    let stale_before = (chrono::Utc::now() - chrono::Duration::seconds(stale_secs))
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string();
+    let failed = {
+        let conn = state.db.0.lock().unwrap();
+        db::fail_stale_commands(&conn, &stale_before, failure_reasons::STALE)?
+    };
+    for cmd in &failed {
+        tracing::warn!(cmd_id = %cmd.id, last_update = %cmd.updated_at, "failing stale command");
+        state
+            .relay
+            .broadcast(BroadcastMessage::CommandUpdate(WsCommandUpdatePayload {
+                id: cmd.id,
+                status: CommandStatus::Failed.as_str().to_string(),
+                output: None,
+                summary: None,
+                cursor_chat_id: None,
+                queue_position: None,
+                failure_reason: cmd.failure_reason.clone(),
+                proposed_prompt: None,
+                updated_at: cmd.updated_at.clone(),
+            }));
+    }
+    if !failed.is_empty() {
+        // A failed step ends its workflow run.
+        let conn = state.db.0.lock().unwrap();
+        for cmd in &failed {
+            workflows::on_command_finished(&conn, &state.relay, cmd.id)?;
+        }
+    }
+    Ok(failed.len())
+}
+
+/// Run `reap_stale_commands` periodically (a tenth of the stale window, 10s..=60s).
+pub async fn run(state: AppState) {
+    let period = Duration::from_secs((state.config.stale_command_secs / 10).clamp(10, 60));
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        if let Err(e) = reap_stale_commands(&state) {
+            tracing::error!(err = %e, "stale command reaper failed");
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Manual recovery (`relayer recover ...`, docs/RECOVERY_CLI.md). The commands work on the
+//! SQLite database directly and skip auth, so they refuse to run unless explicitly enabled with
+//! `--enable` or `RELAYER_RECOVERY_ENABLED=1`.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::auth::generate_totp_secret;
+use crate::cli::{RecoverArgs, RecoverCommand};
+use crate::db;
+
+/// Env var that enables recovery commands, as an alternative to `--enable`.
+pub const ENABLE_ENV: &str = "RELAYER_RECOVERY_ENABLED";
+
+/// Whether recovery is enabled by the flag or by `ENABLE_ENV` (`1` or `true`).
+pub fn is_enabled(flag: bool, env: Option<&str>) -> bool {
+    flag || env.is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Open an existing relayer database. Migrations are not run: recovery never changes the schema.
+pub fn open_db(path: &Path) -> Result<Connection> {
+    if !path.is_file() {
+        bail!("database not found: {}", path.display());
+    }
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    Ok(conn)
+}
+
+/// The hash the webapp sends as the password (see `hashPassword` in apps/web/src/api/auth.ts).
+fn client_hash(password: &str, client_salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(client_salt.as_bytes());
+    hasher.update(b":dev-pm-agent:");
+    hasher.update(password.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Store a new password for `username`, hashed the way login verifies it.
+pub fn reset_password(
+    conn: &Connection,
+    username: &str,
+    new_password: &str,
+    password_salt: &str,
+    client_salt: &str,
+) -> Result<()> {
+    if new_password.is_empty() {
+        bail!("new password must not be empty");
+    }
+    let salted = format!(
+        "{}{}",
+        password_salt,
+        client_hash(new_password, client_salt)
+    );
+    let hash = bcrypt::hash(salted, bcrypt::DEFAULT_COST)?;
+    if !db::set_admin_password_hash(conn, username, &hash)? {
+        bail!("no admin named {:?}", username);
+    }
+    Ok(())
+}
+
+/// Give `username` a new TOTP secret. Returns it (base32).
+pub fn reset_totp(conn: &Connection, username: &str) -> Result<String> {
+    let secret = generate_totp_secret()?;
+    if !db::set_admin_totp_secret(conn, username, &secret)? {
+        bail!("no admin named {:?}", username);
+    }
+    Ok(secret)
+}
+
+/// Revoke the device whose `device_id` or `id` is `key`, keeping its commands. Refuses when `key`
+/// matches more than one device (the same `device_id` under several admins); pass the `id`
+/// instead.
+pub fn revoke_device(conn: &Connection, key: &str) -> Result<db::DeviceRow> {
+    let mut devices = db::find_devices(conn, key)?;
+    match devices.len() {
+        0 => bail!("no device {:?}", key),
+        1 => {}
+        _ => bail!(
+            "{:?} matches {} devices; pass one of their ids: {}",
+            key,
+            devices.len(),
+            devices
+                .iter()
+                .map(|d| d.id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+    let device = devices.remove(0);
+    if !db::revoke_device(
+        conn,
+        Uuid::parse_str(&device.id)?,
+        Uuid::parse_str(&device.admin_id)?,
+    )? {
+        bail!("device {:?} is already revoked", key);
+    }
+    Ok(device)
+}
+
+/// Run a `relayer recover` command, printing its result.
+pub fn run(args: RecoverArgs) -> Result<()> {
+    if !is_enabled(args.enable, std::env::var(ENABLE_ENV).ok().as_deref()) {
+        bail!(
+            "recovery is disabled; pass --enable or set {}=1 to run it",
+            ENABLE_ENV
+        );
+    }
+    let conn = open_db(&args.db_path)?;
+    match args.command {
+        RecoverCommand::ResetPassword {
+            username,
+            new_password,
+            password_salt,
+            client_salt,
+        } => {
+            reset_password(
+                &conn,
+                &username,
+                &new_password,
+                &password_salt,
+                &client_salt,
+            )?;
+            println!("Password reset for {}. TOTP is unchanged.", username);
+        }
+        RecoverCommand::ResetTotp { username } => {
+            let secret = reset_totp(&conn, &username)?;
+            println!("New TOTP secret for {} (add it to your authenticator; the old one no longer works):", username);
+            println!("{}", secret);
+        }
+        RecoverCommand::RevokeDevice { device_id } => {
+            let device = revoke_device(&conn, &device_id)?;
+            println!(
+                "Revoked {} device {} ({}) of {}.",
+                device.role, device.device_id, device.id, device.username
+            );
+        }
+        RecoverCommand::ListDevices => {
+            println!("ID\tDEVICE_ID\tROLE\tADMIN\tNAME\tLAST_SEEN_AT\tREVOKED_AT");
+            for d in db::list_devices(&conn)? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    d.id,
+                    d.device_id,
+                    d.role,
+                    d.username,
+                    d.name.as_deref().unwrap_or("-"),
+                    d.last_seen_at,
+                    d.revoked_at.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        RecoverCommand::ClearExpiredCodes => {
+            let n = db::clear_expired_codes(&conn)?;
+            println!("Deleted {} registration code(s).", n);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{generate_api_key, hash_api_key};
+
+    fn temp_db() -> Connection {
+        let migrations_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../migrations")
+            .canonicalize()
+            .unwrap();
+        std::env::set_var("MIGRATIONS_DIR", migrations_dir);
+        let path = std::env::temp_dir().join(format!("relayer_recover_{}.db", Uuid::new_v4()));
+        db::Db::open(&path).unwrap().run_migrations().unwrap();
+        let conn = open_db(&path).unwrap();
+        let key_hash = hash_api_key(&generate_api_key()).unwrap();
+        db::setup_admin(&conn, "admin1", "old-hash", "OLDSECRET", &key_hash).unwrap();
+        conn
+    }
+
+    #[test]
+    fn recovery_requires_enable_and_an_existing_db() {
+        assert!(!is_enabled(false, None));
+        assert!(!is_enabled(false, Some("0")));
+        assert!(is_enabled(false, Some("1")));
+        assert!(is_enabled(true, None));
+        let missing = std::env::temp_dir().join(format!("relayer_missing_{}.db", Uuid::new_v4()));
+        assert!(open_db(&missing).is_err());
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn reset_password_and_totp() {
+        let conn = temp_db();
+        reset_password(&conn, "admin1", "new-pass", "server-salt", "client-salt").unwrap();
+        let (_, hash, secret) = db::get_admin(&conn, "admin1").unwrap().unwrap();
+        let salted = format!("server-salt{}", client_hash("new-pass", "client-salt"));
+        assert!(bcrypt::verify(salted, &hash).unwrap());
+        assert_eq!(secret, "OLDSECRET");
+
+        let new_secret = reset_totp(&conn, "admin1").unwrap();
+        let (_, _, secret) = db::get_admin(&conn, "admin1").unwrap().unwrap();
+        assert_eq!(secret, new_secret);
+        assert_ne!(secret, "OLDSECRET");
+
+        assert!(reset_password(&conn, "nobody", "p", "s", "c").is_err());
+        assert!(reset_totp(&conn, "nobody").is_err());
+    }
+
+    #[test]
+    fn revoke_device_and_clear_codes() {
+        let conn = temp_db();
+        let devices = db::list_devices(&conn).unwrap();
+        assert_eq!(devices.len(), 1);
+        let device = &devices[0];
+        assert_eq!(device.role, "controller");
+        assert_eq!(device.username, "admin1");
+
+        let now = chrono::Utc::now();
+        let at = |secs: i64| {
+            (now + chrono::Duration::seconds(secs))
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string()
+        };
+        let device_id = Uuid::parse_str(&device.id).unwrap();
+        db::reserve_code(&conn, "expired", device_id, &at(-60)).unwrap();
+        db::reserve_code(&conn, "live", device_id, &at(600)).unwrap();
+        db::reserve_code(&conn, "used", device_id, &at(600)).unwrap();
+        conn.execute(
+            "UPDATE device_registration_codes SET used = 1 WHERE code = 'used'",
+            [],
+        )
+        .unwrap();
+        assert_eq!(db::clear_expired_codes(&conn).unwrap(), 2);
+        let left: Vec<String> = conn
+            .prepare("SELECT code FROM device_registration_codes")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(left, vec!["live"]);
+
+        let command_id = db::create_command(
+            &conn,
+            device_id,
+            &db::NewCommand {
+                input: "x",
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(revoke_device(&conn, "no-such-device").is_err());
+        let revoked = revoke_device(&conn, &device.device_id).unwrap();
+        assert_eq!(revoked.id, device.id);
+        let devices = db::list_devices(&conn).unwrap();
+        assert!(devices[0].revoked_at.is_some());
+        // Revoking keeps the device's commands.
+        assert!(db::get_command(&conn, command_id).unwrap().is_some());
+        assert!(revoke_device(&conn, &device.device_id).is_err());
+    }
+}
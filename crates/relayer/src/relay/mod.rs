@@ -1,10 +1,11 @@
 //! WebSocket relay state and broadcast.
 
 use tokio::sync::broadcast;
+use uuid::Uuid;
 
 use shared::{
-    WsCommandNewPayload, WsCommandUpdatePayload, WsFileReadRequestPayload,
-    WsFileSearchRequestPayload,
+    WsCommandCancelPayload, WsCommandNewPayload, WsCommandOutputPayload, WsCommandUpdatePayload,
+    WsFileReadRequestPayload, WsFileSearchRequestPayload, WsWorkflowUpdatePayload,
 };
 
 /// Message to broadcast to WebSocket clients.
@@ -12,8 +13,17 @@ use shared::{
 pub enum BroadcastMessage {
     CommandNew(WsCommandNewPayload),
     CommandUpdate(WsCommandUpdatePayload),
+    CommandOutput(WsCommandOutputPayload),
+    CommandCancel(WsCommandCancelPayload),
+    WorkflowUpdate(WsWorkflowUpdatePayload),
     FileReadRequest(WsFileReadRequestPayload),
     FileSearchRequest(WsFileSearchRequestPayload),
+    /// Not sent to clients: closes the revoked device's sockets.
+    DeviceRevoked(Uuid),
+    /// Not sent to clients: closes executor sockets authenticated with the revoked key.
+    ExecutorKeyRevoked(Uuid),
+    /// Not sent to clients: closes sockets authenticated with the revoked session's JWT.
+    SessionRevoked(Uuid),
 }
 
 /// Relay state: broadcast channel for WebSocket messages.
@@ -36,3 +46,9 @@ impl RelayState {
         let _ = self.tx.send(msg);
     }
 }
+
+impl Default for RelayState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
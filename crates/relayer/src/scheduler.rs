@@ -0,0 +1,99 @@
+//! Scheduled commands. The scheduler fires due schedules once per tick: each firing sends the
+//! schedule's command the same way `POST /api/commands` does, unless the schedule's previous
+//! command is still active, then moves `next_run_at` to the next cron occurrence. Occurrences
+//! missed while the relayer was down are not caught up; an overdue schedule fires once.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use croner::Cron;
+use shared::{CreateCommandRequest, TemplateId};
+
+use crate::api::{create_and_send_command, AppState, CommandOrigin};
+use crate::db;
+
+/// How often due schedules are checked. Cron has minute resolution.
+const TICK: Duration = Duration::from_secs(15);
+
+/// Parse a 5-field cron expression (or a nickname like `@daily`), evaluated in UTC.
+pub fn parse_cron(expr: &str) -> Result<Cron, String> {
+    Cron::new(expr.trim()).parse().map_err(|e| e.to_string())
+}
+
+/// First occurrence of `cron` strictly after `after`, as ISO8601.
+pub fn next_run_at(cron: &Cron, after: DateTime<Utc>) -> anyhow::Result<String> {
+    Ok(cron
+        .find_next_occurrence(&after, false)?
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string())
+}
+
+/// Fire the schedules due at `now`. Returns how many commands were created.
+pub fn fire_due_schedules(state: &AppState, now: DateTime<Utc>) -> anyhow::Result<usize> {
+    let now_iso = now.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let conn = state.db.0.lock().unwrap();
+    let mut fired = 0;
+    for schedule in db::due_schedules(&conn, &now_iso)? {
+        // Without a further occurrence the schedule stays enabled but won't fire until edited.
+        let next = parse_cron(&schedule.cron)
+            .map_err(anyhow::Error::msg)
+            .and_then(|cron| next_run_at(&cron, now));
+        let next = match next {
+            Ok(next) => Some(next),
+            Err(e) => {
+                tracing::warn!(schedule_id = %schedule.id, err = %e, "schedule has no next run");
+                None
+            }
+        };
+        if db::schedule_has_active_command(&conn, schedule.id)? {
+            tracing::info!(schedule_id = %schedule.id, "previous run still active, skipping");
+            db::record_schedule_run(&conn, schedule.id, next.as_deref(), None)?;
+            continue;
+        }
+        let req = CreateCommandRequest {
+            input: schedule.input.clone(),
+            repo_path: schedule.repo_path.clone(),
+            context_mode: schedule.context_mode.as_deref().and_then(TemplateId::parse),
+            translator_model: schedule.translator_model.clone(),
+            workload_model: schedule.workload_model.clone(),
+            cursor_chat_id: None,
+            max_runtime_secs: schedule
+                .max_runtime_secs
+                .and_then(|s| u64::try_from(s).ok()),
+            chat_id: None,
+            review_prompt: false,
+            idempotency_key: None,
+        };
+        match create_and_send_command(
+            &conn,
+            &state.relay,
+            schedule.device_id,
+            schedule.admin_id,
+            &req,
+            CommandOrigin::Schedule(schedule.id),
+        ) {
+            Ok(cmd) => {
+                tracing::info!(schedule_id = %schedule.id, cmd_id = %cmd.id, "schedule fired");
+                db::record_schedule_run(&conn, schedule.id, next.as_deref(), Some(&now_iso))?;
+                fired += 1;
+            }
+            Err((_, e)) => {
+                // E.g. its template was deleted; try again at the next occurrence.
+                tracing::warn!(schedule_id = %schedule.id, err = %e, "schedule failed to fire");
+                db::record_schedule_run(&conn, schedule.id, next.as_deref(), None)?;
+            }
+        }
+    }
+    Ok(fired)
+}
+
+/// Run `fire_due_schedules` every `TICK`.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(TICK);
+    loop {
+        interval.tick().await;
+        if let Err(e) = fire_due_schedules(&state, Utc::now()) {
+            tracing::error!(err = %e, "scheduler failed");
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Workflow runs. The relayer sends a run's steps as commands one at a time, all in the run's
+//! chat so they share one Cursor chat. When a step's command finishes, `on_command_finished`
+//! starts the next step, pauses before it for approval, or ends the run.
+
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+use shared::{CommandStatus, WorkflowRunStatus, WorkflowStep, WsWorkflowUpdatePayload};
+use uuid::Uuid;
+
+use crate::api::command_new_payload;
+use crate::db;
+use crate::relay::{BroadcastMessage, RelayState};
+
+/// Command input for a step: its `input` with `{input}` replaced by the run's input.
+pub fn step_input(step: &WorkflowStep, run_input: &str) -> String {
+    step.input.replace("{input}", run_input)
+}
+
+/// Create the command for the run's current step and send it to the executor. It resumes the
+/// Cursor chat the earlier steps recorded on the run's chat.
+pub fn start_current_step(
+    conn: &Connection,
+    relay: &RelayState,
+    run: &db::WorkflowRunRow,
+) -> Result<Uuid> {
+    let step = usize::try_from(run.current_step)
+        .ok()
+        .and_then(|i| run.steps.get(i))
+        .ok_or_else(|| anyhow!("workflow run {} has no step {}", run.id, run.current_step))?;
+    let chat = match run.chat_id {
+        Some(chat_id) => db::get_chat(conn, chat_id, Some(run.admin_id))?,
+        None => None,
+    };
+    let input = step_input(step, &run.input);
+    let id = db::create_command(
+        conn,
+        run.device_id,
+        &db::NewCommand {
+            input: &input,
+            repo_path: run.repo_path.as_deref(),
+            context_mode: step.context_mode.as_ref().map(|t| t.as_str()),
+            translator_model: run.translator_model.as_deref(),
+            workload_model: run.workload_model.as_deref(),
+            cursor_chat_id: chat.as_ref().and_then(|c| c.cursor_chat_id.as_deref()),
+            chat_id: chat.as_ref().map(|c| c.id),
+            workflow_step: Some((run.id, run.current_step)),
+            ..Default::default()
+        },
+    )?;
+    if let Some(chat) = &chat {
+        db::record_chat_turn(conn, chat.id, &input)?;
+    }
+    let cmd = db::get_command(conn, id)?.ok_or_else(|| anyhow!("command not found"))?;
+    relay.broadcast(BroadcastMessage::CommandNew(command_new_payload(
+        conn, &cmd,
+    )));
+    Ok(id)
+}
+
+/// Notify controllers of a run's current status and step.
+pub fn broadcast_run(conn: &Connection, relay: &RelayState, run_id: Uuid) -> Result<()> {
+    if let Some(run) = db::get_workflow_run(conn, run_id)? {
+        relay.broadcast(BroadcastMessage::WorkflowUpdate(WsWorkflowUpdatePayload {
+            id: run.id,
+            status: WorkflowRunStatus::parse(&run.status).unwrap_or(WorkflowRunStatus::Running),
+            current_step: u32::try_from(run.current_step).unwrap_or(0),
+            updated_at: run.updated_at,
+        }));
+    }
+    Ok(())
+}
+
+/// Advance the workflow run of a command that reached a terminal status: start the next step
+/// (or wait for its approval) when it is done, end the run when it failed or was cancelled or
+/// was the last step. No-op for commands outside a workflow, commands that have not finished,
+/// and steps the run has already moved past.
+pub fn on_command_finished(conn: &Connection, relay: &RelayState, command_id: Uuid) -> Result<()> {
+    let Some((run_id, step)) = db::command_workflow_step(conn, command_id)? else {
+        return Ok(());
+    };
+    let Some(status) = db::get_command(conn, command_id)?
+        .and_then(|c| CommandStatus::parse(&c.status))
+        .filter(CommandStatus::is_terminal)
+    else {
+        return Ok(());
+    };
+    let Some(run) = db::get_workflow_run(conn, run_id)? else {
+        return Ok(());
+    };
+    let running = (WorkflowRunStatus::Running.as_str(), step);
+    let next = step + 1;
+    let to = match status {
+        CommandStatus::Done => match usize::try_from(next).ok().and_then(|i| run.steps.get(i)) {
+            None => (WorkflowRunStatus::Done, step),
+            Some(s) if s.approval => (WorkflowRunStatus::AwaitingApproval, next),
+            Some(_) => (WorkflowRunStatus::Running, next),
+        },
+        CommandStatus::Cancelled => (WorkflowRunStatus::Cancelled, step),
+        _ => (WorkflowRunStatus::Failed, step),
+    };
+    if !db::transition_workflow_run(conn, run_id, running, (to.0.as_str(), to.1))? {
+        return Ok(());
+    }
+    tracing::info!(run_id = %run_id, step = to.1, status = to.0.as_str(), "workflow run advanced");
+    if to.0 == WorkflowRunStatus::Running {
+        let run = db::WorkflowRunRow {
+            current_step: next,
+            ..run
+        };
+        start_current_step(conn, relay, &run)?;
+    }
+    broadcast_run(conn, relay, run_id)
+}
@@ -3,14 +3,28 @@
 mod models;
 
 // Explicit re-exports (avoids rust-analyzer issues with `pub use models::*`)
-pub use models::ws_types;
+pub use models::{failure_reasons, phase_outcomes, phases, status_actors, tool_kinds, ws_types};
 pub use models::{
-    AddRepoRequest, BootstrapDeviceResponse, ChatHistoryEntry, CommandResponse, CommandStatus,
-    CreateCommandRequest, DeviceRole, FileReadResponseRequest, FileSearchMatch,
-    FileSearchResponseRequest, LoginRequest, LoginResponse, RefreshRequest, RefreshResponse,
-    RegisterDeviceRequest, RegisterDeviceResponse, RepoResponse, ReserveCodeRequest,
-    ReserveCodeResponse, SetupRequest, SetupResponse, SyncModelsRequest, SyncReposRequest,
-    UpdateCommandRequest, VerifyBootstrapRequest, VerifyBootstrapResponse, WsAuthPayload,
-    WsCommandAckPayload, WsCommandNewPayload, WsCommandResultPayload, WsCommandUpdatePayload,
-    WsEnvelope, WsFileReadRequestPayload, WsFileSearchRequestPayload,
+    AcceptInvitationRequest, AcceptInvitationResponse, AccessTokenResponse, AddRepoRequest,
+    AppendOutputEventsRequest, BootstrapDeviceResponse, ChatHistoryEntry, ChatResponse,
+    CommandListResponse, CommandPhase, CommandResponse, CommandSearchHit, CommandStatus,
+    CommandStatusChange, CreateAccessTokenRequest, CreateChatRequest, CreateCommandRequest,
+    CreateInvitationRequest, CreateScheduleRequest, CreateTemplateRequest, CreateWorkflowRequest,
+    CreatedAccessTokenResponse, CreatedInvitationResponse, DeviceResponse, DeviceRole,
+    ExecutorKeyResponse, FileReadResponseRequest, FileSearchMatch, FileSearchResponseRequest,
+    InvitationResponse, IssueExecutorKeyRequest, IssuedExecutorKeyResponse, LoginRequest,
+    LoginResponse, OutputEvent, OutputEventKind, OutputEventResponse, PasskeyAssertion,
+    PasskeyLoginOptions, PasskeyRegisterOptions, PasskeyResponse, PromptReviewAction,
+    RecoveryCodesResponse, RefreshRequest, RefreshResponse, RegisterDeviceRequest,
+    RegisterDeviceResponse, RegisterExecutorRequest, RegisterPasskeyRequest, RepoResponse,
+    RerunCommandRequest, ReserveCodeRequest, ReserveCodeResponse, ReviewPromptRequest,
+    RevokeSessionsResponse, ScheduleResponse, SessionResponse, SetupRequest, SetupResponse,
+    StartWorkflowRequest, SyncModelsRequest, SyncReposRequest, TemplateId, TemplateResponse,
+    TokenScope, ToolCallRecord, UpdateChatRequest, UpdateCommandRequest, UpdateDeviceRequest,
+    UpdateScheduleRequest, UpdateTemplateRequest, UpdateUserRequest, UpdateWorkflowRequest,
+    UsageStats, UserResponse, UserRole, VerifyBootstrapRequest, VerifyBootstrapResponse,
+    WorkflowResponse, WorkflowRunResponse, WorkflowRunStatus, WorkflowRunStepResponse,
+    WorkflowStep, WsAuthPayload, WsCommandAckPayload, WsCommandCancelPayload, WsCommandNewPayload,
+    WsCommandOutputPayload, WsCommandResultPayload, WsCommandUpdatePayload, WsEnvelope,
+    WsFileReadRequestPayload, WsFileSearchRequestPayload, WsWorkflowUpdatePayload,
 };
@@ -11,12 +11,48 @@ pub enum DeviceRole {
     Controller,
 }
 
+/// A user's role on the relayer. Owners see and manage everything, including users,
+/// invitations, the executor and repos. Members see and change only their own commands,
+/// chats, schedules and workflow runs. Viewers can read everyone's but change nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserRole {
+    Owner,
+    Member,
+    Viewer,
+}
+
+impl UserRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Member => "member",
+            Self::Viewer => "viewer",
+        }
+    }
+
+    /// Parse a stored role string. Returns None for unknown values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "owner" => Some(Self::Owner),
+            "member" => Some(Self::Member),
+            "viewer" => Some(Self::Viewer),
+            _ => None,
+        }
+    }
+}
+
 /// Command status in the relay pipeline.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum CommandStatus {
     Pending,
+    /// Received by the executor, waiting for a free slot or for its repo/chat to be idle.
+    Queued,
     Running,
+    /// Translated with `review_prompt` set; waits for the controller to approve, edit or
+    /// reject the proposed prompt before the workload run.
+    AwaitingApproval,
     Done,
     Failed,
     Cancelled,
@@ -26,12 +62,95 @@ impl CommandStatus {
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Pending => "pending",
+            Self::Queued => "queued",
             Self::Running => "running",
+            Self::AwaitingApproval => "awaiting_approval",
             Self::Done => "done",
             Self::Failed => "failed",
             Self::Cancelled => "cancelled",
         }
     }
+
+    /// Parse a stored status string. Returns None for unknown values.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "queued" => Some(Self::Queued),
+            "running" => Some(Self::Running),
+            "awaiting_approval" => Some(Self::AwaitingApproval),
+            "done" => Some(Self::Done),
+            "failed" => Some(Self::Failed),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// True for statuses that end a command's lifecycle (done, failed, cancelled).
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Done | Self::Failed | Self::Cancelled)
+    }
+
+    /// Whether an update may move a command from `self` to `to`. Commands only move forward
+    /// (pending → queued → running → awaiting_approval → done/failed/cancelled, skipping
+    /// steps allowed) and may stay put while not terminal. Approving a reviewed prompt is the
+    /// one move back (awaiting_approval → pending) and is not an update.
+    pub fn can_transition_to(&self, to: Self) -> bool {
+        let rank = |s: &Self| match s {
+            Self::Pending => 0,
+            Self::Queued => 1,
+            Self::Running => 2,
+            Self::AwaitingApproval => 3,
+            Self::Done | Self::Failed | Self::Cancelled => 4,
+        };
+        !self.is_terminal() && (*self == to || rank(&to) > rank(self))
+    }
+}
+
+/// Template id: 1-64 chars of lowercase ASCII letters, digits, `_` and `-`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TemplateId(String);
+
+impl TemplateId {
+    pub const MAX_LEN: usize = 64;
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let valid = !s.is_empty()
+            && s.len() <= Self::MAX_LEN
+            && s.bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_' || b == b'-');
+        valid.then(|| Self(s.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for TemplateId {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s).ok_or_else(|| {
+            format!(
+                "invalid template id {:?}: use 1-{} lowercase letters, digits, '_' or '-'",
+                s,
+                Self::MAX_LEN
+            )
+        })
+    }
+}
+
+impl From<TemplateId> for String {
+    fn from(id: TemplateId) -> Self {
+        id.0
+    }
+}
+
+impl std::fmt::Display for TemplateId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 /// Create command request.
@@ -39,11 +158,46 @@ impl CommandStatus {
 pub struct CreateCommandRequest {
     pub input: String,
     pub repo_path: Option<String>,
-    pub context_mode: Option<String>,
+    /// Template to run the command with (see `/api/templates`); free-form when unset.
+    pub context_mode: Option<TemplateId>,
     pub translator_model: Option<String>,
     pub workload_model: Option<String>,
     /// When set, executor resumes this Cursor chat instead of creating a new one.
     pub cursor_chat_id: Option<String>,
+    /// Kill the run after this many seconds. Defaults to the executor's `MAX_RUNTIME_SECS`.
+    #[serde(default)]
+    pub max_runtime_secs: Option<u64>,
+    /// Chat this turn belongs to. Without it the command joins the chat of `cursor_chat_id`,
+    /// or starts a new chat. Unset repo/models fall back to the chat's defaults.
+    #[serde(default)]
+    pub chat_id: Option<Uuid>,
+    /// Stop after translation so the prompt can be reviewed (`POST /api/commands/{id}/review`)
+    /// before the workload run. Requires `context_mode`.
+    #[serde(default)]
+    pub review_prompt: bool,
+    /// Client-chosen key; a retry with the same key returns the original command instead of
+    /// creating another. The `Idempotency-Key` header takes precedence.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+/// Re-run a finished command (`POST /api/commands/{id}/rerun`). The new command copies the
+/// original's input, repo, template, models and chat; set fields override them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RerunCommandRequest {
+    #[serde(default)]
+    pub repo_path: Option<String>,
+    #[serde(default)]
+    pub context_mode: Option<TemplateId>,
+    #[serde(default)]
+    pub translator_model: Option<String>,
+    #[serde(default)]
+    pub workload_model: Option<String>,
+    #[serde(default)]
+    pub max_runtime_secs: Option<u64>,
+    /// Start a new chat (and Cursor chat) instead of resuming the original's.
+    #[serde(default)]
+    pub fresh_chat: bool,
 }
 
 /// Command response (full details).
@@ -60,10 +214,238 @@ pub struct CommandResponse {
     pub translator_model: Option<String>,
     pub workload_model: Option<String>,
     pub cursor_chat_id: Option<String>,
+    /// While queued on the executor: commands that will start before this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<u32>,
+    #[serde(default)]
+    pub max_runtime_secs: Option<u64>,
+    /// Why a `failed` command failed (see `failure_reasons`).
+    #[serde(default)]
+    pub failure_reason: Option<String>,
+    #[serde(default)]
+    pub chat_id: Option<Uuid>,
+    /// Total wall-clock time of the recorded phases, once the run reported them.
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// Per-phase timing and usage; only on `GET /api/commands/{id}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phases: Option<Vec<CommandPhase>>,
+    /// Status changes, oldest first; only on `GET /api/commands/{id}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_history: Option<Vec<CommandStatusChange>>,
+    #[serde(default)]
+    pub review_prompt: bool,
+    /// Translated prompt reported with `awaiting_approval`.
+    #[serde(default)]
+    pub proposed_prompt: Option<String>,
+    /// Prompt the workload runs with once approved (the proposed prompt, or the edit).
+    #[serde(default)]
+    pub approved_prompt: Option<String>,
+    /// Schedule that created this command, if any.
+    #[serde(default)]
+    pub schedule_id: Option<Uuid>,
+    /// Command this one re-runs (`POST /api/commands/{id}/rerun`).
+    #[serde(default)]
+    pub rerun_of: Option<Uuid>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// One page of `GET /api/commands`, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandListResponse {
+    pub commands: Vec<CommandResponse>,
+    /// Opaque cursor for the next (older) page; `None` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// A `GET /api/commands/search` hit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandSearchHit {
+    pub command: CommandResponse,
+    /// Excerpt of the best-matching field; matches are wrapped in `<mark>`…`</mark>`, the rest
+    /// is raw command text (escape before rendering as HTML).
+    pub snippet: String,
+    /// bm25 score; lower is a better match. Hits are sorted by it.
+    pub rank: f64,
+}
+
+/// Chat (conversation) response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub id: Uuid,
+    pub title: String,
+    pub repo_path: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+    pub cursor_chat_id: Option<String>,
+    pub archived: bool,
+    pub last_activity_at: String,
     pub created_at: String,
     pub updated_at: String,
 }
 
+/// Create chat request. Without a title the chat is named after its first turn.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateChatRequest {
+    pub title: Option<String>,
+    pub repo_path: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+}
+
+/// Update chat request. Omitted fields are unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateChatRequest {
+    pub title: Option<String>,
+    pub repo_path: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+    pub archived: Option<bool>,
+}
+
+/// Machine-readable `failure_reason` values for failed commands.
+pub mod failure_reasons {
+    /// The agent exited with an error.
+    pub const AGENT_ERROR: &str = "agent_error";
+    /// The run exceeded its `max_runtime_secs` and was killed.
+    pub const TIMEOUT: &str = "timeout";
+    /// The agent produced no output for the executor's idle timeout and was killed.
+    pub const NO_OUTPUT: &str = "no_output";
+    /// The executor stopped reporting progress (crashed or disconnected mid-run).
+    pub const STALE: &str = "stale";
+}
+
+/// Who moved a command to a status, in its status history.
+pub mod status_actors {
+    /// A controller request (create, cancel, prompt review).
+    pub const CONTROLLER: &str = "controller";
+    /// An executor status update.
+    pub const EXECUTOR: &str = "executor";
+    /// The relayer itself (stale-run reaper, schedules, workflow steps).
+    pub const RELAYER: &str = "relayer";
+}
+
+/// One status change of a command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandStatusChange {
+    /// `None` when the command was created.
+    pub from: Option<CommandStatus>,
+    pub to: CommandStatus,
+    /// See `status_actors`.
+    pub actor: String,
+    /// Executor revision of the update that made the change.
+    #[serde(default)]
+    pub revision: Option<u64>,
+    pub at: String,
+}
+
+/// Tool call categories, for filtering a command's tool-call timeline.
+pub mod tool_kinds {
+    /// Shell command (`target` is the command line).
+    pub const SHELL: &str = "shell";
+    /// File read (`target` is the path).
+    pub const READ: &str = "read";
+    /// File write or edit (`target` is the path).
+    pub const WRITE: &str = "write";
+    /// Content search (`target` is the pattern).
+    pub const SEARCH: &str = "search";
+    /// Directory listing (`target` is the path).
+    pub const LIST: &str = "list";
+    /// Anything else.
+    pub const OTHER: &str = "other";
+}
+
+/// One tool call made by the agent during a command run. Sent by the executor when the call
+/// starts and again when it completes (upserted by `call_id`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub call_id: String,
+    /// Order in which calls started within the run, from 1.
+    pub seq: u32,
+    /// Tool name as reported by the agent (e.g. `bash`, `editFile`).
+    pub tool: String,
+    /// See `tool_kinds`.
+    pub kind: String,
+    /// Command line, path or pattern, depending on `kind`.
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub args: Option<serde_json::Value>,
+    pub started_at: String,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    #[serde(default)]
+    pub exit_code: Option<i64>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Tool result, truncated by the executor.
+    #[serde(default)]
+    pub result: Option<String>,
+}
+
+/// Pipeline phases of a command run, as recorded in `CommandPhase::phase`.
+pub mod phases {
+    /// Translator agent turns the user input into a prompt.
+    pub const TRANSLATE: &str = "translate";
+    /// `agent create-chat` for a new Cursor chat session.
+    pub const CREATE_CHAT: &str = "create_chat";
+    /// Workload agent run in the repo.
+    pub const RUN: &str = "run";
+    /// Summarizer agent run over the output.
+    pub const SUMMARIZE: &str = "summarize";
+}
+
+/// `CommandPhase::outcome` values.
+pub mod phase_outcomes {
+    /// The phase finished.
+    pub const OK: &str = "ok";
+    /// The phase failed with an error.
+    pub const FAILED: &str = "failed";
+    /// The run was cancelled, timed out or went silent during the phase.
+    pub const CANCELLED: &str = "cancelled";
+}
+
+/// Wall-clock time, model and usage of one phase of a command run. Token and cost fields are
+/// only set when the agent reports them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandPhase {
+    /// See `phases`.
+    pub phase: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub input_tokens: Option<u64>,
+    #[serde(default)]
+    pub output_tokens: Option<u64>,
+    #[serde(default)]
+    pub cache_read_tokens: Option<u64>,
+    #[serde(default)]
+    pub cache_write_tokens: Option<u64>,
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+    /// See `phase_outcomes`; `None` from executors that predate it.
+    #[serde(default)]
+    pub outcome: Option<String>,
+}
+
+/// Aggregated usage for one group (model, repo or day) in `GET /api/stats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageStats {
+    /// Model name, repo path or day (`YYYY-MM-DD`); `None` for phases without one.
+    pub key: Option<String>,
+    pub commands: u64,
+    pub duration_ms: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost_usd: f64,
+}
+
 /// Update command request (executor).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateCommandRequest {
@@ -71,6 +453,94 @@ pub struct UpdateCommandRequest {
     pub output: Option<String>,
     pub summary: Option<String>,
     pub cursor_chat_id: Option<String>,
+    /// Queue position when status is `queued`. Cleared on any other status.
+    #[serde(default)]
+    pub queue_position: Option<u32>,
+    /// Set with status `failed` (see `failure_reasons`).
+    #[serde(default)]
+    pub failure_reason: Option<String>,
+    /// Per-phase timing and usage, sent with the final status. Replaces earlier reports of
+    /// the same phases.
+    #[serde(default)]
+    pub phases: Option<Vec<CommandPhase>>,
+    /// Set with status `awaiting_approval`: the translated prompt to review.
+    #[serde(default)]
+    pub proposed_prompt: Option<String>,
+    /// Increases with every update the executor sends for a command; updates not newer than
+    /// the last applied one are rejected as stale.
+    #[serde(default)]
+    pub revision: Option<u64>,
+}
+
+/// Controller decision on a command's proposed prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptReviewAction {
+    /// Run the workload with the proposed prompt.
+    Approve,
+    /// Run the workload with `prompt` instead.
+    Edit,
+    /// Cancel the command without running the workload.
+    Reject,
+}
+
+/// `POST /api/commands/{id}/review` body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewPromptRequest {
+    pub action: PromptReviewAction,
+    /// Replacement prompt; required for `edit`.
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+/// How an output event changes the command's accumulated output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputEventKind {
+    /// Content is appended to the output so far.
+    Append,
+    /// Content replaces the output so far (e.g. final result, or resync after a lost delta).
+    Replace,
+}
+
+impl OutputEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Append => "append",
+            Self::Replace => "replace",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "append" => Some(Self::Append),
+            "replace" => Some(Self::Replace),
+            _ => None,
+        }
+    }
+}
+
+/// Single output delta. `seq` is assigned by the sender and strictly increases per command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputEvent {
+    pub seq: u64,
+    pub kind: OutputEventKind,
+    pub content: String,
+}
+
+/// Append output events request (executor).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendOutputEventsRequest {
+    pub events: Vec<OutputEvent>,
+}
+
+/// Stored output event, as returned by `GET /api/commands/{id}/events`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputEventResponse {
+    pub seq: u64,
+    pub kind: OutputEventKind,
+    pub content: String,
+    pub created_at: String,
 }
 
 // --- Auth DTOs ---
@@ -88,32 +558,120 @@ pub struct SetupRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SetupResponse {
     pub totp_secret: String,
+    /// One-time codes that log in in place of a TOTP code. Shown once.
+    pub recovery_codes: Vec<String>,
 }
 
-/// Login request.
+/// Login request. Takes `totp_code`, or `recovery_code` when the authenticator is lost, or
+/// `passkey`, which also identifies the device so `device_api_key` may be left empty.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoginRequest {
+    #[serde(default)]
     pub device_api_key: String,
     pub password: String,
+    #[serde(default)]
     pub totp_code: String,
+    /// One-time recovery code; used instead of `totp_code` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_code: Option<String>,
+    /// Passkey assertion over a challenge from `POST /api/auth/webauthn/login/options`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passkey: Option<PasskeyAssertion>,
+}
+
+/// A `navigator.credentials.get()` response. Binary values are base64url.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyAssertion {
+    pub credential_id: String,
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
 }
 
-/// Login response.
+/// Options for `navigator.credentials.get()` (`POST /api/auth/webauthn/login/options`).
+/// The challenge is single use and short-lived.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyLoginOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout_ms: u64,
+}
+
+/// Options for `navigator.credentials.create()` (`POST /api/auth/webauthn/register/options`).
+/// Binary values are base64url.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyRegisterOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    /// User handle: the controller device's id.
+    pub user_id: String,
+    pub user_name: String,
+    /// COSE algorithms accepted; only ES256 (-7).
+    pub algorithms: Vec<i64>,
+    /// Credential ids already registered on this device.
+    pub exclude_credentials: Vec<String>,
+    pub timeout_ms: u64,
+}
+
+/// Register passkey request (`POST /api/auth/webauthn/register`): the
+/// `navigator.credentials.create()` response, base64url.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterPasskeyRequest {
+    pub client_data_json: String,
+    pub attestation_object: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Passkey response. `id` is what `/api/auth/webauthn/credentials/{id}` takes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyResponse {
+    pub id: Uuid,
+    pub credential_id: String,
+    pub name: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+/// Login response. `token` is the JWT for API calls; `refresh_token` gets the next one.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
 }
 
-/// Refresh token request (old JWT may be expired but within grace period).
+/// Refresh token request. Each refresh token works once; reusing one revokes its session.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RefreshRequest {
-    pub token: String,
+    pub refresh_token: String,
 }
 
-/// Refresh token response.
+/// Refresh token response: a new JWT and the refresh token that replaces the one sent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RefreshResponse {
     pub token: String,
+    pub refresh_token: String,
+}
+
+/// Session response (`GET /api/sessions`). `id` is what `/api/sessions/{id}` takes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    /// The controller device the session was opened on (`DeviceResponse::id`).
+    pub device_id: Uuid,
+    pub device_name: Option<String>,
+    pub created_at: String,
+    pub last_used_at: String,
+    /// When the session ends unless refreshed.
+    pub expires_at: String,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
+
+/// Revoke-all response (`DELETE /api/sessions`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevokeSessionsResponse {
+    pub revoked: usize,
 }
 
 /// Reserve code request.
@@ -158,6 +716,257 @@ pub struct RegisterDeviceRequest {
 pub struct RegisterDeviceResponse {
     pub device_api_key: String,
     pub totp_secret: String,
+    /// A new set of recovery codes if the admin had none left, else empty. Shown once.
+    pub recovery_codes: Vec<String>,
+}
+
+/// User response (`GET /api/users`, `GET /api/users/me`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserResponse {
+    pub id: Uuid,
+    pub username: String,
+    pub role: UserRole,
+    pub created_at: String,
+    /// Set when an owner removed the user; their devices are revoked.
+    pub disabled_at: Option<String>,
+    /// Whether this is the user making the request.
+    pub current: bool,
+}
+
+/// Change a user's role (`PATCH /api/users/{id}`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateUserRequest {
+    pub role: UserRole,
+}
+
+/// Create invitation request (`POST /api/invitations`). `expires_in_secs` defaults to 7 days.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateInvitationRequest {
+    pub role: UserRole,
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+/// Invitation metadata; the code is only returned when created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvitationResponse {
+    pub id: Uuid,
+    pub role: UserRole,
+    pub created_at: String,
+    pub expires_at: String,
+    pub accepted_at: Option<String>,
+    /// The user who accepted it.
+    pub accepted_by: Option<Uuid>,
+    pub revoked_at: Option<String>,
+}
+
+/// Newly created invitation. `code` is only ever returned here; the relayer stores a hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedInvitationResponse {
+    pub code: String,
+    pub invitation: InvitationResponse,
+}
+
+/// Accept invitation request (`POST /api/auth/accept-invitation`): creates the user and their
+/// first controller device. `password` is client-hashed, as for setup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptInvitationRequest {
+    pub code: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Accept invitation response: the new user's device key, TOTP secret and recovery codes.
+/// Shown once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptInvitationResponse {
+    pub device_api_key: String,
+    pub totp_secret: String,
+    pub recovery_codes: Vec<String>,
+}
+
+/// Recovery codes response (`POST /api/auth/recovery-codes`): a new set replacing the old one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Device response. `id` is what `/api/devices/{id}` takes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceResponse {
+    pub id: Uuid,
+    pub device_id: String,
+    pub name: Option<String>,
+    pub role: DeviceRole,
+    pub registered_at: String,
+    /// Last authenticated request or WebSocket connect.
+    pub last_seen_at: String,
+    /// Whether this is the device making the request.
+    pub current: bool,
+}
+
+/// Rename device request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateDeviceRequest {
+    pub name: String,
+}
+
+/// Register executor request. Creates the admin's executor device and its first key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegisterExecutorRequest {
+    pub name: Option<String>,
+}
+
+/// Issue executor key request. The executor's other keys keep working for `grace_secs`
+/// (default 24h, at most 30 days; 0 expires them now) so it can be switched over first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IssueExecutorKeyRequest {
+    pub label: Option<String>,
+    pub grace_secs: Option<u64>,
+}
+
+/// Executor key metadata. `prefix` is the key's first characters, to tell keys apart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutorKeyResponse {
+    pub id: Uuid,
+    pub executor_id: Uuid,
+    pub prefix: String,
+    pub label: Option<String>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+/// Newly issued executor key. `api_key` is only ever returned here; the relayer stores a hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuedExecutorKeyResponse {
+    pub api_key: String,
+    pub key: ExecutorKeyResponse,
+}
+
+/// What a personal access token may do. Each API route a token can call needs one scope;
+/// routes managing devices, sessions, passkeys, executors and tokens take none, so tokens
+/// can't call them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TokenScope {
+    /// List, search and read commands with their output and tool calls.
+    #[serde(rename = "commands:read")]
+    CommandsRead,
+    /// Create and re-run commands.
+    #[serde(rename = "commands:create")]
+    CommandsCreate,
+    /// Cancel, review and delete commands.
+    #[serde(rename = "commands:write")]
+    CommandsWrite,
+    #[serde(rename = "chats:read")]
+    ChatsRead,
+    #[serde(rename = "chats:write")]
+    ChatsWrite,
+    #[serde(rename = "templates:read")]
+    TemplatesRead,
+    #[serde(rename = "templates:write")]
+    TemplatesWrite,
+    /// Read workflows and their runs.
+    #[serde(rename = "workflows:read")]
+    WorkflowsRead,
+    #[serde(rename = "workflows:write")]
+    WorkflowsWrite,
+    /// Start, approve and cancel workflow runs.
+    #[serde(rename = "workflows:run")]
+    WorkflowsRun,
+    #[serde(rename = "schedules:read")]
+    SchedulesRead,
+    #[serde(rename = "schedules:write")]
+    SchedulesWrite,
+    /// List repos and models.
+    #[serde(rename = "repos:read")]
+    ReposRead,
+    #[serde(rename = "repos:write")]
+    ReposWrite,
+    /// Read and search files in repos through the executor.
+    #[serde(rename = "files:read")]
+    FilesRead,
+    #[serde(rename = "stats:read")]
+    StatsRead,
+}
+
+impl TokenScope {
+    pub const ALL: [Self; 16] = [
+        Self::CommandsRead,
+        Self::CommandsCreate,
+        Self::CommandsWrite,
+        Self::ChatsRead,
+        Self::ChatsWrite,
+        Self::TemplatesRead,
+        Self::TemplatesWrite,
+        Self::WorkflowsRead,
+        Self::WorkflowsWrite,
+        Self::WorkflowsRun,
+        Self::SchedulesRead,
+        Self::SchedulesWrite,
+        Self::ReposRead,
+        Self::ReposWrite,
+        Self::FilesRead,
+        Self::StatsRead,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CommandsRead => "commands:read",
+            Self::CommandsCreate => "commands:create",
+            Self::CommandsWrite => "commands:write",
+            Self::ChatsRead => "chats:read",
+            Self::ChatsWrite => "chats:write",
+            Self::TemplatesRead => "templates:read",
+            Self::TemplatesWrite => "templates:write",
+            Self::WorkflowsRead => "workflows:read",
+            Self::WorkflowsWrite => "workflows:write",
+            Self::WorkflowsRun => "workflows:run",
+            Self::SchedulesRead => "schedules:read",
+            Self::SchedulesWrite => "schedules:write",
+            Self::ReposRead => "repos:read",
+            Self::ReposWrite => "repos:write",
+            Self::FilesRead => "files:read",
+            Self::StatsRead => "stats:read",
+        }
+    }
+
+    /// Parse a stored scope string. Returns None for unknown values.
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == s)
+    }
+}
+
+/// Create access token request (`POST /api/tokens`). `expires_in_secs` defaults to 90 days;
+/// 0 means the token never expires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateAccessTokenRequest {
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+/// Personal access token metadata. `prefix` is the token's first characters, to tell tokens
+/// apart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessTokenResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+/// Newly created access token. `token` is only ever returned here; the relayer stores a hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedAccessTokenResponse {
+    pub token: String,
+    pub access_token: AccessTokenResponse,
 }
 
 // --- WebSocket envelope ---
@@ -182,6 +991,9 @@ pub mod ws_types {
     pub const COMMAND_UPDATE: &str = "command_update";
     pub const COMMAND_ACK: &str = "command_ack";
     pub const COMMAND_RESULT: &str = "command_result";
+    pub const COMMAND_CANCEL: &str = "command_cancel";
+    pub const COMMAND_OUTPUT: &str = "command_output";
+    pub const WORKFLOW_UPDATE: &str = "workflow_update";
     pub const FILE_READ_REQUEST: &str = "file_read_request";
     pub const FILE_SEARCH_REQUEST: &str = "file_search_request";
     pub const PING: &str = "ping";
@@ -216,6 +1028,22 @@ pub struct WsCommandNewPayload {
     /// Prior turns in this chat (for translator context). Present when resuming.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub chat_history: Option<Vec<ChatHistoryEntry>>,
+    /// Per-command runtime limit; executor default when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_runtime_secs: Option<u64>,
+    /// Body of the `context_mode` template, with variables still unresolved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Stop after translation and report the prompt as `awaiting_approval`.
+    #[serde(default)]
+    pub review_prompt: bool,
+    /// Reviewed prompt to run as-is; translation is skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approved_prompt: Option<String>,
+    /// Last output event seq already stored for the command (from the run before approval);
+    /// the executor's events continue after it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_seq: Option<u64>,
 }
 
 /// command_update payload.
@@ -227,20 +1055,51 @@ pub struct WsCommandUpdatePayload {
     pub summary: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cursor_chat_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proposed_prompt: Option<String>,
     pub updated_at: String,
 }
 
+/// workflow_update payload (relayer → controllers). Sent when a run moves to another step or
+/// status; step commands still send their own `command_update`s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsWorkflowUpdatePayload {
+    pub id: Uuid,
+    pub status: WorkflowRunStatus,
+    pub current_step: u32,
+    pub updated_at: String,
+}
+
+/// command_output payload (relayer → controllers). One stored output event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsCommandOutputPayload {
+    pub id: Uuid,
+    pub seq: u64,
+    pub kind: OutputEventKind,
+    pub content: String,
+}
+
 /// command_ack payload.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WsCommandAckPayload {
     pub id: Uuid,
 }
 
+/// command_cancel payload (relayer → executor). Executor kills the command's agent if running.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsCommandCancelPayload {
+    pub id: Uuid,
+}
+
 /// command_result payload.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WsCommandResultPayload {
     pub id: Uuid,
-    pub status: String, // "done" | "failed"
+    pub status: String, // "done" | "failed" | "cancelled"
     pub output: String,
     pub summary: String,
 }
@@ -288,6 +1147,224 @@ pub struct FileSearchResponseRequest {
     pub error: Option<String>,
 }
 
+/// Template response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateResponse {
+    pub id: TemplateId,
+    pub name: String,
+    pub description: Option<String>,
+    /// Instructions for the translator. `{repo}` and `{next_sprint_number}` are filled in by
+    /// the executor; other braces are left as-is.
+    pub body: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Create template request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTemplateRequest {
+    pub id: TemplateId,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub body: String,
+}
+
+/// Update template request. `None` leaves a field unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateTemplateRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub body: Option<String>,
+}
+
+/// One step of a workflow: a command sent once the previous step is done.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    pub name: String,
+    /// Template for the step's command; free-form when unset.
+    #[serde(default)]
+    pub context_mode: Option<TemplateId>,
+    /// Command input. `{input}` is replaced with the input the run was started with.
+    pub input: String,
+    /// Wait for `POST /api/workflow-runs/{id}/approve` before starting this step.
+    #[serde(default)]
+    pub approval: bool,
+}
+
+/// Workflow response. Workflow ids follow the same rules as template ids.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowResponse {
+    pub id: TemplateId,
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<WorkflowStep>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Create workflow request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWorkflowRequest {
+    pub id: TemplateId,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub steps: Vec<WorkflowStep>,
+}
+
+/// Update workflow request. `None` leaves a field unchanged; runs already started keep the
+/// steps they started with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateWorkflowRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub steps: Option<Vec<WorkflowStep>>,
+}
+
+/// Start a workflow run (`POST /api/workflows/{id}/runs`). Steps run in one chat and one
+/// Cursor chat; unset repo/models fall back to the chat's defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartWorkflowRequest {
+    pub input: String,
+    #[serde(default)]
+    pub repo_path: Option<String>,
+    #[serde(default)]
+    pub translator_model: Option<String>,
+    #[serde(default)]
+    pub workload_model: Option<String>,
+    /// Run in this chat (continuing its Cursor chat) instead of a new one.
+    #[serde(default)]
+    pub chat_id: Option<Uuid>,
+}
+
+/// Workflow run status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowRunStatus {
+    /// The current step's command is pending or running.
+    Running,
+    /// The current step has `approval` set and has not started yet.
+    AwaitingApproval,
+    Done,
+    /// A step's command failed; later steps did not run.
+    Failed,
+    Cancelled,
+}
+
+impl WorkflowRunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::AwaitingApproval => "awaiting_approval",
+            Self::Done => "done",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "running" => Some(Self::Running),
+            "awaiting_approval" => Some(Self::AwaitingApproval),
+            "done" => Some(Self::Done),
+            "failed" => Some(Self::Failed),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Done | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// A step of a run with the command it started, if it has started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRunStepResponse {
+    #[serde(flatten)]
+    pub step: WorkflowStep,
+    pub command_id: Option<Uuid>,
+    pub status: Option<CommandStatus>,
+}
+
+/// Workflow run response: the whole sequence of steps as one unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRunResponse {
+    pub id: Uuid,
+    /// `None` once the workflow has been deleted.
+    pub workflow_id: Option<String>,
+    pub name: String,
+    pub status: WorkflowRunStatus,
+    /// Index into `steps` of the step running or awaiting approval (last step once done).
+    pub current_step: u32,
+    pub input: String,
+    pub repo_path: Option<String>,
+    pub chat_id: Option<Uuid>,
+    pub steps: Vec<WorkflowRunStepResponse>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Schedule response. `cron` is a 5-field expression evaluated in UTC.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub cron: String,
+    pub input: String,
+    pub context_mode: Option<String>,
+    pub repo_path: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+    pub max_runtime_secs: Option<u64>,
+    pub enabled: bool,
+    /// Next firing; `None` while disabled.
+    pub next_run_at: Option<String>,
+    /// Last firing that created a command (skipped firings don't count).
+    pub last_run_at: Option<String>,
+    /// Most recent command the schedule created.
+    pub last_command_id: Option<Uuid>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Create schedule request. Each firing sends `input` as a new command in a new chat, like
+/// `POST /api/commands` from the creating device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateScheduleRequest {
+    pub name: String,
+    pub cron: String,
+    pub input: String,
+    #[serde(default)]
+    pub context_mode: Option<TemplateId>,
+    #[serde(default)]
+    pub repo_path: Option<String>,
+    #[serde(default)]
+    pub translator_model: Option<String>,
+    #[serde(default)]
+    pub workload_model: Option<String>,
+    #[serde(default)]
+    pub max_runtime_secs: Option<u64>,
+    /// Defaults to true.
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Update schedule request. `None` leaves a field unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateScheduleRequest {
+    pub name: Option<String>,
+    pub cron: Option<String>,
+    pub input: Option<String>,
+    pub context_mode: Option<TemplateId>,
+    pub repo_path: Option<String>,
+    pub translator_model: Option<String>,
+    pub workload_model: Option<String>,
+    pub max_runtime_secs: Option<u64>,
+    pub enabled: Option<bool>,
+}
+
 /// Add repo request.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddRepoRequest {
@@ -320,7 +1397,6 @@ pub struct RepoResponse {
 mod tests {
     use super::*;
     use crate::ws_types;
-    use serde_json;
 
     fn random_uuid() -> Uuid {
         Uuid::new_v4()
@@ -331,15 +1407,36 @@ mod tests {
         let req = CreateCommandRequest {
             input: "add tests".to_string(),
             repo_path: Some("~/repos/foo".to_string()),
-            context_mode: Some("continue".to_string()),
+            context_mode: TemplateId::parse("continue"),
             translator_model: Some("claude-4".to_string()),
             workload_model: Some("cursor".to_string()),
             cursor_chat_id: None,
+            max_runtime_secs: Some(600),
+            chat_id: None,
+            review_prompt: true,
+            idempotency_key: Some("retry-1".to_string()),
         };
         let json = serde_json::to_string(&req).unwrap();
         let parsed: CreateCommandRequest = serde_json::from_str(&json).unwrap();
         assert_eq!(parsed.input, req.input);
         assert_eq!(parsed.repo_path, req.repo_path);
+        assert_eq!(parsed.max_runtime_secs, Some(600));
+        assert_eq!(parsed.idempotency_key.as_deref(), Some("retry-1"));
+        assert_eq!(parsed.context_mode, req.context_mode);
+        assert!(parsed.review_prompt);
+    }
+
+    #[test]
+    fn template_id_rejects_invalid() {
+        assert!(TemplateId::parse("bug_triage").is_some());
+        assert!(TemplateId::parse("release-notes-2").is_some());
+        for bad in ["", "Sprint", "a b", "../x", &"x".repeat(65)] {
+            assert!(TemplateId::parse(bad).is_none(), "{:?}", bad);
+        }
+        let err = serde_json::from_str::<CreateCommandRequest>(
+            r#"{"input":"x","repo_path":null,"context_mode":"Not Valid","translator_model":null,"workload_model":null,"cursor_chat_id":null}"#,
+        );
+        assert!(err.is_err());
     }
 
     #[test]
@@ -358,6 +1455,18 @@ mod tests {
             translator_model: None,
             workload_model: None,
             cursor_chat_id: None,
+            queue_position: None,
+            max_runtime_secs: None,
+            failure_reason: None,
+            chat_id: None,
+            duration_ms: None,
+            phases: None,
+            status_history: None,
+            review_prompt: false,
+            proposed_prompt: None,
+            approved_prompt: None,
+            schedule_id: None,
+            rerun_of: None,
             created_at: "2025-01-01T00:00:00Z".to_string(),
             updated_at: "2025-01-01T00:00:01Z".to_string(),
         };
@@ -384,10 +1493,78 @@ mod tests {
     fn setup_response_serde_roundtrip() {
         let resp = SetupResponse {
             totp_secret: "JBSWY3DPEHPK3PXP".to_string(),
+            recovery_codes: vec!["abcd-efgh-ijkl-mnop".to_string()],
         };
         let json = serde_json::to_string(&resp).unwrap();
         let parsed: SetupResponse = serde_json::from_str(&json).unwrap();
         assert_eq!(parsed.totp_secret, resp.totp_secret);
+        assert_eq!(parsed.recovery_codes, resp.recovery_codes);
+    }
+
+    #[test]
+    fn login_request_accepts_recovery_code_without_totp() {
+        let req: LoginRequest = serde_json::from_str(
+            r#"{"device_api_key":"k","password":"p","recovery_code":"abcd-efgh-ijkl-mnop"}"#,
+        )
+        .unwrap();
+        assert_eq!(req.totp_code, "");
+        assert_eq!(req.recovery_code.as_deref(), Some("abcd-efgh-ijkl-mnop"));
+    }
+
+    #[test]
+    fn user_role_serde_matches_as_str() {
+        for role in [UserRole::Owner, UserRole::Member, UserRole::Viewer] {
+            let json = serde_json::to_string(&role).unwrap();
+            assert_eq!(json, format!("\"{}\"", role.as_str()));
+            assert_eq!(UserRole::parse(role.as_str()), Some(role));
+        }
+        assert_eq!(UserRole::parse("admin"), None);
+    }
+
+    #[test]
+    fn token_scope_serde_matches_as_str() {
+        for scope in TokenScope::ALL {
+            let json = serde_json::to_string(&scope).unwrap();
+            assert_eq!(json, format!("\"{}\"", scope.as_str()));
+            assert_eq!(TokenScope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(TokenScope::parse("commands:*"), None);
+    }
+
+    #[test]
+    fn command_status_parse_roundtrip() {
+        for s in [
+            CommandStatus::Pending,
+            CommandStatus::Queued,
+            CommandStatus::Running,
+            CommandStatus::AwaitingApproval,
+            CommandStatus::Done,
+            CommandStatus::Failed,
+            CommandStatus::Cancelled,
+        ] {
+            assert_eq!(CommandStatus::parse(s.as_str()), Some(s));
+        }
+        assert_eq!(CommandStatus::parse("bogus"), None);
+        assert!(CommandStatus::Cancelled.is_terminal());
+        assert!(!CommandStatus::Running.is_terminal());
+        assert!(!CommandStatus::AwaitingApproval.is_terminal());
+    }
+
+    #[test]
+    fn command_status_transitions_only_move_forward() {
+        use CommandStatus::*;
+        assert!(Pending.can_transition_to(Running));
+        assert!(Queued.can_transition_to(Queued));
+        assert!(Running.can_transition_to(AwaitingApproval));
+        assert!(Pending.can_transition_to(Cancelled));
+        assert!(!Running.can_transition_to(Queued));
+        // Only approving the prompt sends a reviewed command back.
+        assert!(!AwaitingApproval.can_transition_to(Pending));
+        assert!(!AwaitingApproval.can_transition_to(Queued));
+        assert!(!AwaitingApproval.can_transition_to(Running));
+        assert!(!Done.can_transition_to(Running));
+        assert!(!Failed.can_transition_to(Done));
+        assert!(!Done.can_transition_to(Done));
     }
 
     #[test]
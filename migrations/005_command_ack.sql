@@ -0,0 +1,6 @@
+-- Migration 005: Executor delivery acknowledgement
+-- acked_at is set when the executor sends command_ack for a pending command on its current
+-- connection. Pending commands with acked_at NULL are replayed when an executor authenticates;
+-- acks on still-pending commands are cleared when that connection drops.
+
+ALTER TABLE commands ADD COLUMN acked_at TEXT;
@@ -0,0 +1,5 @@
+-- Migration 006: Executor queue position
+-- Set while a command waits in the executor's scheduler (status 'queued'): number of commands
+-- that will start before it. Cleared when the command leaves the queue.
+
+ALTER TABLE commands ADD COLUMN queue_position INTEGER;
@@ -0,0 +1,9 @@
+-- Migration 007: Command timeouts and failure reasons
+-- max_runtime_secs: per-command runtime limit requested at creation (NULL = executor default).
+-- failure_reason: machine-readable reason for status 'failed' (timeout, no_output, stale, agent_error).
+
+ALTER TABLE commands ADD COLUMN max_runtime_secs INTEGER;
+ALTER TABLE commands ADD COLUMN failure_reason TEXT;
+
+-- Stale-run reaper scans running commands by updated_at.
+CREATE INDEX IF NOT EXISTS idx_commands_status_updated_at ON commands(status, updated_at);
@@ -0,0 +1,11 @@
+-- Migration 008: Indexes for paginated, filtered command listing
+-- GET /api/commands pages newest-first by (created_at, id) and filters by status, repo_path,
+-- context_mode, model (translator or workload) and cursor_chat_id.
+
+CREATE INDEX IF NOT EXISTS idx_commands_device_created ON commands(device_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_commands_created ON commands(created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_commands_repo_created ON commands(repo_path, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_commands_context_created ON commands(context_mode, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_commands_chat_created ON commands(cursor_chat_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_commands_translator_model ON commands(translator_model, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_commands_workload_model ON commands(workload_model, created_at DESC);
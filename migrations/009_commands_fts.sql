@@ -0,0 +1,35 @@
+-- Migration 009: Full-text search over commands
+-- External-content FTS5 index over commands.input/output/summary (keyed by commands.rowid),
+-- kept in sync by triggers. Queried by GET /api/commands/search.
+
+CREATE VIRTUAL TABLE IF NOT EXISTS commands_fts USING fts5(
+  input,
+  output,
+  summary,
+  content = 'commands',
+  content_rowid = 'rowid',
+  tokenize = 'porter unicode61'
+);
+
+CREATE TRIGGER IF NOT EXISTS commands_fts_ai AFTER INSERT ON commands BEGIN
+  INSERT INTO commands_fts(rowid, input, output, summary)
+  VALUES (new.rowid, new.input, new.output, new.summary);
+END;
+
+CREATE TRIGGER IF NOT EXISTS commands_fts_ad AFTER DELETE ON commands BEGIN
+  INSERT INTO commands_fts(commands_fts, rowid, input, output, summary)
+  VALUES ('delete', old.rowid, old.input, old.output, old.summary);
+END;
+
+-- update_command rewrites these columns on every PATCH; only reindex when text changed.
+CREATE TRIGGER IF NOT EXISTS commands_fts_au AFTER UPDATE OF input, output, summary ON commands
+WHEN old.input IS NOT new.input OR old.output IS NOT new.output OR old.summary IS NOT new.summary
+BEGIN
+  INSERT INTO commands_fts(commands_fts, rowid, input, output, summary)
+  VALUES ('delete', old.rowid, old.input, old.output, old.summary);
+  INSERT INTO commands_fts(rowid, input, output, summary)
+  VALUES (new.rowid, new.input, new.output, new.summary);
+END;
+
+-- Index commands created before this migration.
+INSERT INTO commands_fts(commands_fts) VALUES ('rebuild');
@@ -0,0 +1,66 @@
+-- Migration 010: First-class conversations
+-- A chat groups the commands (turns) of one conversation. title_pending = 1 while the title is
+-- a placeholder; the first turn replaces it. cursor_chat_id is the Cursor CLI session resumed by
+-- later turns, recorded when the executor reports it.
+
+CREATE TABLE IF NOT EXISTS chats (
+  id               TEXT PRIMARY KEY,
+  admin_id         TEXT NOT NULL REFERENCES admin(id) ON DELETE CASCADE,
+  title            TEXT NOT NULL,
+  title_pending    INTEGER NOT NULL DEFAULT 0,
+  repo_path        TEXT,
+  translator_model TEXT,
+  workload_model   TEXT,
+  cursor_chat_id   TEXT,
+  archived         INTEGER NOT NULL DEFAULT 0,
+  last_activity_at TEXT NOT NULL,
+  created_at       TEXT NOT NULL,
+  updated_at       TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_chats_admin_activity ON chats(admin_id, archived, last_activity_at DESC);
+CREATE INDEX IF NOT EXISTS idx_chats_cursor_chat_id ON chats(admin_id, cursor_chat_id);
+
+ALTER TABLE commands ADD COLUMN chat_id TEXT REFERENCES chats(id) ON DELETE CASCADE;
+CREATE INDEX IF NOT EXISTS idx_commands_chat_created ON commands(chat_id, created_at);
+
+-- Backfill: one chat per (admin, cursor_chat_id) and one per command without a Cursor chat,
+-- titled from the first turn and carrying its repo and models.
+CREATE TEMP TABLE _chat_groups AS
+SELECT admin_id, group_key, first_id, created_at, last_activity_at,
+       substr(h, 1, 8) || '-' || substr(h, 9, 4) || '-4' || substr(h, 14, 3) || '-a'
+         || substr(h, 18, 3) || '-' || substr(h, 21, 12) AS chat_id
+FROM (
+  SELECT
+    d.admin_id AS admin_id,
+    COALESCE(c.cursor_chat_id, 'cmd:' || c.id) AS group_key,
+    (SELECT c2.id FROM commands c2
+     WHERE c2.device_id IN (SELECT id FROM devices WHERE admin_id = d.admin_id)
+       AND COALESCE(c2.cursor_chat_id, 'cmd:' || c2.id) = COALESCE(c.cursor_chat_id, 'cmd:' || c.id)
+     ORDER BY c2.created_at ASC, c2.rowid ASC
+     LIMIT 1) AS first_id,
+    MIN(c.created_at) AS created_at,
+    MAX(c.updated_at) AS last_activity_at,
+    lower(hex(randomblob(16))) AS h
+  FROM commands c
+  JOIN devices d ON c.device_id = d.id
+  GROUP BY d.admin_id, COALESCE(c.cursor_chat_id, 'cmd:' || c.id)
+);
+
+INSERT INTO chats (id, admin_id, title, title_pending, repo_path, translator_model, workload_model,
+                   cursor_chat_id, archived, last_activity_at, created_at, updated_at)
+SELECT g.chat_id, g.admin_id, substr(trim(substr(f.input, 1, instr(f.input || char(10), char(10)) - 1)), 1, 60), 0,
+       f.repo_path, f.translator_model, f.workload_model, f.cursor_chat_id, 0,
+       g.last_activity_at, g.created_at, g.last_activity_at
+FROM _chat_groups g
+JOIN commands f ON f.id = g.first_id;
+
+UPDATE commands SET chat_id = (
+  SELECT g.chat_id FROM _chat_groups g
+  JOIN devices d ON d.admin_id = g.admin_id
+  WHERE d.id = commands.device_id
+    AND g.group_key = COALESCE(commands.cursor_chat_id, 'cmd:' || commands.id)
+)
+WHERE chat_id IS NULL;
+
+DROP TABLE _chat_groups;
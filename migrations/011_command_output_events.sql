@@ -0,0 +1,17 @@
+-- Migration 011: Append-only command output log
+-- Output is stored as sequenced events instead of being overwritten on every update.
+-- kind: 'append' (content is added to the output so far) or 'replace' (content is the whole output).
+-- commands.output stays as the materialized result of applying the events in seq order.
+
+CREATE TABLE IF NOT EXISTS command_output_events (
+    command_id TEXT NOT NULL REFERENCES commands(id) ON DELETE CASCADE,
+    seq INTEGER NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('append', 'replace')),
+    content TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (command_id, seq)
+);
+
+-- Existing output becomes the first event of its command.
+INSERT INTO command_output_events (command_id, seq, kind, content, created_at)
+SELECT id, 1, 'replace', output, updated_at FROM commands WHERE output IS NOT NULL;
@@ -0,0 +1,24 @@
+-- Migration 012: Tool-call timeline per command
+-- One row per agent tool call, upserted by the executor when the call starts and completes.
+-- kind: shell | read | write | search | list | other; target is the command line, path or pattern.
+-- args is the tool's JSON arguments; result is truncated by the executor.
+
+CREATE TABLE IF NOT EXISTS command_tool_calls (
+    command_id TEXT NOT NULL REFERENCES commands(id) ON DELETE CASCADE,
+    call_id TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    tool TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    target TEXT,
+    args TEXT,
+    started_at TEXT NOT NULL,
+    completed_at TEXT,
+    duration_ms INTEGER,
+    exit_code INTEGER,
+    error TEXT,
+    result TEXT,
+    PRIMARY KEY (command_id, call_id)
+);
+
+-- "Which runs ran `cargo test`" / "which runs wrote src/lib.rs" filters.
+CREATE INDEX IF NOT EXISTS idx_command_tool_calls_kind ON command_tool_calls(kind, command_id);
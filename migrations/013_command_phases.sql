@@ -0,0 +1,21 @@
+-- Migration 013: Per-phase timing and usage of command runs
+-- One row per pipeline phase (translate, create_chat, run, summarize), reported by the executor
+-- with the final status. Token and cost columns are NULL unless the agent reported them.
+-- commands.duration_ms is the total of the command's phases.
+
+CREATE TABLE IF NOT EXISTS command_phases (
+    command_id TEXT NOT NULL REFERENCES commands(id) ON DELETE CASCADE,
+    phase TEXT NOT NULL,
+    model TEXT,
+    duration_ms INTEGER NOT NULL,
+    input_tokens INTEGER,
+    output_tokens INTEGER,
+    cache_read_tokens INTEGER,
+    cache_write_tokens INTEGER,
+    cost_usd REAL,
+    PRIMARY KEY (command_id, phase)
+);
+
+CREATE INDEX IF NOT EXISTS idx_command_phases_model ON command_phases(model);
+
+ALTER TABLE commands ADD COLUMN duration_ms INTEGER;
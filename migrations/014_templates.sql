@@ -0,0 +1,99 @@
+-- Migration 014: Command templates
+-- Templates are selected by id through commands.context_mode. The executor receives the body with
+-- the command and fills in variables: {repo} (workspace path) and {next_sprint_number}
+-- (next free SPRINT_NNN number in the repo). Other braces are passed through as-is.
+-- Seeded with the templates previously hard-coded in the executor.
+
+CREATE TABLE IF NOT EXISTS templates (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    body TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO templates (id, name, description, body, created_at, updated_at) VALUES (
+    'monorepo_init',
+    'Monorepo init',
+    'Create plans/ folder and PLAN_INITIAL.md, then interactive review',
+    'Context: User selected MONOREPO INIT template. Workspace: "{repo}".
+
+Template output folder placement: Put documents in plans/, security_reviews/, or sprints/ as appropriate. Use repo root (e.g. sprints/SPRINT_001.md) for repo-wide scope; if the work only touches one package in a monorepo, use that package''s subdirectory (e.g. packages/foo/sprints/SPRINT_001.md).
+
+Create plans/ folder and PLAN_INITIAL.md (at root or packages/{name}/plans/ if package-scoped). Output for interactive review.
+',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+);
+
+INSERT OR IGNORE INTO templates (id, name, description, body, created_at, updated_at) VALUES (
+    'gap_analysis',
+    'Gap analysis',
+    'Write PLAN_GAP_{x}.md, interactive review',
+    'Context: User selected GAP ANALYSIS template. Workspace: "{repo}".
+
+Template output folder placement: Put documents in plans/, security_reviews/, or sprints/ as appropriate. Use repo root (e.g. sprints/SPRINT_001.md) for repo-wide scope; if the work only touches one package in a monorepo, use that package''s subdirectory (e.g. packages/foo/sprints/SPRINT_001.md).
+
+Write PLAN_GAP_{x}.md in plans/ (or packages/{name}/plans/ if package-scoped). Output for interactive review.
+',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+);
+
+INSERT OR IGNORE INTO templates (id, name, description, body, created_at, updated_at) VALUES (
+    'security_review',
+    'Security review',
+    'Write a security review document, interactive review',
+    'Context: User selected SECURITY REVIEW template. Workspace: "{repo}".
+
+Template output folder placement: Put documents in plans/, security_reviews/, or sprints/ as appropriate. Use repo root (e.g. sprints/SPRINT_001.md) for repo-wide scope; if the work only touches one package in a monorepo, use that package''s subdirectory (e.g. packages/foo/sprints/SPRINT_001.md).
+
+Produce a security review document. Place it in security_reviews/ at root, or packages/{name}/security_reviews/ if only touching one package. Include findings summary, severity levels, and recommended actions.
+',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+);
+
+INSERT OR IGNORE INTO templates (id, name, description, body, created_at, updated_at) VALUES (
+    'feature_plan',
+    'Feature plan',
+    'Write PLAN_FEAT_{x}.md, interactive review',
+    'Context: User selected FEATURE PLAN template. Workspace: "{repo}".
+
+Template output folder placement: Put documents in plans/, security_reviews/, or sprints/ as appropriate. Use repo root (e.g. sprints/SPRINT_001.md) for repo-wide scope; if the work only touches one package in a monorepo, use that package''s subdirectory (e.g. packages/foo/sprints/SPRINT_001.md).
+
+Write PLAN_FEAT_{x}.md in plans/ (or packages/{name}/plans/ if package-scoped). Output for interactive review.
+',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+);
+
+INSERT OR IGNORE INTO templates (id, name, description, body, created_at, updated_at) VALUES (
+    'sprint',
+    'Sprint',
+    'Select plan, create/continue sprint doc, implement',
+    'Context: User selected SPRINT template. You are in workspace: "{repo}".
+
+Template output folder placement: Put documents in plans/, security_reviews/, or sprints/ as appropriate. Use repo root (e.g. sprints/SPRINT_001.md) for repo-wide scope; if the work only touches one package in a monorepo, use that package''s subdirectory (e.g. packages/foo/sprints/SPRINT_001.md).
+
+Sprint workflow: Create a sprint document in sprints/ (or packages/{name}/sprints/ if only touching one package). The next sprint number is {next_sprint_number} (e.g. SPRINT_{next_sprint_number}.md); use the next available number if that file exists. Base it on the user''s request and any prior work (security audit, gap analysis, etc.).
+Write the sprint doc to the repo. Output its full contents for user review.
+IMPORTANT: Do NOT implement the changes yet. The user will review the sprint doc, then send a follow-up message (e.g. "implement it" or "approved") to execute the sprint.
+Exception: If the user explicitly asks to "implement" or "execute" an existing sprint, do that instead of creating a new doc.
+',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+);
+
+INSERT OR IGNORE INTO templates (id, name, description, body, created_at, updated_at) VALUES (
+    'commit',
+    'Commit',
+    'Stage and commit changes from the conversation; pre-commit may run long. NEVER use --no-verify.',
+    'Context: User selected COMMIT template. Workspace: "{repo}".
+Commit the changes made in this conversation (stage and commit with an appropriate message). Pre-commit hooks (lint, test, format) often run and can take a long time. In your output, clearly describe what happened: whether pre-commit passed or failed, what ran, and any errors if it failed. The user needs to know the outcome either way. NEVER use --no-verify.
+Output format for pre-commit report: Keep it compact. Put section numbers and headers on the same line (e.g. "1. Format checks" not "1.\nFormat checks"). Use a proper Markdown unordered list (-) for each check item so they render as separate list items (e.g. "- operator (Rust fmt) – ✓" on its own line), not as continuation of the section header.
+',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+);
@@ -0,0 +1,9 @@
+-- Migration 015: Prompt review before the workload run
+-- review_prompt: the executor stops after translation and reports status 'awaiting_approval'
+-- with proposed_prompt. The controller approves (approved_prompt = proposed_prompt), edits
+-- (approved_prompt = the edit) or rejects (status 'cancelled'). An approved command goes back
+-- to 'pending' and the executor runs approved_prompt without translating again.
+
+ALTER TABLE commands ADD COLUMN review_prompt INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE commands ADD COLUMN proposed_prompt TEXT;
+ALTER TABLE commands ADD COLUMN approved_prompt TEXT;
@@ -0,0 +1,53 @@
+-- Migration 016: Multi-step workflows
+-- A workflow is an ordered list of steps (JSON array of {name, context_mode, input, approval}).
+-- A run copies the steps and the relayer sends them as commands one at a time, in the run's chat
+-- (and so its Cursor chat), starting the next step when the previous command is done. Steps with
+-- approval = true wait in 'awaiting_approval' until the controller approves. A failed or
+-- cancelled step ends the run. commands.workflow_run_id / workflow_step link a step's command.
+
+CREATE TABLE IF NOT EXISTS workflows (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    steps TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS workflow_runs (
+    id TEXT PRIMARY KEY,
+    admin_id TEXT NOT NULL REFERENCES admin(id) ON DELETE CASCADE,
+    device_id TEXT NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    workflow_id TEXT REFERENCES workflows(id) ON DELETE SET NULL,
+    name TEXT NOT NULL,
+    steps TEXT NOT NULL,
+    input TEXT NOT NULL,
+    repo_path TEXT,
+    translator_model TEXT,
+    workload_model TEXT,
+    chat_id TEXT REFERENCES chats(id) ON DELETE SET NULL,
+    status TEXT NOT NULL CHECK (status IN ('running', 'awaiting_approval', 'done', 'failed', 'cancelled')),
+    current_step INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_workflow_runs_admin_created ON workflow_runs(admin_id, created_at DESC);
+
+ALTER TABLE commands ADD COLUMN workflow_run_id TEXT REFERENCES workflow_runs(id) ON DELETE SET NULL;
+ALTER TABLE commands ADD COLUMN workflow_step INTEGER;
+CREATE INDEX IF NOT EXISTS idx_commands_workflow_run ON commands(workflow_run_id, workflow_step);
+
+INSERT OR IGNORE INTO workflows (id, name, description, steps, created_at, updated_at) VALUES (
+    'sprint_to_commit',
+    'Sprint to commit',
+    'Write a sprint doc, implement it after approval, run the tests, then commit after approval',
+    '[
+      {"name": "Sprint", "context_mode": "sprint", "input": "{input}", "approval": false},
+      {"name": "Implement", "context_mode": null, "input": "Implement the sprint document you just wrote.", "approval": true},
+      {"name": "Run tests", "context_mode": null, "input": "Run the project''s test suite and fix any failures caused by the changes.", "approval": false},
+      {"name": "Commit", "context_mode": "commit", "input": "Commit the sprint''s changes.", "approval": true}
+    ]',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+);
@@ -0,0 +1,31 @@
+-- Migration 017: Scheduled commands
+-- A schedule sends a command on a cron expression (5 fields, UTC) with its template, repo and
+-- models. The relayer's scheduler creates the command for the schedule's device the same way
+-- POST /api/commands does, in a new chat per run. A firing is skipped while the schedule's
+-- previous command is still pending or running. next_run_at is NULL while disabled.
+-- commands.schedule_id records which schedule produced a command.
+
+CREATE TABLE IF NOT EXISTS schedules (
+    id TEXT PRIMARY KEY,
+    admin_id TEXT NOT NULL REFERENCES admin(id) ON DELETE CASCADE,
+    device_id TEXT NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    input TEXT NOT NULL,
+    context_mode TEXT,
+    repo_path TEXT,
+    translator_model TEXT,
+    workload_model TEXT,
+    max_runtime_secs INTEGER,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    next_run_at TEXT,
+    last_run_at TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_schedules_next_run ON schedules(next_run_at) WHERE enabled = 1;
+CREATE INDEX IF NOT EXISTS idx_schedules_admin ON schedules(admin_id, name);
+
+ALTER TABLE commands ADD COLUMN schedule_id TEXT REFERENCES schedules(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_commands_schedule ON commands(schedule_id, created_at DESC);
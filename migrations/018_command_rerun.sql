@@ -0,0 +1,6 @@
+-- Migration 018: Re-run a command
+-- POST /api/commands/{id}/rerun creates a new command from a finished one (same input, repo,
+-- template, models and chat unless overridden). rerun_of links the new command to its origin.
+
+ALTER TABLE commands ADD COLUMN rerun_of TEXT REFERENCES commands(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_commands_rerun_of ON commands(rerun_of);
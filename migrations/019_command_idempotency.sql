@@ -0,0 +1,9 @@
+-- Migration 019: Idempotency keys for command creation
+-- POST /api/commands accepts an Idempotency-Key header (or idempotency_key field). A replay
+-- of the same key from the same device within the retention window returns the original
+-- command instead of creating (and broadcasting) a second one. A key is cleared from its old
+-- command when it is reused after the window.
+
+ALTER TABLE commands ADD COLUMN idempotency_key TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_commands_idempotency_key
+    ON commands(device_id, idempotency_key) WHERE idempotency_key IS NOT NULL;
@@ -0,0 +1,21 @@
+-- Migration 020: Command status state machine and history
+-- Status updates must move a command forward (pending → queued → running → awaiting_approval
+-- → done/failed/cancelled); terminal statuses are final. The executor numbers its updates
+-- with an increasing revision and commands.revision keeps the last applied one, so a late or
+-- retried update is rejected instead of overwriting a newer status. Every status change is
+-- recorded in command_status_history with its actor ('controller', 'executor', 'relayer').
+-- History starts with this migration; earlier changes are not backfilled.
+
+ALTER TABLE commands ADD COLUMN revision INTEGER;
+
+CREATE TABLE IF NOT EXISTS command_status_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    command_id TEXT NOT NULL REFERENCES commands(id) ON DELETE CASCADE,
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    revision INTEGER,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_command_status_history_command ON command_status_history(command_id, id);
@@ -0,0 +1,6 @@
+-- Migration 021: Device revocation
+-- DELETE /api/devices/{id} revokes a device instead of deleting it, so the commands, schedules
+-- and workflow runs it created keep their history. A revoked device has revoked_at set and no
+-- token_hash: its device key no longer logs in and its JWTs are rejected.
+
+ALTER TABLE devices ADD COLUMN revoked_at TEXT;
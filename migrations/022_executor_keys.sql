@@ -0,0 +1,26 @@
+-- Migration 022: Per-executor credentials
+-- Executors are rows in devices (role 'executor') and authenticate with keys stored here as
+-- SHA-256 hashes: keys are 256-bit random values checked on every executor request, so a slow
+-- hash buys nothing. A device can hold several keys so a new one can be issued while the old
+-- one keeps working until expires_at. EXECUTOR_API_KEY from the relayer's env is imported as a
+-- key of the admin's executor on first use (label 'env') and can be expired or revoked like
+-- any other; once its row exists it is never imported again.
+
+CREATE TABLE IF NOT EXISTS executor_keys (
+    id TEXT PRIMARY KEY,
+    device_id TEXT NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    key_hash TEXT NOT NULL UNIQUE,
+    prefix TEXT NOT NULL,
+    label TEXT,
+    created_at TEXT NOT NULL,
+    expires_at TEXT,
+    revoked_at TEXT,
+    last_used_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_executor_keys_device ON executor_keys(device_id, created_at);
+
+-- A revoked executor no longer counts toward the one-executor-per-admin limit.
+DROP INDEX IF EXISTS idx_one_executor;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_one_executor ON devices(admin_id)
+    WHERE role = 'executor' AND revoked_at IS NULL;
@@ -0,0 +1,22 @@
+-- Migration 023: Server-side sessions
+-- Login opens a session for the controller device; its id is the `jti` claim of every JWT issued
+-- for it, and a JWT is only accepted while its session is live. The session's refresh token
+-- (`<session id>.<secret>`) is stored as a SHA-256 hash and replaced on every refresh; presenting
+-- a replaced token again means it leaked, so the session is revoked (revoked_reason 'reuse').
+-- expires_at slides forward on each refresh. Logout, revoking the session or revoking its
+-- device set revoked_at.
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    admin_id TEXT NOT NULL REFERENCES admin(id) ON DELETE CASCADE,
+    device_id TEXT NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    refresh_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    last_used_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    revoked_at TEXT,
+    revoked_reason TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_admin ON sessions(admin_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_sessions_device ON sessions(device_id);
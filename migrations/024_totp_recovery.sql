@@ -0,0 +1,18 @@
+-- Migration 024: TOTP replay protection and recovery codes
+-- totp_last_step is the 30s time step of the last TOTP code accepted at login; a code from that
+-- step or an earlier one is refused, so a code can't be replayed inside its window.
+-- Recovery codes are one-time alternatives to a TOTP code, issued as a set at setup and device
+-- registration (replacing the previous set). They are 80-bit random values stored as SHA-256
+-- hashes; used_at is set when one logs in.
+
+ALTER TABLE admin ADD COLUMN totp_last_step INTEGER;
+
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    id TEXT PRIMARY KEY,
+    admin_id TEXT NOT NULL REFERENCES admin(id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    used_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_recovery_codes_admin ON recovery_codes(admin_id);
@@ -0,0 +1,26 @@
+-- Migration 025: Passkeys (WebAuthn)
+-- A controller device can register passkeys; login with a passkey and the password replaces the
+-- TOTP code, and the passkey identifies the device so its device key needn't be typed.
+-- public_key is the uncompressed P-256 point (only ES256 is supported); credential_id is
+-- base64url. Challenges are single use: register challenges are bound to the device asking,
+-- login challenges (device_id NULL) to nothing.
+
+CREATE TABLE IF NOT EXISTS webauthn_credentials (
+    id TEXT PRIMARY KEY,
+    device_id TEXT NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    credential_id TEXT NOT NULL UNIQUE,
+    public_key BLOB NOT NULL,
+    sign_count INTEGER NOT NULL DEFAULT 0,
+    name TEXT,
+    created_at TEXT NOT NULL,
+    last_used_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_webauthn_credentials_device ON webauthn_credentials(device_id);
+
+CREATE TABLE IF NOT EXISTS webauthn_challenges (
+    challenge TEXT PRIMARY KEY,
+    purpose TEXT NOT NULL CHECK (purpose IN ('register', 'login')),
+    device_id TEXT REFERENCES devices(id) ON DELETE CASCADE,
+    expires_at TEXT NOT NULL
+);
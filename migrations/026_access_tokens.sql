@@ -0,0 +1,22 @@
+-- Migration 026: Personal access tokens
+-- Long-lived bearer tokens for scripts and CI, created from a controller session. A token acts for
+-- the admin as the device that created it (commands it creates are attributed to that device) and
+-- only on routes covered by its scopes, stored space-separated (e.g. 'commands:read files:read').
+-- Tokens are `pat_` + 64 hex characters, stored as SHA-256 hashes with their first characters
+-- as prefix. A token stops working once expired, revoked, or when its device is revoked.
+
+CREATE TABLE IF NOT EXISTS access_tokens (
+    id TEXT PRIMARY KEY,
+    admin_id TEXT NOT NULL REFERENCES admin(id) ON DELETE CASCADE,
+    device_id TEXT NOT NULL REFERENCES devices(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    prefix TEXT NOT NULL,
+    scopes TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT,
+    last_used_at TEXT,
+    revoked_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_access_tokens_admin ON access_tokens(admin_id, created_at);
@@ -0,0 +1,28 @@
+-- Migration 027: Multiple users with roles
+-- Each `admin` row is now a user account. The account created at setup is the owner; others join
+-- through invitations and get the invitation's role. Owners manage users, invitations, the
+-- executor and repos, and see every user's commands; members see only their own commands, chats,
+-- schedules and workflow runs; viewers see everyone's but can't change anything. The executor,
+-- repos, templates and workflow definitions are shared by all users.
+-- Removing a user sets disabled_at and revokes their devices (so their sessions and access tokens
+-- stop); their commands are kept.
+-- Invitation codes are 256-bit random values stored as SHA-256 hashes, usable once until
+-- expires_at.
+
+ALTER TABLE admin ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'member', 'viewer'));
+ALTER TABLE admin ADD COLUMN disabled_at TEXT;
+
+CREATE TABLE IF NOT EXISTS invitations (
+    id TEXT PRIMARY KEY,
+    code_hash TEXT NOT NULL UNIQUE,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'member', 'viewer')),
+    created_by TEXT NOT NULL REFERENCES admin(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    accepted_at TEXT,
+    accepted_by TEXT REFERENCES admin(id) ON DELETE SET NULL,
+    revoked_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_invitations_created ON invitations(created_at);
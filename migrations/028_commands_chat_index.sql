@@ -0,0 +1,6 @@
+-- Migration 028: Index commands by chat
+-- 010 created this index as idx_commands_chat_created, the name 008 already uses for the
+-- cursor_chat_id index, so IF NOT EXISTS skipped it and chat listings and chat history scanned
+-- commands.
+
+CREATE INDEX IF NOT EXISTS idx_commands_chat_id_created ON commands(chat_id, created_at);
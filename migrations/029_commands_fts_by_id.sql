@@ -0,0 +1,41 @@
+-- Migration 029: Key the command search index by command id
+-- 009 indexed commands as external content keyed on commands.rowid, which VACUUM may renumber
+-- (commands has a TEXT primary key), desyncing the index from the rows it points at. The index
+-- now stores its own copy of the text with the command id in an unindexed column.
+-- Output changes on every PATCH while a command runs, so a command is indexed when created
+-- (input) and reindexed once when it finishes, not on every change.
+
+DROP TRIGGER IF EXISTS commands_fts_ai;
+DROP TRIGGER IF EXISTS commands_fts_ad;
+DROP TRIGGER IF EXISTS commands_fts_au;
+DROP TABLE IF EXISTS commands_fts;
+
+CREATE VIRTUAL TABLE commands_fts USING fts5(
+  command_id UNINDEXED,
+  input,
+  output,
+  summary,
+  tokenize = 'porter unicode61'
+);
+
+CREATE TRIGGER commands_fts_ai AFTER INSERT ON commands BEGIN
+  INSERT INTO commands_fts(command_id, input, output, summary)
+  VALUES (new.id, new.input, new.output, new.summary);
+END;
+
+CREATE TRIGGER commands_fts_ad AFTER DELETE ON commands BEGIN
+  DELETE FROM commands_fts WHERE command_id = old.id;
+END;
+
+CREATE TRIGGER commands_fts_au AFTER UPDATE OF status, input, output, summary ON commands
+WHEN new.status IN ('done', 'failed', 'cancelled')
+  AND (old.status IS NOT new.status OR old.input IS NOT new.input
+       OR old.output IS NOT new.output OR old.summary IS NOT new.summary)
+BEGIN
+  DELETE FROM commands_fts WHERE command_id = old.id;
+  INSERT INTO commands_fts(command_id, input, output, summary)
+  VALUES (new.id, new.input, new.output, new.summary);
+END;
+
+INSERT INTO commands_fts(command_id, input, output, summary)
+SELECT id, input, output, summary FROM commands;
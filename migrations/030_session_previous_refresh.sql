@@ -0,0 +1,10 @@
+-- Migration 030: Remember each session's previous refresh token
+-- 023 revoked a session whenever its id came with a secret other than the current one, so
+-- anyone who knew a session id (it is the JWT's jti) could revoke it with a made-up secret.
+-- Only presenting the token a rotation replaced (previous_hash) now counts as reuse; unknown
+-- secrets are just invalid. Within a short grace after rotated_at the previous token still
+-- rotates, so clients that refresh concurrently (e.g. tabs sharing one token) don't revoke
+-- their own session.
+
+ALTER TABLE sessions ADD COLUMN previous_hash TEXT;
+ALTER TABLE sessions ADD COLUMN rotated_at TEXT;
@@ -0,0 +1,36 @@
+-- Migration 031: Find a command's search row by rowid
+-- 029's triggers found a command's row by its command_id, an unindexed FTS5 column, so every
+-- finished, edited or deleted command scanned the whole index. Each command now records the
+-- rowid of its search row (search_rowid; FTS5 rowids are stable, unlike commands.rowid, which
+-- VACUUM may renumber) and the triggers look it up by that.
+
+ALTER TABLE commands ADD COLUMN search_rowid INTEGER;
+
+DROP TRIGGER IF EXISTS commands_fts_ai;
+DROP TRIGGER IF EXISTS commands_fts_ad;
+DROP TRIGGER IF EXISTS commands_fts_au;
+DELETE FROM commands_fts;
+
+INSERT INTO commands_fts(rowid, command_id, input, output, summary)
+SELECT rowid, id, input, output, summary FROM commands;
+UPDATE commands SET search_rowid = rowid;
+
+CREATE TRIGGER commands_fts_ai AFTER INSERT ON commands BEGIN
+  INSERT INTO commands_fts(command_id, input, output, summary)
+  VALUES (new.id, new.input, new.output, new.summary);
+  UPDATE commands SET search_rowid = last_insert_rowid() WHERE id = new.id;
+END;
+
+CREATE TRIGGER commands_fts_ad AFTER DELETE ON commands BEGIN
+  DELETE FROM commands_fts WHERE rowid = old.search_rowid;
+END;
+
+CREATE TRIGGER commands_fts_au AFTER UPDATE OF status, input, output, summary ON commands
+WHEN new.status IN ('done', 'failed', 'cancelled')
+  AND (old.status IS NOT new.status OR old.input IS NOT new.input
+       OR old.output IS NOT new.output OR old.summary IS NOT new.summary)
+BEGIN
+  DELETE FROM commands_fts WHERE rowid = old.search_rowid;
+  INSERT INTO commands_fts(rowid, command_id, input, output, summary)
+  VALUES (old.search_rowid, new.id, new.input, new.output, new.summary);
+END;
@@ -0,0 +1,6 @@
+-- Migration 032: Outcome of each command phase
+-- Phases that failed or were stopped used to go unrecorded, so a failed run's phases didn't
+-- show where it spent its time. outcome is 'ok', 'failed' or 'cancelled' (see
+-- shared::phase_outcomes); NULL for phases reported before it existed.
+
+ALTER TABLE command_phases ADD COLUMN outcome TEXT;